@@ -0,0 +1,98 @@
+//! `step_slice` against stepping through the same actions one `next` at a time.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use state_machine::{state_machine, Action, State};
+
+#[derive(Debug, Default)]
+pub struct Even(u64);
+#[derive(Debug)]
+pub struct Odd(u64);
+
+#[derive(Debug, Clone)]
+pub struct Add(u64);
+#[derive(Debug, Clone)]
+pub struct Reset;
+
+state_machine! {
+    Parity,
+    #[derive(Debug, Clone)] ParityAction,
+    initial Even,
+    Even { Add => Even | Odd, Reset => Even },
+    Odd { Add => Even | Odd, Reset => Even },
+}
+
+fn add(sum: u64, action: Add) -> Parity {
+    let sum = sum.wrapping_add(action.0);
+    if sum.is_multiple_of(2) {
+        Even(sum).into()
+    } else {
+        Odd(sum).into()
+    }
+}
+
+impl State<Parity, Add> for Even {
+    fn next(self, action: Add) -> Parity {
+        add(self.0, action)
+    }
+}
+
+impl State<Parity, Add> for Odd {
+    fn next(self, action: Add) -> Parity {
+        add(self.0, action)
+    }
+}
+
+impl State<Parity, Reset> for Even {
+    fn next(self, _action: Reset) -> Parity {
+        Even(0).into()
+    }
+}
+
+impl State<Parity, Reset> for Odd {
+    fn next(self, _action: Reset) -> Parity {
+        Even(0).into()
+    }
+}
+
+fn actions() -> Vec<ParityAction> {
+    (0..4096)
+        .map(|i| match i % 64 {
+            63 => Reset.into(),
+            _ => Add(i).into(),
+        })
+        .collect()
+}
+
+fn step_slice(c: &mut Criterion) {
+    let actions = actions();
+    let mut group = c.benchmark_group("4096 actions");
+    group.bench_function("next per action", |b| {
+        b.iter(|| {
+            let mut state = Parity::new();
+            for action in black_box(&actions) {
+                state = state.next(action.clone()).unwrap();
+            }
+            state
+        })
+    });
+    group.bench_function("step per action", |b| {
+        b.iter(|| {
+            let mut state = Parity::new();
+            for action in black_box(&actions) {
+                state.step(action.clone()).unwrap();
+            }
+            state
+        })
+    });
+    group.bench_function("step_slice", |b| {
+        b.iter(|| {
+            let mut state = Parity::new();
+            state.step_slice(black_box(&actions)).unwrap();
+            state
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, step_slice);
+criterion_main!(benches);
@@ -9,7 +9,7 @@ struct ParseState {
     digits_exponent: Vec<u8>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 struct ParseSign;
 
 #[derive(Debug)]
@@ -40,18 +40,23 @@ struct Eos;
 state_machine! {
     FloatParser,
     Char,
+    max_size 128,
+    initial ParseSign,
+    terminal Finished,
     ParseSign {
         Sign | Digit => ParseDigitsBeforeDot
     },
 
     ParseDigitsBeforeDot {
         Digit => ParseDigitsBeforeDot,
-        Dot | Exponential => ParseDigitsAfterDot,
+        Dot => ParseDigitsAfterDot,
+        Exponential => ParseScientificNotation,
         Eos => Finished
     },
 
     ParseDigitsAfterDot {
-         Digit | Exponential => ParseDigitsAfterDot,
+         Digit => ParseDigitsAfterDot,
+         Exponential => ParseScientificNotationSign,
          Eos => Finished
     },
 
@@ -178,7 +183,7 @@ fn build_from_parsed(f: Finished) -> f64 {
 
 fn main() {
     let input = "3.141596";
-    let mut state = FloatParser::from(ParseSign);
+    let mut state = FloatParser::new();
     for c in input.chars() {
         let a = match c {
             '+' => Sign::Plus.into(),
@@ -190,26 +195,19 @@ fn main() {
         };
 
         state = match state.next(a) {
-            Err((s, a)) => panic!(
-                "Unexpected char when parsing float: state = {:#?}, action = {:#?}",
-                s, a
-            ),
+            Err(e) => panic!("Unexpected char when parsing float: {}", e),
             Ok(s) => s,
         }
     }
 
     state = match state.next(Eos.into()) {
-        Err((s, a)) => panic!(
-            "Unexpected char when parsing float: state = {:#?}, action = {:#?}",
-            s, a
-        ),
+        Err(e) => panic!("Unexpected char when parsing float: {}", e),
         Ok(s) => s,
     };
 
-    let float = if let FloatParser::Finished(parsed) = state {
-        build_from_parsed(parsed)
-    } else {
-        panic!("Not our terminal state");
+    let float = match state.into_terminal() {
+        Ok(parsed) => build_from_parsed(parsed),
+        Err(s) => panic!("Not our terminal state: {:#?}", s),
     };
 
     println!(
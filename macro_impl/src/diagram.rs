@@ -0,0 +1,216 @@
+//! Readers for the subsets of Mermaid `stateDiagram` and Graphviz DOT that map onto the DSL: one
+//! transition per edge, labeled by the actions taking it (`A | B`).
+
+use crate::scxml::{Document, ScxmlState, ScxmlTransition};
+
+fn state<'a>(document: &'a mut Document, id: &str) -> &'a mut ScxmlState {
+    let index = match document.states.iter().position(|s| s.id == id) {
+        Some(index) => index,
+        None => {
+            document.states.push(ScxmlState {
+                id: id.to_owned(),
+                is_final: false,
+                submachine: None,
+                done: Vec::new(),
+                transitions: Vec::new(),
+            });
+            document.states.len() - 1
+        }
+    };
+    &mut document.states[index]
+}
+
+/// Adds the `from -> to` edge, merged with the edges of `from` taken by the same actions.
+fn edge(document: &mut Document, from: &str, label: &str, to: &str) -> Result<(), String> {
+    let events = label
+        .split('|')
+        .map(|e| e.trim().to_owned())
+        .filter(|e| !e.is_empty())
+        .collect::<Vec<_>>();
+    if events.is_empty() {
+        return Err(format!("the edge from {} to {} has no action", from, to));
+    }
+
+    state(document, to);
+    let from = state(document, from);
+    match from.transitions.iter_mut().find(|t| t.events == events) {
+        Some(t) => t.targets.push(to.to_owned()),
+        None => from.transitions.push(ScxmlTransition {
+            events,
+            targets: vec![to.to_owned()],
+            kind: None,
+        }),
+    }
+    Ok(())
+}
+
+/// `A --> B : Action` lines, `[*] --> A` giving the initial state and `A --> [*]` a terminal one.
+pub fn mermaid(source: &str) -> Result<Document, String> {
+    let mut document = Document {
+        name: None,
+        initial: None,
+        states: Vec::new(),
+    };
+    for line in source.lines() {
+        let line = line.split("%%").next().unwrap_or_default().trim();
+        if line.ends_with('{') {
+            return Err("composite states are not supported".to_owned());
+        }
+        // Header, notes, directions and state declarations.
+        let Some((from, rest)) = line.split_once("-->") else {
+            continue;
+        };
+        let (to, label) = rest.split_once(':').unwrap_or((rest, ""));
+        match (from.trim(), to.trim()) {
+            ("[*]", "[*]") => return Err("edge from [*] to [*]".to_owned()),
+            ("[*]", to) => {
+                state(&mut document, to);
+                document.initial = Some(to.to_owned());
+            }
+            (from, "[*]") => state(&mut document, from).is_final = true,
+            (from, to) => edge(&mut document, from, label, to)?,
+        }
+    }
+    document.check()?;
+    Ok(document)
+}
+
+/// `A -> B [label="Action"]` edges, an edge from a `__start` node giving the initial state and a
+/// `doublecircle` shape marking a terminal one, as in the output of the generated `dot()`.
+pub fn dot(source: &str) -> Result<Document, String> {
+    let mut document = Document {
+        name: None,
+        initial: None,
+        states: Vec::new(),
+    };
+    let (Some(start), Some(end)) = (source.find('{'), source.rfind('}')) else {
+        return Err("expected a `digraph { ... }`".to_owned());
+    };
+    let unquote = |id: &str| id.trim().trim_matches('"').to_owned();
+    let statements = source[start + 1..end]
+        .lines()
+        .map(|line| line.split("//").next().unwrap_or_default())
+        .flat_map(|line| line.split(';'));
+    for statement in statements {
+        let (target, attributes) = match statement.split_once('[') {
+            Some((target, attributes)) => (target, attributes.trim_end().trim_end_matches(']')),
+            None => (statement, ""),
+        };
+        let attribute = |name: &str| {
+            attributes.split(',').find_map(|a| {
+                let (key, value) = a.split_once('=')?;
+                (key.trim() == name).then(|| unquote(value))
+            })
+        };
+
+        if let Some((from, to)) = target.split_once("->") {
+            if to.contains("->") {
+                return Err("edge chains are not supported".to_owned());
+            }
+            let (from, to) = (unquote(from), unquote(to));
+            if from == "__start" {
+                state(&mut document, &to);
+                document.initial = Some(to);
+            } else {
+                edge(
+                    &mut document,
+                    &from,
+                    &attribute("label").unwrap_or_default(),
+                    &to,
+                )?;
+            }
+        } else if ["graph", "node", "edge"].contains(&target.trim()) {
+            // Default attributes.
+        } else if attribute("shape").as_deref() == Some("doublecircle") {
+            state(&mut document, &unquote(target)).is_final = true;
+        }
+    }
+    document.check()?;
+    Ok(document)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_mermaid() {
+        let document = mermaid(
+            "stateDiagram-v2
+            %% A door.
+            [*] --> Closed
+            Closed --> Opened : Open
+            Opened --> Closed : Close | Slam
+            Closed --> Locked : Lock
+            Closed --> Closed : Lock
+            Locked --> [*]
+            note right of Locked : no way out",
+        )
+        .unwrap();
+        assert_eq!(
+            document.to_dsl(),
+            "initial Closed,\n\
+             terminal Locked,\n\
+             Closed {\n    Open => Opened,\n    Lock => Locked | Closed,\n},\n\
+             Opened {\n    Close | Slam => Closed,\n},\n\
+             Locked {\n},\n"
+        );
+    }
+
+    #[test]
+    fn rejects_unsupported_mermaid() {
+        assert_eq!(
+            mermaid("state Busy {\n A --> B : Go\n}").err().as_deref(),
+            Some("composite states are not supported")
+        );
+        assert_eq!(
+            mermaid("A --> B").err().as_deref(),
+            Some("the edge from A to B has no action")
+        );
+        assert_eq!(
+            mermaid("[*] --> [*]").err().as_deref(),
+            Some("edge from [*] to [*]")
+        );
+    }
+
+    #[test]
+    fn reads_dot() {
+        let document = dot(r#"digraph Door {
+            graph [rankdir=LR];
+            node [shape=circle];
+            __start [shape=point];
+            __start -> Closed;
+            "Locked" [shape=doublecircle];
+            Closed -> Opened [label="Open"];
+            Opened -> Closed [label="Close | Slam"]; // Both close it.
+            Closed -> Locked [color=red, label="Lock"]
+        }"#)
+        .unwrap();
+        assert_eq!(
+            document.to_dsl(),
+            "initial Closed,\n\
+             terminal Locked,\n\
+             Closed {\n    Open => Opened,\n    Lock => Locked,\n},\n\
+             Locked {\n},\n\
+             Opened {\n    Close | Slam => Closed,\n},\n"
+        );
+    }
+
+    #[test]
+    fn rejects_unsupported_dot() {
+        assert_eq!(
+            dot("A -> B").err().as_deref(),
+            Some("expected a `digraph { ... }`")
+        );
+        assert_eq!(
+            dot("digraph { A -> B -> C [label=\"Go\"] }")
+                .err()
+                .as_deref(),
+            Some("edge chains are not supported")
+        );
+        assert_eq!(
+            dot("digraph { A -> B }").err().as_deref(),
+            Some("the edge from A to B has no action")
+        );
+    }
+}
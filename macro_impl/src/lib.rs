@@ -1,29 +1,127 @@
-use quote::quote;
-use syn::{braced, parse::Parse, parse_macro_input, punctuated::Punctuated, Ident, Token};
+use quote::{format_ident, quote};
+use syn::{
+    braced, parse::Parse, parse_macro_input, punctuated::Punctuated, Attribute, Ident, Token,
+    Visibility,
+};
 
 type StateId = Ident;
 type ActionId = Ident;
 
+mod diagram;
+mod scxml;
+
+mod kw {
+    syn::custom_keyword!(alphabet);
+    syn::custom_keyword!(blend);
+    syn::custom_keyword!(boxed);
+    syn::custom_keyword!(ctx);
+    syn::custom_keyword!(debug_summary);
+    syn::custom_keyword!(defer);
+    syn::custom_keyword!(deprecated);
+    syn::custom_keyword!(dot);
+    syn::custom_keyword!(exhaustive);
+    syn::custom_keyword!(experimental);
+    syn::custom_keyword!(forbid);
+    syn::custom_keyword!(frozen);
+    syn::custom_keyword!(hooks);
+    syn::custom_keyword!(idle);
+    syn::custom_keyword!(ignore);
+    syn::custom_keyword!(initial);
+    syn::custom_keyword!(internal);
+    syn::custom_keyword!(max_size);
+    syn::custom_keyword!(mermaid);
+    syn::custom_keyword!(on_invalid_transition);
+    syn::custom_keyword!(optional);
+    syn::custom_keyword!(reject);
+    syn::custom_keyword!(requires);
+    syn::custom_keyword!(strict);
+    syn::custom_keyword!(submachine);
+    syn::custom_keyword!(terminal);
+    syn::custom_keyword!(unit);
+    syn::custom_keyword!(validate);
+}
+
 struct StateTransitions {
     state: StateId,
+    /// `submachine Child: A | B => Next`: the state holds a `Child` machine, which the listed
+    /// actions step before reaching the state's own transitions. Once the child reaches a terminal
+    /// state, the state's `State<_, Done<Child>>` handler goes to one of the states after `=>`.
+    submachine: Option<Ident>,
+    child_actions: Vec<ActionId>,
+    done: Vec<StateId>,
+    /// `defer A | B`: actions kept aside by the runner until a later state accepts them.
+    deferred: Vec<ActionId>,
+    /// `ignore A | B`: accepted without calling a handler, the state staying as it is.
+    ignored: Vec<ActionId>,
+    /// `reject A | B`: rejected, even when a wildcard or global transition would take them.
+    rejected: Vec<ActionId>,
     transitions: Vec<Transition>,
 }
 
 impl StateTransitions {
-    fn check_transitions_consistency(&self) -> bool {
-        // TODO: check which ones are conflicting exactly...
-        let number_actions = self
-            .transitions
-            .iter()
-            .map(|t| t.actions.len())
-            .sum::<usize>();
-        let number_unique_actions = self
+    /// Whether the state decides itself what happens to `action`, over the global transitions.
+    fn overrides(&self, action: &ActionId) -> bool {
+        self.transitions.iter().any(|t| t.actions.contains(action))
+            || self.ignored.contains(action)
+            || self.rejected.contains(action)
+    }
+
+    /// Errors on every action handled more than once without a guard, pointing at its repeated
+    /// occurrences.
+    fn check_transitions_consistency(&self) -> syn::Result<()> {
+        let mut handled = Vec::new();
+        let mut errors = Vec::new();
+        let mut wildcard_handled = false;
+        for wildcard in self
             .transitions
             .iter()
-            .flat_map(|t| t.actions.iter())
-            .collect::<std::collections::HashSet<_>>()
-            .len();
-        number_actions == number_unique_actions
+            .filter(|t| !t.is_conditional())
+            .filter_map(|t| t.wildcard.as_ref())
+        {
+            if wildcard_handled {
+                errors.push(syn::Error::new(
+                    wildcard.span,
+                    format!("state {} already has a wildcard transition", self.state),
+                ));
+            }
+            wildcard_handled = true;
+        }
+
+        let unguarded = self.transitions.iter().filter(|t| !t.is_conditional());
+        for a in unguarded.flat_map(|t| t.actions.iter()) {
+            if handled.contains(&a) {
+                errors.push(syn::Error::new(
+                    a.span(),
+                    format!("action {} is already handled by state {}", a, self.state),
+                ));
+            } else {
+                handled.push(a);
+            }
+        }
+
+        let mentioned = self.transitions.iter().flat_map(|t| t.actions.iter());
+        let mut handled = mentioned.collect::<Vec<_>>();
+        for a in self.ignored.iter().chain(&self.rejected) {
+            if handled.contains(&a) {
+                errors.push(syn::Error::new(
+                    a.span(),
+                    format!("action {} is already handled by state {}", a, self.state),
+                ));
+            } else {
+                handled.push(a);
+            }
+        }
+        combine_errors(errors)
+    }
+}
+
+fn combine_errors(errors: Vec<syn::Error>) -> syn::Result<()> {
+    match errors.into_iter().reduce(|mut acc, e| {
+        acc.combine(e);
+        acc
+    }) {
+        None => Ok(()),
+        Some(e) => Err(e),
     }
 }
 
@@ -32,240 +130,4069 @@ impl Parse for StateTransitions {
         let state = input.parse::<Ident>()?;
         let transitions_def;
         braced!(transitions_def in input);
-        let transitions = Punctuated::<Transition, Token![,]>::parse_terminated(&transitions_def)?
-            .into_iter()
-            .collect();
-        Ok(StateTransitions { state, transitions })
+
+        let mut submachine = None;
+        let mut child_actions = Vec::new();
+        let mut done = Vec::new();
+        let mut deferred = Vec::new();
+        let mut ignored = Vec::new();
+        let mut rejected = Vec::new();
+        let mut transitions = Vec::new();
+        while !transitions_def.is_empty() {
+            if transitions_def.peek(kw::submachine) && transitions_def.peek2(Ident) {
+                let keyword = transitions_def.parse::<kw::submachine>()?;
+                if submachine.is_some() {
+                    return Err(syn::Error::new(
+                        keyword.span,
+                        format!("state {} already has a submachine", state),
+                    ));
+                }
+                submachine = Some(transitions_def.parse::<Ident>()?);
+                if transitions_def.peek(Token![:]) {
+                    transitions_def.parse::<Token![:]>()?;
+                    child_actions =
+                        Punctuated::<Ident, Token![|]>::parse_separated_nonempty(&transitions_def)?
+                            .into_iter()
+                            .collect();
+                }
+                if transitions_def.peek(Token![=>]) {
+                    transitions_def.parse::<Token![=>]>()?;
+                    done =
+                        Punctuated::<Ident, Token![|]>::parse_separated_nonempty(&transitions_def)?
+                            .into_iter()
+                            .collect();
+                }
+            } else if transitions_def.peek(kw::defer) && transitions_def.peek2(Ident) {
+                transitions_def.parse::<kw::defer>()?;
+                deferred.extend(Punctuated::<Ident, Token![|]>::parse_separated_nonempty(
+                    &transitions_def,
+                )?);
+            } else if transitions_def.peek(kw::ignore) && transitions_def.peek2(Ident) {
+                transitions_def.parse::<kw::ignore>()?;
+                ignored.extend(Punctuated::<Ident, Token![|]>::parse_separated_nonempty(
+                    &transitions_def,
+                )?);
+            } else if transitions_def.peek(kw::reject) && transitions_def.peek2(Ident) {
+                transitions_def.parse::<kw::reject>()?;
+                rejected.extend(Punctuated::<Ident, Token![|]>::parse_separated_nonempty(
+                    &transitions_def,
+                )?);
+            } else {
+                transitions.push(transitions_def.parse::<Transition>()?);
+            }
+
+            if transitions_def.is_empty() {
+                break;
+            }
+            transitions_def.parse::<Token![,]>()?;
+        }
+        Ok(StateTransitions {
+            state,
+            submachine,
+            child_actions,
+            done,
+            deferred,
+            ignored,
+            rejected,
+            transitions,
+        })
     }
 }
 
 struct Transition {
+    /// Only taken when the caller enables experimental transitions.
+    experimental: Option<kw::experimental>,
+    /// `optional(Flag)`: only taken by the instances created with `Flag` enabled.
+    optional: Option<Ident>,
+    /// `blend(Millis, Easing)`: how long and along which curve animations blend from the state to
+    /// the next one. Left to the caller, dispatch ignores it.
+    blend: Option<Blend>,
+    /// `requires(RoleA | RoleB)`: only callers holding one of the roles may take the transition.
+    /// Left to the caller, dispatch ignores it; `allowed_actions_for` filters on it.
+    requires: Vec<Ident>,
     actions: Vec<ActionId>,
+    /// Pattern each action was named by, `Enum::Variant(0)` for the variant `Variant` of the
+    /// `alphabet` enum, or `None` if it was named alone.
+    patterns: Vec<Option<syn::Pat>>,
+    /// `_` in place of the actions: any action the state does not otherwise handle. The state
+    /// then implements `State` for the whole action wrapper.
+    wildcard: Option<Token![_]>,
+    /// Called with references to the state and the action, the transition is only taken if it
+    /// returns true.
+    guard: Option<syn::Expr>,
+    /// `=> @internal`: the handler stays in the same state, without going through the hooks nor
+    /// the next state check. `next_states` is then empty.
+    internal: bool,
     next_states: Vec<StateId>,
 }
 
+impl Transition {
+    fn is_conditional(&self) -> bool {
+        self.guard.is_some()
+            || self.experimental.is_some()
+            || self.optional.is_some()
+            || self.patterns.iter().flatten().any(narrows)
+    }
+
+    fn pattern(&self, action: &ActionId) -> Option<&syn::Pat> {
+        let index = self.actions.iter().position(|a| a == action)?;
+        self.patterns[index].as_ref()
+    }
+
+    /// `condition` for `action`, narrowed by the pattern naming it, whose bindings the guard
+    /// can use.
+    fn matching(
+        &self,
+        action: &ActionId,
+        condition: Option<proc_macro2::TokenStream>,
+    ) -> Option<proc_macro2::TokenStream> {
+        let Some(pattern) = self.pattern(action) else {
+            return condition;
+        };
+        let condition = condition.unwrap_or_else(|| quote! { true });
+        Some(quote! {{
+            #[allow(unused_variables)]
+            let taken = matches!(&a.0, #pattern if #condition);
+            taken
+        }})
+    }
+
+    /// Runtime condition of the transition, on top of matching the action.
+    fn condition(
+        &self,
+        smd: &StateMachineDefinition,
+        state_ref: &proc_macro2::TokenStream,
+    ) -> Option<proc_macro2::TokenStream> {
+        let mut conditions = self.switches(smd);
+        if let Some(guard) = &self.guard {
+            conditions.push(quote! { (#guard)(#state_ref, &a) });
+        }
+        (!conditions.is_empty()).then(|| quote! { #(#conditions)&&* })
+    }
+
+    /// The part of the condition decided by the caller rather than by the state and the action:
+    /// `experimental` and the `enabled` flags.
+    fn switches(&self, smd: &StateMachineDefinition) -> Vec<proc_macro2::TokenStream> {
+        let mut switches = Vec::new();
+        if self.experimental.is_some() {
+            switches.push(quote! { experimental });
+        }
+        if let Some(flag) = &self.optional {
+            let flagged = format_ident!("{}Flagged", smd.state_wrapper);
+            let flag = screaming_snake_case(flag);
+            switches.push(quote! { enabled & #flagged::#flag != 0 });
+        }
+        switches
+    }
+
+    /// Rendering of the condition for the diagram labels.
+    fn condition_label(&self) -> String {
+        self.render_condition(|tokens| tokens.to_string())
+    }
+
+    /// Rendering of the condition for the machine hash, independent of how the compiler prints
+    /// tokens.
+    fn canonical_condition(&self) -> String {
+        self.render_condition(canonical_tokens)
+    }
+
+    fn render_condition(&self, render: impl Fn(proc_macro2::TokenStream) -> String) -> String {
+        let mut condition = String::new();
+        if self.experimental.is_some() {
+            condition += " experimental";
+        }
+        if let Some(flag) = &self.optional {
+            condition += &format!(" optional({})", flag);
+        }
+        for pattern in self.patterns.iter().flatten().filter(|p| narrows(p)) {
+            condition += &format!(" matching {}", render(quote! { #pattern }));
+        }
+        if let Some(guard) = &self.guard {
+            condition += &format!(" if {}", render(quote! { #guard }));
+        }
+        condition
+    }
+}
+
+/// Whether `pattern` only matches some values of its variant, rather than binding or ignoring
+/// all the fields.
+fn narrows(pattern: &syn::Pat) -> bool {
+    let catch_all = |p: &syn::Pat| match p {
+        syn::Pat::Ident(p) => p.subpat.is_none(),
+        syn::Pat::Wild(_) | syn::Pat::Rest(_) => true,
+        _ => false,
+    };
+    match pattern {
+        syn::Pat::Path(_) => false,
+        syn::Pat::TupleStruct(p) => !p.elems.iter().all(catch_all),
+        syn::Pat::Struct(p) => !p.fields.iter().all(|f| catch_all(&f.pat)),
+        _ => true,
+    }
+}
+
+/// Action named by `Enum::Variant` patterns: the variant.
+fn pattern_variant(pattern: &syn::Pat) -> Option<(&syn::Path, &Ident)> {
+    let path = match pattern {
+        syn::Pat::Path(p) => &p.path,
+        syn::Pat::TupleStruct(p) => &p.path,
+        syn::Pat::Struct(p) => &p.path,
+        _ => return None,
+    };
+    Some((path, &path.segments.last()?.ident))
+}
+
+/// Tokens separated by single spaces, a `~` marking punctuation joined to the next one so that
+/// e.g. `&&` and `& &` stay apart.
+fn canonical_tokens(tokens: proc_macro2::TokenStream) -> String {
+    use proc_macro2::{Delimiter, Spacing, TokenTree};
+
+    let mut rendered = Vec::new();
+    for tt in tokens {
+        rendered.push(match tt {
+            TokenTree::Group(g) => {
+                let (open, close) = match g.delimiter() {
+                    Delimiter::Parenthesis => ("(", ")"),
+                    Delimiter::Brace => ("{", "}"),
+                    Delimiter::Bracket => ("[", "]"),
+                    Delimiter::None => ("", ""),
+                };
+                format!("{}{}{}", open, canonical_tokens(g.stream()), close)
+            }
+            TokenTree::Punct(p) if p.spacing() == Spacing::Joint => format!("{}~", p.as_char()),
+            TokenTree::Punct(p) => p.as_char().to_string(),
+            TokenTree::Ident(i) => i.to_string(),
+            TokenTree::Literal(l) => l.to_string(),
+        });
+    }
+    rendered.join(" ")
+}
+
 impl Parse for Transition {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        let actions = Punctuated::<Ident, Token![|]>::parse_separated_nonempty(input)?
-            .into_iter()
-            .collect();
-        input.parse::<Token![=>]>()?;
-        let next_states = Punctuated::<Ident, Token![|]>::parse_separated_nonempty(input)?
-            .into_iter()
-            .collect();
+        let experimental =
+            if input.peek(kw::experimental) && (input.peek2(Ident) || input.peek2(Token![_])) {
+                Some(input.parse::<kw::experimental>()?)
+            } else {
+                None
+            };
+        let optional = if input.peek(kw::optional) && input.peek2(syn::token::Paren) {
+            input.parse::<kw::optional>()?;
+            let content;
+            syn::parenthesized!(content in input);
+            Some(content.parse::<Ident>()?)
+        } else {
+            None
+        };
+        let blend = if input.peek(kw::blend) && input.peek2(syn::token::Paren) {
+            input.parse::<kw::blend>()?;
+            let content;
+            syn::parenthesized!(content in input);
+            let millis = content.parse::<syn::LitInt>()?;
+            content.parse::<Token![,]>()?;
+            let easing = content.parse::<Ident>()?;
+            Some(Blend { millis, easing })
+        } else {
+            None
+        };
+        let requires = if input.peek(kw::requires) && input.peek2(syn::token::Paren) {
+            input.parse::<kw::requires>()?;
+            let content;
+            syn::parenthesized!(content in input);
+            Punctuated::<Ident, Token![|]>::parse_separated_nonempty(&content)?
+                .into_iter()
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let (actions, patterns, wildcard) = if input.peek(Token![_]) {
+            (Vec::new(), Vec::new(), Some(input.parse::<Token![_]>()?))
+        } else {
+            let mut actions = Vec::new();
+            let mut patterns = Vec::new();
+            loop {
+                if input.peek(Ident) && input.peek2(Token![::]) {
+                    let pattern = syn::Pat::parse_single(input)?;
+                    let (_, variant) = pattern_variant(&pattern).ok_or_else(|| {
+                        syn::Error::new_spanned(&pattern, "expected a pattern of an enum variant")
+                    })?;
+                    actions.push(variant.clone());
+                    patterns.push(Some(pattern));
+                } else {
+                    actions.push(input.parse::<ActionId>()?);
+                    patterns.push(None);
+                }
+                if !input.peek(Token![|]) {
+                    break;
+                }
+                input.parse::<Token![|]>()?;
+            }
+            (actions, patterns, None)
+        };
+        let guard = if input.peek(Token![if]) {
+            input.parse::<Token![if]>()?;
+            Some(syn::Expr::parse_without_eager_brace(input)?)
+        } else {
+            None
+        };
+        input.parse::<Token![=>]>().map_err(|e| {
+            syn::Error::new(
+                e.span(),
+                "expected `|` and another action, a guard, or `=>` and the next states",
+            )
+        })?;
+        let (internal, next_states) = if input.peek(Token![@]) {
+            input.parse::<Token![@]>()?;
+            input.parse::<kw::internal>()?;
+            (true, Vec::new())
+        } else {
+            let next_states = Punctuated::<Ident, Token![|]>::parse_separated_nonempty(input)?
+                .into_iter()
+                .collect();
+            (false, next_states)
+        };
         Ok(Transition {
+            experimental,
+            optional,
+            blend,
+            requires,
             actions,
+            patterns,
+            wildcard,
+            guard,
+            internal,
             next_states,
         })
     }
 }
 
+/// `blend(Millis, Easing)` on a transition, the easing naming a variant of
+/// `state_machine::blend::Easing`.
+struct Blend {
+    millis: syn::LitInt,
+    easing: Ident,
+}
+
+/// `forbid State { A, B }`: the state must accept none of the actions.
+struct Forbidden {
+    state: StateId,
+    actions: Vec<ActionId>,
+}
+
+/// `validate Action(Type) where pattern`: `Action::new` only builds actions whose payload matches
+/// the pattern.
+struct Validation {
+    action: ActionId,
+    payload: syn::Type,
+    pattern: syn::Pat,
+}
+
+/// `deprecated Old => New`: `Old` is still accepted, but handled as `New`. Its variant of the
+/// action wrapper is deprecated, and there is no `From<Old>` conversion going around it.
+struct Deprecation {
+    old: ActionId,
+    new: ActionId,
+}
+
+/// `frozen Version = Pin`: the pin is derived from both the version and the machine hash, so that
+/// any change to the machine needs a new version along with its new pin. `0` asks for the pin.
+struct Frozen {
+    version: syn::LitInt,
+    pin: syn::LitInt,
+}
+
 struct StateMachineDefinition {
+    /// Forwarded to the wrapper, a `derive` among them replacing the built-in `Debug` one.
+    state_attrs: Vec<Attribute>,
+    state_vis: Visibility,
+    /// `async Wrapper`: handlers implement `AsyncState` and the generated `next` is async.
+    asyncness: Option<Token![async]>,
     state_wrapper: Ident,
+    /// Same as `state_attrs`, for the action wrapper.
+    action_attrs: Vec<Attribute>,
+    action_vis: Visibility,
     action_wrapper: Ident,
+    /// `alphabet Enum`: the actions are the variants of an existing enum, each wrapped whole in a
+    /// generated newtype named after the variant.
+    alphabet: Option<Ident>,
     state_transitions: Vec<StateTransitions>,
+    /// `* { ... }`: transitions accepted from every state but the terminal ones, handled by the
+    /// state wrapper itself.
+    global: Vec<Transition>,
+    max_size: Option<syn::LitInt>,
+    /// `ctx Type`: handlers implement `ContextState` and get a `&mut Type` shared by all states.
+    ctx: Option<syn::Type>,
+    boxed: Vec<StateId>,
+    deprecated: Vec<Deprecation>,
+    forbidden: Vec<Forbidden>,
+    validations: Vec<Validation>,
+    /// `unit A | B`: data-less actions, given a constant on the action wrapper.
+    unit_actions: Vec<ActionId>,
+    /// `on_invalid_transition path`: called with an `InvalidTransition` when a handler goes to a
+    /// state its transition does not declare, in place of panicking. `on_invalid_transition Err`
+    /// returns it from `next` instead.
+    on_invalid_transition: Option<syn::Path>,
+    /// `debug_summary`: the state wrapper's `Debug` also lists the actions the state accepts.
+    debug_summary: bool,
+    /// `hooks`: every state implements `StateHooks`, called around each transition.
+    hooks: bool,
+    /// `strict`: the modeling mistakes otherwise reported as warnings are errors.
+    strict: bool,
+    /// `exhaustive`: every state must handle, ignore or reject every action.
+    exhaustive: bool,
+    frozen: Option<Frozen>,
+    initial: Option<StateId>,
+    terminal: Vec<StateId>,
+    /// `idle State`: the machine rests in the state until given an action from outside, so a bus
+    /// with nothing left to deliver to it may consider it settled.
+    idle: Vec<StateId>,
 }
 
-impl Parse for StateMachineDefinition {
-    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        let state_wrapper = input.parse::<Ident>()?;
-        input.parse::<Token![,]>()?;
-        let action_wrapper = input.parse::<Ident>()?;
-        input.parse::<Token![,]>()?;
-        let state_transitions = Punctuated::<StateTransitions, Token![,]>::parse_terminated(input)?
-            .into_iter()
-            .collect();
-
-        Ok(StateMachineDefinition {
-            state_wrapper,
-            action_wrapper,
-            state_transitions,
-        })
+impl StateMachineDefinition {
+    /// Every state, in order of first appearance.
+    fn states(&self) -> Vec<&StateId> {
+        let mut states = Vec::new();
+        for st in &self.state_transitions {
+            if !states.contains(&&st.state) {
+                states.push(&st.state);
+            }
+            let next_states = st.transitions.iter().flat_map(|t| &t.next_states);
+            for next_s in next_states.chain(&st.done) {
+                if !states.contains(&next_s) {
+                    states.push(next_s);
+                }
+            }
+        }
+        for next_s in self.global.iter().flat_map(|t| t.next_states.iter()) {
+            if !states.contains(&next_s) {
+                states.push(next_s);
+            }
+        }
+        states
     }
-}
-
-fn define_wrappers(smd: &StateMachineDefinition) -> proc_macro2::TokenStream {
-    let state_wrapper = &smd.state_wrapper;
-    let action_wrapper = &smd.action_wrapper;
 
-    let mut states = std::collections::HashSet::new();
-    let mut actions = std::collections::HashSet::new();
-    for st in &smd.state_transitions {
-        states.insert(&st.state);
-        for t in &st.transitions {
-            for a in &t.actions {
-                actions.insert(a);
+    /// Every action, in order of first appearance, followed by the deprecated ones.
+    fn actions(&self) -> Vec<&ActionId> {
+        let mut actions = Vec::new();
+        for st in &self.state_transitions {
+            for t in &st.transitions {
+                for a in &t.actions {
+                    if !actions.contains(&a) {
+                        actions.push(a);
+                    }
+                }
             }
-
-            for next_s in &t.next_states {
-                states.insert(next_s);
+        }
+        let child_actions = self.state_transitions.iter().flat_map(|st| {
+            st.child_actions
+                .iter()
+                .chain(&st.deferred)
+                .chain(&st.ignored)
+                .chain(&st.rejected)
+        });
+        for a in self
+            .global
+            .iter()
+            .flat_map(|t| t.actions.iter())
+            .chain(child_actions)
+        {
+            if !actions.contains(&a) {
+                actions.push(a);
             }
         }
+        for d in &self.deprecated {
+            if !actions.contains(&&d.old) {
+                actions.push(&d.old);
+            }
+        }
+        actions
     }
 
-    let mut state_from_impl_acc = quote! {};
-    let mut state_acc = quote! {};
-    for s in states {
-        state_acc = quote! {
-            #state_acc
-            #s(#s),
-        };
+    fn is_child_action(&self, action: &ActionId) -> bool {
+        self.state_transitions
+            .iter()
+            .any(|st| st.child_actions.contains(action))
+    }
 
-        state_from_impl_acc = quote! {
-            #state_from_impl_acc
+    /// Whether the global transitions for `action` apply in `state`: terminal states take none of
+    /// them, so that a finished machine stays finished, and the others those they do not override.
+    fn takes_global(&self, state: &StateId, action: &ActionId) -> bool {
+        !self.terminal.contains(state)
+            && !self
+                .state_transitions
+                .iter()
+                .any(|st| &st.state == state && st.overrides(action))
+    }
 
-            impl From<#s> for #state_wrapper {
-                fn from(s: #s) -> #state_wrapper {
-                    #state_wrapper::#s(s)
-                }
+    /// Blend of the transition `state` takes for `action`: the first one listing it, else a global
+    /// one, else the wildcard.
+    /// Transitions `state` may take for `action`: its own ones listing the action, or else the
+    /// global ones, or else its wildcard. Deprecated actions go through their replacement.
+    fn handling(&self, state: &StateId, action: &ActionId) -> Vec<&Transition> {
+        let action = self
+            .deprecated
+            .iter()
+            .find(|d| &d.old == action)
+            .map_or(action, |d| &d.new);
+        let own = self
+            .state_transitions
+            .iter()
+            .filter(|st| &st.state == state)
+            .flat_map(|st| &st.transitions);
+        let listing = own
+            .clone()
+            .filter(|t| t.actions.contains(action))
+            .collect::<Vec<_>>();
+        if !listing.is_empty() || !self.takes_global(state, action) {
+            return listing;
+        }
+        let global = self
+            .global
+            .iter()
+            .filter(|t| t.actions.contains(action))
+            .collect::<Vec<_>>();
+        if !global.is_empty() {
+            return global;
+        }
+        own.filter(|t| t.wildcard.is_some()).collect()
+    }
+
+    fn blend(&self, state: &StateId, action: &ActionId) -> Option<&Blend> {
+        self.handling(state, action)
+            .first()
+            .and_then(|t| t.blend.as_ref())
+    }
+
+    /// Every role named by the `requires` annotations, in order of first appearance.
+    fn roles(&self) -> Vec<&Ident> {
+        let mut roles = Vec::new();
+        let transitions = self
+            .state_transitions
+            .iter()
+            .flat_map(|st| &st.transitions)
+            .chain(&self.global);
+        for role in transitions.flat_map(|t| &t.requires) {
+            if !roles.contains(&role) {
+                roles.push(role);
             }
-        };
+        }
+        roles
     }
 
-    let mut action_acc = quote! {};
-    let mut action_trait_impl_acc = quote! {};
-    let mut action_from_impl_acc = quote! {};
-    for a in actions {
-        action_acc = quote! {
-            #action_acc
-            #a(#a),
-        };
+    /// Whether some transition `state` takes for `action` requires no role.
+    fn permits_anyone(&self, state: &StateId, action: &ActionId) -> bool {
+        self.handling(state, action)
+            .iter()
+            .any(|t| t.requires.is_empty())
+    }
 
-        action_trait_impl_acc = quote! {
-            #action_trait_impl_acc
-            impl Action for #a {}
-        };
+    /// Whether a caller holding `role` may take one of the transitions `state` takes for `action`.
+    fn permits(&self, state: &StateId, action: &ActionId, role: &Ident) -> bool {
+        self.handling(state, action)
+            .iter()
+            .any(|t| t.requires.is_empty() || t.requires.contains(role))
+    }
 
-        action_from_impl_acc = quote! {
-            #action_from_impl_acc
+    fn is_deprecated(&self, action: &ActionId) -> bool {
+        self.deprecated.iter().any(|d| &d.old == action)
+    }
 
-            impl From<#a> for #action_wrapper {
-                fn from(a: #a) -> #action_wrapper {
-                    #action_wrapper::#a(a)
-                }
+    /// Every flag of the `optional` transitions, in order of first appearance, which gives its
+    /// bit.
+    fn flags(&self) -> Vec<&Ident> {
+        let mut flags = Vec::new();
+        let transitions = self
+            .state_transitions
+            .iter()
+            .flat_map(|st| &st.transitions)
+            .chain(&self.global);
+        for flag in transitions.filter_map(|t| t.optional.as_ref()) {
+            if !flags.contains(&flag) {
+                flags.push(flag);
             }
-        };
+        }
+        flags
     }
 
-    quote! {
-        #[derive(Debug)]
-        enum #state_wrapper {
-            #state_acc
+    fn validate(&self) -> syn::Result<()> {
+        let mut errors = Vec::new();
+        let mut blocks = Vec::new();
+        for st in &self.state_transitions {
+            if blocks.contains(&&st.state) {
+                errors.push(syn::Error::new(
+                    st.state.span(),
+                    format!("state {} already has a block of transitions", st.state),
+                ));
+            }
+            blocks.push(&st.state);
+
+            if let Err(e) = st.check_transitions_consistency() {
+                errors.push(e);
+            }
         }
 
-        #state_from_impl_acc
+        let mut global_handled = Vec::new();
+        for t in &self.global {
+            if let Some(wildcard) = &t.wildcard {
+                errors.push(syn::Error::new(
+                    wildcard.span,
+                    "global transitions cannot use a wildcard",
+                ));
+            }
+            if t.is_conditional() {
+                continue;
+            }
+            for a in &t.actions {
+                if global_handled.contains(&a) {
+                    errors.push(syn::Error::new(
+                        a.span(),
+                        format!("action {} is already handled globally", a),
+                    ));
+                } else {
+                    global_handled.push(a);
+                }
+            }
+        }
 
-        #[derive(Debug)]
-        enum #action_wrapper {
-            #action_acc
+        let transitions = self
+            .state_transitions
+            .iter()
+            .flat_map(|st| st.transitions.iter())
+            .chain(self.global.iter());
+        for pattern in transitions.flat_map(|t| t.patterns.iter().flatten()) {
+            let (path, _) = pattern_variant(pattern).unwrap();
+            let of_alphabet = self.alphabet.as_ref().is_some_and(|alphabet| {
+                path.segments.len() == 2 && path.segments[0].ident == *alphabet
+            });
+            if !of_alphabet {
+                errors.push(syn::Error::new_spanned(
+                    pattern,
+                    "patterns name variants of the `alphabet` enum, as `Enum::Variant`",
+                ));
+            }
+        }
+        if let Some(alphabet) = &self.alphabet {
+            for st in self
+                .state_transitions
+                .iter()
+                .filter(|st| st.submachine.is_some())
+            {
+                errors.push(syn::Error::new(
+                    alphabet.span(),
+                    format!(
+                        "state {} has a submachine, whose actions cannot be variants of {}",
+                        st.state, alphabet
+                    ),
+                ));
+            }
         }
 
-        #action_trait_impl_acc
-        #action_from_impl_acc
-    }
-}
+        let states = self.states();
+        let directives = self
+            .boxed
+            .iter()
+            .map(|s| ("boxed", s))
+            .chain(self.terminal.iter().map(|s| ("terminal", s)))
+            .chain(self.idle.iter().map(|s| ("idle", s)))
+            .chain(self.initial.iter().map(|s| ("initial", s)));
+        for (directive, state) in directives {
+            if !states.contains(&state) {
+                errors.push(syn::Error::new(
+                    state.span(),
+                    format!("{} state {} is not part of the machine", directive, state),
+                ));
+            }
+        }
 
-fn define_transition(st: &StateTransitions, action_wrapper: &Ident) -> proc_macro2::TokenStream {
-    let start_state = &st.state;
+        let handled = self
+            .state_transitions
+            .iter()
+            .flat_map(|st| st.transitions.iter())
+            .chain(self.global.iter())
+            .flat_map(|t| t.actions.iter())
+            .collect::<Vec<_>>();
+        for d in &self.deprecated {
+            if handled.contains(&&d.old) {
+                errors.push(syn::Error::new(
+                    d.old.span(),
+                    format!(
+                        "deprecated action {} is handled as {} and cannot have transitions",
+                        d.old, d.new
+                    ),
+                ));
+            }
+            if !handled.contains(&&d.new) {
+                errors.push(syn::Error::new(
+                    d.new.span(),
+                    format!("replacement action {} is not part of the machine", d.new),
+                ));
+            }
+        }
 
-    let mut action_to_lambda_acc = quote! {};
-    for t in &st.transitions {
-        if t.next_states.is_empty() {
-            continue;
+        for f in &self.forbidden {
+            if !states.contains(&&f.state) {
+                errors.push(syn::Error::new(
+                    f.state.span(),
+                    format!("forbid state {} is not part of the machine", f.state),
+                ));
+                continue;
+            }
+            for a in &f.actions {
+                if let Some(reason) = self.accepting(&f.state, a) {
+                    errors.push(syn::Error::new(
+                        a.span(),
+                        format!(
+                            "state {} accepts forbidden action {}: {}",
+                            f.state, a, reason
+                        ),
+                    ));
+                }
+            }
         }
 
-        let mut assert_acc = quote! {};
-        for output_state in &t.next_states {
-            assert_acc = quote! {
-                #assert_acc
-                matches!(n, &Self::#output_state(_)) ||
-            };
+        if let (Some(asyncness), Some(_)) = (&self.asyncness, &self.ctx) {
+            errors.push(syn::Error::new(
+                asyncness.span,
+                "async machines cannot declare a ctx",
+            ));
         }
 
-        let assert_as_str = assert_acc.to_string();
-        for a in &t.actions {
-            let state_as_str = start_state.to_string();
-            let action_as_str = a.to_string();
-            action_to_lambda_acc = quote! {
-                #action_to_lambda_acc
-                #action_wrapper::#a(_) => |n| if !(#assert_acc false) { panic!("For state {:#?} and action {:#?}, got wrong state: {:#?}, matched against: {:#?}", #state_as_str, #action_as_str, n, #assert_as_str); },
-            };
+        let actions = self.actions();
+        let declared = self
+            .validations
+            .iter()
+            .map(|v| ("validated", &v.action))
+            .chain(self.unit_actions.iter().map(|a| ("unit", a)));
+        for (directive, action) in declared {
+            if !actions.contains(&action) {
+                errors.push(syn::Error::new(
+                    action.span(),
+                    format!("{} action {} is not part of the machine", directive, action),
+                ));
+            }
         }
-    }
 
-    let mut action_dispatch = quote! {};
-    for t in &st.transitions {
-        for a in &t.actions {
-            action_dispatch = quote! {
-                #action_dispatch
-                #action_wrapper::#a(a) => state.next(a),
-            };
+        if let Some(frozen) = &self.frozen {
+            let hash = machine_hash(self);
+            let parsed = frozen
+                .version
+                .base10_parse::<u64>()
+                .and_then(|version| Ok((version, frozen.pin.base10_parse::<u64>()?)));
+            match parsed {
+                Ok((version, pin)) if pin == frozen_pin(version, hash) => {}
+                Ok((version, 0)) => errors.push(syn::Error::new(
+                    frozen.pin.span(),
+                    format!(
+                        "record {:#018x} to freeze machine {} as version {}",
+                        frozen_pin(version, hash),
+                        self.state_wrapper,
+                        version
+                    ),
+                )),
+                Ok((version, _)) => errors.push(syn::Error::new(
+                    frozen.pin.span(),
+                    format!(
+                        "machine {} does not match the definition frozen as version {}: bump the \
+                         version to {} and record {:#018x}",
+                        self.state_wrapper,
+                        version,
+                        version + 1,
+                        frozen_pin(version + 1, hash)
+                    ),
+                )),
+                Err(e) => errors.push(e),
+            }
         }
-    }
 
-    quote! {
-        Self::#start_state(state) => {
-            let assert_lambda =
-            match &action {
-                #action_to_lambda_acc
-                _ => |_| (),
-            };
+        if self.exhaustive {
+            for s in &states {
+                let block = self.state_transitions.iter().find(|st| &st.state == *s);
+                let addressed = |a: &ActionId| {
+                    (self.global.iter().any(|t| t.actions.contains(a))
+                        && !self.terminal.contains(s))
+                        || block.is_some_and(|st| {
+                            st.overrides(a)
+                                || st.child_actions.contains(a)
+                                || st.deferred.contains(a)
+                                || st.transitions.iter().any(|t| t.wildcard.is_some())
+                        })
+                };
+                let missing = actions
+                    .iter()
+                    .filter(|a| !self.is_deprecated(a) && !addressed(a))
+                    .map(|a| a.to_string())
+                    .collect::<Vec<_>>();
+                if !missing.is_empty() {
+                    errors.push(syn::Error::new(
+                        block.map_or(s.span(), |st| st.state.span()),
+                        format!(
+                            "state {} does not address {}: handle, ignore or reject them",
+                            s,
+                            missing.join(", ")
+                        ),
+                    ));
+                }
+            }
+        }
 
-            let next_state = match action {
-                #action_dispatch
-                _ => return Err((Self::#start_state(state), action)),
-            };
-            assert_lambda(&next_state);
-            next_state
+        if let Some(flag) = self.flags().get(64) {
+            errors.push(syn::Error::new(
+                flag.span(),
+                "a machine cannot have more than 64 flags",
+            ));
+        }
+        // Bits of the `expected` actions of the generated error.
+        if actions.len() > 128 {
+            errors.push(syn::Error::new(
+                actions[128].span(),
+                "a machine cannot have more than 128 actions",
+            ));
         }
-    }
-}
 
-fn define_loop(smd: &StateMachineDefinition) -> proc_macro2::TokenStream {
-    let state_wrapper = &smd.state_wrapper;
-    let action_wrapper = &smd.action_wrapper;
+        if self.strict {
+            errors.extend(
+                self.warnings()
+                    .into_iter()
+                    .map(|(s, m)| syn::Error::new(s.span(), m)),
+            );
+        }
 
-    let mut acc = quote! {};
+        combine_errors(errors)
+    }
 
-    for st in &smd.state_transitions {
-        let transition_case = define_transition(st, action_wrapper);
-        acc = quote! {
-            #acc
-            #transition_case
+    /// Why `state` accepts `action`, if it does.
+    fn accepting(&self, state: &StateId, action: &ActionId) -> Option<&'static str> {
+        let action = self
+            .deprecated
+            .iter()
+            .find(|d| &d.old == action)
+            .map_or(action, |d| &d.new);
+        let Some(st) = self.state_transitions.iter().find(|st| &st.state == state) else {
+            return Some("it has no transitions and keeps any action");
         };
+        if st.transitions.iter().any(|t| t.actions.contains(action)) {
+            Some("it has a transition for it")
+        } else if st.child_actions.contains(action) {
+            Some("it hands it to its submachine")
+        } else if st.ignored.contains(action) {
+            Some("it ignores it")
+        } else if st.rejected.contains(action) {
+            None
+        } else if self.global.iter().any(|t| t.actions.contains(action))
+            && !self.terminal.contains(state)
+        {
+            Some("it has a global transition for it")
+        } else if st.transitions.iter().any(|t| t.wildcard.is_some()) {
+            Some("it has a wildcard transition")
+        } else {
+            None
+        }
     }
 
-    quote! {
-        impl #state_wrapper {
-            fn next(self, action: #action_wrapper) -> Result<#state_wrapper, (#state_wrapper, #action_wrapper)> {
-                Ok(match self  {
-                    #acc
-                    terminal_state => terminal_state
-                })
+    /// Every edge of the transition graph, as `(from, label, to)`, in definition order.
+    fn edges(&self) -> Vec<(&StateId, String, &StateId)> {
+        let mut edges = Vec::new();
+        for st in &self.state_transitions {
+            if let Some(child) = &st.submachine {
+                let actions = st
+                    .child_actions
+                    .iter()
+                    .map(|a| a.to_string())
+                    .collect::<Vec<_>>();
+                edges.push((
+                    &st.state,
+                    format!("{} ({})", actions.join(" | "), child),
+                    &st.state,
+                ));
+                for next_s in &st.done {
+                    edges.push((&st.state, format!("{} done", child), next_s));
+                }
+            }
+            if !st.ignored.is_empty() {
+                let actions = st.ignored.iter().map(|a| a.to_string());
+                let label = format!("{} (ignored)", actions.collect::<Vec<_>>().join(" | "));
+                edges.push((&st.state, label, &st.state));
+            }
+            for t in &st.transitions {
+                let actions = match t.wildcard {
+                    Some(_) => "_".to_owned(),
+                    None => t
+                        .actions
+                        .iter()
+                        .map(|a| a.to_string())
+                        .collect::<Vec<_>>()
+                        .join(" | "),
+                };
+                let label = format!("{}{}", actions, t.condition_label());
+                if t.internal {
+                    edges.push((&st.state, label.clone(), &st.state));
+                }
+                for next_s in &t.next_states {
+                    edges.push((&st.state, label.clone(), next_s));
+                }
             }
         }
-    }
-}
 
-#[proc_macro]
-pub fn state_machine(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    let smd = parse_macro_input!(item as StateMachineDefinition);
-    for st in &smd.state_transitions {
-        if !st.check_transitions_consistency() {
-            panic!(
-                "Some pair (state, action) have been defined multiple times for state {}",
-                st.state
+        for state in self.states() {
+            for t in &self.global {
+                let actions = t
+                    .actions
+                    .iter()
+                    .filter(|a| self.takes_global(state, a))
+                    .map(|a| a.to_string())
+                    .collect::<Vec<_>>();
+                if actions.is_empty() {
+                    continue;
+                }
+                let label = format!("{}{}", actions.join(" | "), t.condition_label());
+                if t.internal {
+                    edges.push((state, label.clone(), state));
+                }
+                for next_s in &t.next_states {
+                    edges.push((state, label.clone(), next_s));
+                }
+            }
+        }
+        edges
+    }
+
+    /// Modeling mistakes that still make a valid machine, with the state they are about.
+    fn warnings(&self) -> Vec<(&StateId, String)> {
+        let mut warnings = Vec::new();
+        if self.initial.is_some() {
+            for s in self.states() {
+                if shortest_path(self, s).is_none() {
+                    warnings.push((
+                        s,
+                        format!("state {} is not reachable from the initial state", s),
+                    ));
+                }
+            }
+        }
+        for s in self.states() {
+            let leaves = self.state_transitions.iter().any(|st| {
+                &st.state == s && (st.submachine.is_some() || !st.transitions.is_empty())
+            });
+            if !leaves && self.global.is_empty() && !self.terminal.contains(s) {
+                warnings.push((
+                    s,
+                    format!("state {} has no transitions but is not terminal", s),
+                ));
+            }
+        }
+        warnings
+    }
+
+    /// Whether `next` returns the `InvalidTransition` rather than calling a policy with it.
+    fn invalid_transition_err(&self) -> bool {
+        self.on_invalid_transition
+            .as_ref()
+            .is_some_and(|policy| policy.is_ident("Err"))
+    }
+
+    fn is_boxed(&self, state: &StateId) -> bool {
+        self.boxed.contains(state)
+    }
+}
+
+impl Parse for StateMachineDefinition {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let state_attrs = input.call(Attribute::parse_outer)?;
+        let state_vis = input.parse::<Visibility>()?;
+        let asyncness = input.parse::<Option<Token![async]>>()?;
+        let state_wrapper = input.parse::<Ident>()?;
+        input.parse::<Token![,]>()?;
+        let action_attrs = input.call(Attribute::parse_outer)?;
+        let action_vis = input.parse::<Visibility>()?;
+        let action_wrapper = input.parse::<Ident>()?;
+        input.parse::<Token![,]>()?;
+
+        // State blocks and directives, in any order. A directive is a keyword not followed by
+        // braces, so states can still be named like one.
+        let mut state_transitions = Vec::new();
+        let mut global = Vec::new();
+        let mut max_size = None;
+        let mut ctx = None;
+        let mut boxed = Vec::new();
+        let mut deprecated = Vec::new();
+        let mut forbidden = Vec::new();
+        let mut validations = Vec::new();
+        let mut unit_actions = Vec::new();
+        let mut on_invalid_transition = None;
+        let mut debug_summary = false;
+        let mut strict = false;
+        let mut exhaustive = false;
+        let mut frozen = None;
+        let mut hooks = false;
+        let mut initial = None;
+        let mut terminal = Vec::new();
+        let mut idle = Vec::new();
+        let mut alphabet = None;
+        while !input.is_empty() {
+            // Spliced fragments may leave empty items behind.
+            if input.peek(Token![,]) {
+                input.parse::<Token![,]>()?;
+                continue;
+            }
+
+            if input.peek(kw::initial) && !input.peek2(syn::token::Brace) {
+                let keyword = input.parse::<kw::initial>()?;
+                if initial.is_some() {
+                    return Err(syn::Error::new(
+                        keyword.span,
+                        "initial state declared twice",
+                    ));
+                }
+                initial = Some(input.parse::<StateId>()?);
+            } else if input.peek(kw::alphabet) && !input.peek2(syn::token::Brace) {
+                let keyword = input.parse::<kw::alphabet>()?;
+                if alphabet.is_some() {
+                    return Err(syn::Error::new(keyword.span, "alphabet declared twice"));
+                }
+                alphabet = Some(input.parse::<Ident>()?);
+            } else if input.peek(kw::terminal) && !input.peek2(syn::token::Brace) {
+                input.parse::<kw::terminal>()?;
+                terminal.push(input.parse::<StateId>()?);
+            } else if input.peek(kw::idle) && !input.peek2(syn::token::Brace) {
+                input.parse::<kw::idle>()?;
+                idle.push(input.parse::<StateId>()?);
+            } else if input.peek(kw::deprecated) && !input.peek2(syn::token::Brace) {
+                input.parse::<kw::deprecated>()?;
+                let old = input.parse::<ActionId>()?;
+                input.parse::<Token![=>]>()?;
+                let new = input.parse::<ActionId>()?;
+                deprecated.push(Deprecation { old, new });
+            } else if input.peek(kw::ctx) && !input.peek2(syn::token::Brace) {
+                let keyword = input.parse::<kw::ctx>()?;
+                if ctx.is_some() {
+                    return Err(syn::Error::new(keyword.span, "ctx declared twice"));
+                }
+                ctx = Some(input.parse::<syn::Type>()?);
+            } else if input.peek(kw::unit) && !input.peek2(syn::token::Brace) {
+                input.parse::<kw::unit>()?;
+                unit_actions.extend(Punctuated::<Ident, Token![|]>::parse_separated_nonempty(
+                    input,
+                )?);
+            } else if input.peek(kw::validate) && !input.peek2(syn::token::Brace) {
+                input.parse::<kw::validate>()?;
+                let action = input.parse::<ActionId>()?;
+                let payload_def;
+                syn::parenthesized!(payload_def in input);
+                let payload = payload_def.parse::<syn::Type>()?;
+                input.parse::<Token![where]>()?;
+                let pattern = syn::Pat::parse_multi(input)?;
+                validations.push(Validation {
+                    action,
+                    payload,
+                    pattern,
+                });
+            } else if input.peek(kw::forbid) && !input.peek2(syn::token::Brace) {
+                input.parse::<kw::forbid>()?;
+                let state = input.parse::<StateId>()?;
+                let actions_def;
+                braced!(actions_def in input);
+                let actions = Punctuated::<Ident, Token![,]>::parse_terminated(&actions_def)?
+                    .into_iter()
+                    .collect();
+                forbidden.push(Forbidden { state, actions });
+            } else if input.peek(kw::on_invalid_transition) && !input.peek2(syn::token::Brace) {
+                let keyword = input.parse::<kw::on_invalid_transition>()?;
+                if on_invalid_transition.is_some() {
+                    return Err(syn::Error::new(
+                        keyword.span,
+                        "on_invalid_transition declared twice",
+                    ));
+                }
+                on_invalid_transition = Some(input.parse::<syn::Path>()?);
+            } else if input.peek(kw::debug_summary) && !input.peek2(syn::token::Brace) {
+                input.parse::<kw::debug_summary>()?;
+                debug_summary = true;
+            } else if input.peek(kw::hooks) && !input.peek2(syn::token::Brace) {
+                input.parse::<kw::hooks>()?;
+                hooks = true;
+            } else if input.peek(kw::frozen) && !input.peek2(syn::token::Brace) {
+                let keyword = input.parse::<kw::frozen>()?;
+                if frozen.is_some() {
+                    return Err(syn::Error::new(keyword.span, "frozen declared twice"));
+                }
+                let version = input.parse::<syn::LitInt>()?;
+                input.parse::<Token![=]>()?;
+                let pin = input.parse::<syn::LitInt>()?;
+                frozen = Some(Frozen { version, pin });
+            } else if input.peek(kw::exhaustive) && !input.peek2(syn::token::Brace) {
+                input.parse::<kw::exhaustive>()?;
+                exhaustive = true;
+            } else if input.peek(kw::strict) && !input.peek2(syn::token::Brace) {
+                input.parse::<kw::strict>()?;
+                strict = true;
+            } else if input.peek(kw::boxed) && !input.peek2(syn::token::Brace) {
+                input.parse::<kw::boxed>()?;
+                boxed.push(input.parse::<StateId>()?);
+            } else if input.peek(kw::max_size) && !input.peek2(syn::token::Brace) {
+                let keyword = input.parse::<kw::max_size>()?;
+                if max_size.is_some() {
+                    return Err(syn::Error::new(keyword.span, "max_size declared twice"));
+                }
+                max_size = Some(input.parse::<syn::LitInt>()?);
+            } else if input.peek(Token![*]) {
+                input.parse::<Token![*]>()?;
+                let transitions_def;
+                braced!(transitions_def in input);
+                global.extend(Punctuated::<Transition, Token![,]>::parse_terminated(
+                    &transitions_def,
+                )?);
+            } else {
+                state_transitions.push(input.parse::<StateTransitions>()?);
+            }
+
+            if input.is_empty() {
+                break;
+            }
+            input.parse::<Token![,]>()?;
+        }
+
+        Ok(StateMachineDefinition {
+            state_attrs,
+            state_vis,
+            asyncness,
+            state_wrapper,
+            action_attrs,
+            action_vis,
+            action_wrapper,
+            alphabet,
+            state_transitions,
+            global,
+            max_size,
+            ctx,
+            boxed,
+            deprecated,
+            forbidden,
+            validations,
+            unit_actions,
+            on_invalid_transition,
+            debug_summary,
+            hooks,
+            strict,
+            exhaustive,
+            frozen,
+            initial,
+            terminal,
+            idle,
+        })
+    }
+}
+
+/// Shortest sequence of actions leading from the initial state to `target`, on the action-kind
+/// view of the table.
+fn shortest_path<'a>(
+    smd: &'a StateMachineDefinition,
+    target: &StateId,
+) -> Option<Vec<&'a ActionId>> {
+    let initial = smd.initial.as_ref()?;
+    let actions = smd.actions();
+    let mut paths = vec![(initial, Vec::new())];
+    let mut queue = std::collections::VecDeque::from([initial]);
+    while let Some(current) = queue.pop_front() {
+        let path = paths.iter().find(|(s, _)| *s == current).unwrap().1.clone();
+        if current == target {
+            return Some(path);
+        }
+        for &a in &actions {
+            for successor in kind_successors(smd, current, a) {
+                if !paths.iter().any(|(s, _)| *s == successor) {
+                    let mut path = path.clone();
+                    path.push(a);
+                    paths.push((successor, path));
+                    queue.push_back(successor);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Doc of a state variant, telling how to reach it.
+fn reachability_doc(smd: &StateMachineDefinition, state: &StateId) -> proc_macro2::TokenStream {
+    if smd.initial.is_none() {
+        return quote! {};
+    }
+    let doc = match shortest_path(smd, state) {
+        Some(path) if path.is_empty() => "Initial state.".to_owned(),
+        Some(path) => format!(
+            "Reached from the initial state with: {}.",
+            path.iter()
+                .map(|a| format!("`{}`", a))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        None => "Not reachable from the initial state.".to_owned(),
+    };
+    quote! { #[doc = #doc] }
+}
+
+/// With `serde`, a wrapper is (de)serializable whenever all the types it wraps are: the bounds use
+/// the same `for<'a>` trick as `new`, so the wrapper still compiles when they are not.
+fn serde_derive(inner: &[&Ident]) -> proc_macro2::TokenStream {
+    if !cfg!(feature = "serde") {
+        return quote! {};
+    }
+
+    let serialize = inner
+        .iter()
+        .map(|t| format!("for<'a> {}: ::state_machine::serde::Serialize", t))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let deserialize = inner
+        .iter()
+        .map(|t| format!("for<'a> {}: ::state_machine::serde::Deserialize<'de>", t))
+        .collect::<Vec<_>>()
+        .join(", ");
+    quote! {
+        #[derive(::state_machine::serde::Serialize, ::state_machine::serde::Deserialize)]
+        #[serde(crate = "::state_machine::serde")]
+        #[serde(bound(serialize = #serialize, deserialize = #deserialize))]
+    }
+}
+
+fn define_wrappers(smd: &StateMachineDefinition) -> proc_macro2::TokenStream {
+    let state_attrs = &smd.state_attrs;
+    let action_attrs = &smd.action_attrs;
+    let state_vis = &smd.state_vis;
+    let state_wrapper = &smd.state_wrapper;
+    let action_vis = &smd.action_vis;
+    let action_wrapper = &smd.action_wrapper;
+
+    let mut state_from_impl_acc = quote! {};
+    let mut state_acc = quote! {};
+    let mut state_name_acc = quote! {};
+    let mut variant_sizes_acc = quote! {};
+    for s in smd.states() {
+        let (payload, wrap) = if smd.is_boxed(s) {
+            (quote! { Box<#s> }, quote! { Box::new(s) })
+        } else {
+            (quote! { #s }, quote! { s })
+        };
+
+        let doc = reachability_doc(smd, s);
+        state_acc = quote! {
+            #state_acc
+            #doc
+            #s(#payload),
+        };
+
+        state_from_impl_acc = quote! {
+            #state_from_impl_acc
+
+            impl From<#s> for #state_wrapper {
+                fn from(s: #s) -> #state_wrapper {
+                    #state_wrapper::#s(#wrap)
+                }
+            }
+        };
+
+        let state_as_str = s.to_string();
+        state_name_acc = quote! {
+            #state_name_acc
+            #state_wrapper::#s(_) => #state_as_str,
+        };
+        variant_sizes_acc = quote! {
+            #variant_sizes_acc
+            (#state_as_str, ::core::mem::size_of::<#s>()),
+        };
+    }
+
+    let mut action_acc = quote! {};
+    let mut action_trait_impl_acc = quote! {};
+    let mut action_from_impl_acc = quote! {};
+    let mut action_name_acc = quote! {};
+    for a in smd.actions() {
+        let deprecation = if smd.is_deprecated(a) {
+            let note = format!(
+                "use {} instead",
+                smd.deprecated.iter().find(|d| &d.old == a).unwrap().new
+            );
+            quote! { #[deprecated(note = #note)] }
+        } else {
+            quote! {}
+        };
+        action_acc = quote! {
+            #action_acc
+            #deprecation
+            #a(#a),
+        };
+
+        let action_as_str = a.to_string();
+        action_name_acc = quote! {
+            #action_name_acc
+            #[allow(deprecated)]
+            #action_wrapper::#a(_) => #action_as_str,
+        };
+
+        // The child machine already implements it.
+        if !smd.is_child_action(a) {
+            action_trait_impl_acc = quote! {
+                #action_trait_impl_acc
+                impl Action for #a {}
+            };
+        }
+
+        // Senders of a deprecated action have to name its deprecated variant, and get warned.
+        if smd.is_deprecated(a) {
+            continue;
+        }
+        action_from_impl_acc = quote! {
+            #action_from_impl_acc
+
+            impl From<#a> for #action_wrapper {
+                fn from(a: #a) -> #action_wrapper {
+                    #action_wrapper::#a(a)
+                }
+            }
+        };
+    }
+
+    let states = smd.states();
+    let state_serde = serde_derive(&states);
+    let action_serde = serde_derive(&smd.actions());
+
+    // Deriving anything takes over the built-in `Debug`, which would otherwise conflict with the
+    // user's own.
+    let derives = |attrs: &[Attribute]| attrs.iter().any(|a| a.path().is_ident("derive"));
+    let state_debug = if smd.debug_summary || derives(state_attrs) {
+        quote! {}
+    } else {
+        quote! { #[derive(Debug)] }
+    };
+    let action_debug = if derives(action_attrs) {
+        quote! {}
+    } else {
+        quote! { #[derive(Debug)] }
+    };
+
+    // The newtypes derive what the wrapper derives, which they are then part of.
+    let alphabet = smd.alphabet.as_ref().map(|alphabet| {
+        let actions = smd.actions();
+        let derives = action_attrs
+            .iter()
+            .filter(|a| a.path().is_ident("derive"))
+            .collect::<Vec<_>>();
+        let derives = quote! { #(#derives)* };
+        let serde = serde_derive(&[alphabet]);
+        let docs = actions
+            .iter()
+            .map(|a| format!("`{}::{}`, whole.", alphabet, a));
+        quote! {
+            #(
+                #[doc = #docs]
+                #action_debug
+                #serde
+                #derives
+                #action_vis struct #actions(pub #alphabet);
+            )*
+
+            /// Gives the variants no transition names back.
+            impl TryFrom<#alphabet> for #action_wrapper {
+                type Error = #alphabet;
+
+                fn try_from(a: #alphabet) -> Result<#action_wrapper, #alphabet> {
+                    #[allow(deprecated)]
+                    match a {
+                        #(a @ #alphabet::#actions { .. } => Ok(#action_wrapper::#actions(#actions(a))),)*
+                        #[allow(unreachable_patterns)]
+                        a => Err(a),
+                    }
+                }
+            }
+
+            impl From<#action_wrapper> for #alphabet {
+                fn from(a: #action_wrapper) -> #alphabet {
+                    #[allow(deprecated)]
+                    match a {
+                        #(#action_wrapper::#actions(#actions(a)))|* => a,
+                    }
+                }
+            }
+        }
+    });
+
+    quote! {
+        #state_debug
+        #state_serde
+        #(#state_attrs)*
+        #state_vis enum #state_wrapper {
+            #state_acc
+        }
+
+        #state_from_impl_acc
+
+        impl #state_wrapper {
+            /// Size of each state's payload, boxed or not, to decide which ones are worth boxing.
+            #state_vis const VARIANT_SIZES: &'static [(&'static str, usize)] = &[
+                #variant_sizes_acc
+            ];
+
+            #state_vis fn name(&self) -> &'static str {
+                match self {
+                    #state_name_acc
+                }
+            }
+        }
+
+        /// Prints the name of the state, without its payload.
+        impl ::core::fmt::Display for #state_wrapper {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                f.write_str(self.name())
+            }
+        }
+
+        #action_debug
+        #action_serde
+        #(#action_attrs)*
+        #action_vis enum #action_wrapper {
+            #action_acc
+        }
+
+        impl #action_wrapper {
+            #action_vis fn name(&self) -> &'static str {
+                match self {
+                    #action_name_acc
+                }
+            }
+        }
+
+        /// Prints the name of the action, without its payload.
+        impl ::core::fmt::Display for #action_wrapper {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                f.write_str(self.name())
+            }
+        }
+
+        impl Action for #action_wrapper {}
+        #action_trait_impl_acc
+        #action_from_impl_acc
+        #alphabet
+    }
+}
+
+/// Hands the handler's next state `n` to the policy if it is not one of the declared ones.
+///
+/// With `on_invalid_transition Err`, `n` is given back instead, along with the action the handler
+/// got: `given_back` rebuilds it from the clone `keep_action` takes before the call.
+fn next_state_check(
+    smd: &StateMachineDefinition,
+    state_as_str: &str,
+    action_name: &proc_macro2::TokenStream,
+    next_states: &[StateId],
+    given_back: &proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let allowed = next_states.iter().map(|s| s.to_string());
+    let invalid = quote! {
+        ::state_machine::InvalidTransition {
+            state: #state_as_str,
+            action: #action_name,
+            got: n.name(),
+            allowed: &[#(#allowed),*],
+        }
+    };
+    let report = match &smd.on_invalid_transition {
+        _ if smd.invalid_transition_err() => {
+            quote! {
+                let invalid = #invalid;
+                return Err((n, #given_back, Some(invalid)));
+            }
+        }
+        Some(policy) => quote! { #policy(#invalid); },
+        None => quote! { ::state_machine::panic_on_invalid_transition(#invalid); },
+    };
+    quote! {
+        if !(#(matches!(n, Self::#next_states(_)) ||)* false) {
+            #report
+        }
+    }
+}
+
+/// Clones the action before the handler consumes it, when `on_invalid_transition Err` may have
+/// to give it back.
+fn keep_action(smd: &StateMachineDefinition) -> proc_macro2::TokenStream {
+    if smd.invalid_transition_err() {
+        quote! { let given_back = ::core::clone::Clone::clone(&a); }
+    } else {
+        quote! {}
+    }
+}
+
+fn define_transition(
+    smd: &StateMachineDefinition,
+    st: &StateTransitions,
+) -> proc_macro2::TokenStream {
+    let action_wrapper = &smd.action_wrapper;
+    let boxed = smd.is_boxed(&st.state);
+    let hooks = smd.hooks;
+    let ctx = smd.ctx.as_ref();
+    let asyncness = smd.asyncness.as_ref();
+    let start_state = &st.state;
+    let state_as_str = start_state.to_string();
+
+    // A boxed state is only moved out of its box once the action is known to be accepted.
+    let (state_ref, state_mut, unboxed_state) = if boxed {
+        (
+            quote! { &*state },
+            quote! { &mut *state },
+            quote! { (*state) },
+        )
+    } else {
+        (quote! { &state }, quote! { &mut state }, quote! { state })
+    };
+
+    let (exit, enter) = if hooks {
+        (
+            quote! { let mut s = s; ::state_machine::StateHooks::on_exit(&mut s); },
+            quote! { let mut n = n; ::state_machine::StateHooks::on_enter(&mut n); },
+        )
+    } else {
+        (quote! {}, quote! {})
+    };
+
+    let call = match (ctx, asyncness) {
+        (Some(_), _) => quote! { s.next(a, ctx) },
+        (None, Some(_)) => quote! { s.next(a).await },
+        (None, None) => quote! { s.next(a) },
+    };
+
+    // The listed actions step the child, leaving the parent in the same state unless the child
+    // finished, and bubble up to the parent's transitions if the child rejects them.
+    let (binding, delegation) = match &st.submachine {
+        Some(child) => {
+            let child_as_str = child.to_string();
+            let submachine = quote! { <#start_state as ::state_machine::Submachine<#child>> };
+            let completion = if st.done.is_empty() {
+                quote! {}
+            } else {
+                let done_as_str = format!("{} done", child);
+                let check = next_state_check(
+                    smd,
+                    &state_as_str,
+                    &quote! { #done_as_str },
+                    &st.done,
+                    &quote! { given_back },
+                );
+                quote! {
+                    if finished {
+                        let s = #unboxed_state;
+                        #exit
+                        let mut s = s;
+                        let a = ::state_machine::Done(::core::mem::take(#submachine::child_mut(&mut s)));
+                        let n = #call;
+                        #enter
+                        #check
+                        return Ok(n);
+                    }
+                }
+            };
+            // Under `on_invalid_transition Err`, the action that finished the child is the one
+            // given back.
+            let keep = !st.done.is_empty() && smd.invalid_transition_err();
+            let child_actions = &st.child_actions;
+            let arms = child_actions.iter().map(|a| {
+                let keep = if keep {
+                    quote! { let given_back = #action_wrapper::#a(::core::clone::Clone::clone(&a)); }
+                } else {
+                    quote! {}
+                };
+                quote! {
+                    #action_wrapper::#a(a) => {
+                        #keep
+                        let child = ::core::mem::take(#submachine::child_mut(#state_mut));
+                        match ::state_machine::Machine::next(child, a.into()).map_err(Into::into) {
+                            Ok(child) => {
+                                let finished = ::state_machine::Machine::is_finished(&child);
+                                *#submachine::child_mut(#state_mut) = child;
+                                #completion
+                                return Ok(Self::#start_state(state));
+                            }
+                            Err((child, action)) => {
+                                *#submachine::child_mut(#state_mut) = child;
+                                type ChildAction = <#child as ::state_machine::Machine>::Action;
+                                #[allow(unreachable_patterns)]
+                                match action {
+                                    #(ChildAction::#child_actions(a) => #action_wrapper::#child_actions(a),)*
+                                    _ => unreachable!("{} gave back an action it was not given", #child_as_str),
+                                }
+                            }
+                        }
+                    }
+                }
+            });
+            (
+                quote! { mut state },
+                quote! {
+                    #[allow(deprecated)]
+                    let action = match action {
+                        #(#arms)*
+                        action => action,
+                    };
+                },
+            )
+        }
+        None => (quote! { state }, quote! {}),
+    };
+
+    // Guarded transitions are tried in order, before the unguarded one for the same action, and
+    // the wildcard only catches what no other transition took.
+    let mut transitions = st.transitions.iter().collect::<Vec<_>>();
+    transitions.sort_by_key(|t| (t.wildcard.is_some(), !t.is_conditional()));
+
+    let mut action_dispatch = quote! {};
+    for t in transitions {
+        let (exit, enter) = if t.internal {
+            (&quote! {}, &quote! {})
+        } else {
+            (&exit, &enter)
+        };
+
+        let condition = t.condition(smd, &state_ref);
+        let guard = |condition: Option<proc_macro2::TokenStream>| {
+            condition.map(|condition| quote! { if #condition })
+        };
+        // The wildcard reports the action it actually got, named before the handler takes it.
+        let patterns = match t.wildcard {
+            Some(_) => vec![(
+                quote! { a },
+                guard(condition),
+                quote! { action_name },
+                quote! { given_back },
+                quote! { let action_name = a.name(); },
+            )],
+            None => t
+                .actions
+                .iter()
+                .map(|a| {
+                    let action_as_str = a.to_string();
+                    (
+                        quote! { #action_wrapper::#a(a) },
+                        guard(t.matching(a, condition.clone())),
+                        quote! { #action_as_str },
+                        quote! { #action_wrapper::#a(given_back) },
+                        quote! {},
+                    )
+                })
+                .collect(),
+        };
+        for (pattern, guard, action_name, given_back, name_action) in patterns {
+            let (keep, check) = if t.internal {
+                (quote! {}, quote! {})
+            } else {
+                let keep = keep_action(smd);
+                (
+                    quote! { #name_action #keep },
+                    next_state_check(
+                        smd,
+                        &state_as_str,
+                        &action_name,
+                        &t.next_states,
+                        &given_back,
+                    ),
+                )
+            };
+            action_dispatch = quote! {
+                #action_dispatch
+                #pattern #guard => {
+                    let s = #unboxed_state;
+                    #exit
+                    #keep
+                    let n = #call;
+                    #enter
+                    #check
+                    n
+                }
+            };
+        }
+    }
+
+    // Decided by the state before any transition, the wildcard included.
+    let ignored = &st.ignored;
+    let rejected = &st.rejected;
+    quote! {
+        Self::#start_state(#binding) => {
+            #delegation
+            match action {
+                #(#action_wrapper::#ignored(_) => Self::#start_state(state),)*
+                #(action @ #action_wrapper::#rejected(_) => return Err((Self::#start_state(state), action, None)),)*
+                #action_dispatch
+                #[allow(unreachable_patterns)]
+                action => return Err((Self::#start_state(state), action, None)),
+            }
+        }
+    }
+}
+
+/// Global transitions are tried before the state's own ones, except in the terminal states and in
+/// those which handle the same action themselves.
+fn define_global_transitions(smd: &StateMachineDefinition) -> proc_macro2::TokenStream {
+    if smd.global.is_empty() {
+        return quote! {};
+    }
+    let state_wrapper = &smd.state_wrapper;
+    let action_wrapper = &smd.action_wrapper;
+
+    let (exit, enter) = if smd.hooks {
+        (
+            quote! { let mut s = s; ::state_machine::StateHooks::on_exit(&mut s); },
+            quote! { let mut n = n; ::state_machine::StateHooks::on_enter(&mut n); },
+        )
+    } else {
+        (quote! {}, quote! {})
+    };
+
+    let mut transitions = smd.global.iter().collect::<Vec<_>>();
+    transitions.sort_by_key(|t| !t.is_conditional());
+
+    let mut action_dispatch = quote! {};
+    for t in transitions {
+        let (exit, enter) = if t.internal {
+            (&quote! {}, &quote! {})
+        } else {
+            (&exit, &enter)
+        };
+
+        for a in &t.actions {
+            let overriding = smd
+                .states()
+                .into_iter()
+                .filter(|s| !smd.takes_global(s, a))
+                .collect::<Vec<_>>();
+            let not_overridden = if overriding.is_empty() {
+                quote! { true }
+            } else {
+                quote! { !matches!(self, #(Self::#overriding(_))|*) }
+            };
+            let guard = match t.matching(a, t.condition(smd, &quote! { &self })) {
+                Some(condition) => quote! { if #condition && #not_overridden },
+                None => quote! { if #not_overridden },
+            };
+            let action_as_str = a.to_string();
+            let call = match (&smd.ctx, &smd.asyncness) {
+                (Some(ctx), _) => quote! {
+                    <#state_wrapper as ContextState<#state_wrapper, #a, #ctx>>::next(s, a, ctx)
+                },
+                (None, Some(_)) => quote! {
+                    <#state_wrapper as AsyncState<#state_wrapper, #a>>::next(s, a).await
+                },
+                (None, None) => {
+                    quote! { <#state_wrapper as State<#state_wrapper, #a>>::next(s, a) }
+                }
+            };
+            let (keep, check) = if t.internal {
+                (quote! {}, quote! {})
+            } else {
+                (
+                    keep_action(smd),
+                    next_state_check(
+                        smd,
+                        "*",
+                        &quote! { #action_as_str },
+                        &t.next_states,
+                        &quote! { #action_wrapper::#a(given_back) },
+                    ),
+                )
+            };
+            action_dispatch = quote! {
+                #action_dispatch
+                #action_wrapper::#a(a) #guard => {
+                    let s = self;
+                    #exit
+                    #keep
+                    let n = #call;
+                    #enter
+                    #check
+                    return Ok(n);
+                }
+            };
+        }
+    }
+
+    quote! {
+        let action = match action {
+            #action_dispatch
+            action => action,
+        };
+    }
+}
+
+fn define_loop(smd: &StateMachineDefinition) -> proc_macro2::TokenStream {
+    let state_vis = &smd.state_vis;
+    let state_wrapper = &smd.state_wrapper;
+    let action_wrapper = &smd.action_wrapper;
+
+    let mut acc = quote! {};
+
+    let mut deprecation_acc = quote! {};
+    for d in &smd.deprecated {
+        let old = &d.old;
+        let new = &d.new;
+        deprecation_acc = quote! {
+            #deprecation_acc
+            #action_wrapper::#old(a) => #action_wrapper::#new(a.into()),
+        };
+    }
+
+    let global_dispatch = define_global_transitions(smd);
+    let error = format_ident!("{}Error", state_wrapper);
+
+    for st in &smd.state_transitions {
+        let transition_case = define_transition(smd, st);
+        acc = quote! {
+            #acc
+            #transition_case
+        };
+    }
+
+    let (ctx_param, ctx_arg) = match &smd.ctx {
+        Some(ctx) => (quote! { , ctx: &mut #ctx }, quote! { , ctx }),
+        None => (quote! {}, quote! {}),
+    };
+
+    let (asyncness, await_) = match &smd.asyncness {
+        Some(asyncness) => (quote! { #asyncness }, quote! { .await }),
+        None => (quote! {}, quote! {}),
+    };
+
+    // Only the states holding a child have anything below them.
+    let children = smd
+        .state_transitions
+        .iter()
+        .filter_map(|st| Some((&st.state, st.submachine.as_ref()?)))
+        .collect::<Vec<_>>();
+    let active_state = if children.is_empty() {
+        quote! {}
+    } else {
+        let arms = children.iter().map(|(state, child)| {
+            quote! {
+                #state_wrapper::#state(s) => ::state_machine::Machine::active_state(
+                    <#state as ::state_machine::Submachine<#child>>::child(s),
+                    depth - 1,
+                ),
+            }
+        });
+        quote! {
+            fn active_state(&self, depth: usize) -> Option<&'static str> {
+                if depth == 0 {
+                    return Some(#state_wrapper::name(self));
+                }
+                #[allow(unreachable_patterns)]
+                match self {
+                    #(#arms)*
+                    _ => None,
+                }
+            }
+        }
+    };
+
+    // Without a context to pass nor a future to poll, the machine can be driven by the generic
+    // runners.
+    let machine_impl = if smd.ctx.is_some() || smd.asyncness.is_some() {
+        quote! {}
+    } else {
+        quote! {
+            impl ::state_machine::Machine for #state_wrapper {
+                type Action = #action_wrapper;
+                type Error = #error;
+
+                fn next(self, action: #action_wrapper) -> Result<#state_wrapper, #error> {
+                    #state_wrapper::next(self, action)
+                }
+
+                fn is_deferred(&self, action: &#action_wrapper) -> bool {
+                    #state_wrapper::is_deferred(self, action)
+                }
+
+                fn next_experimental(self, action: #action_wrapper) -> Result<#state_wrapper, #error> {
+                    #state_wrapper::next_with(self, action, true)
+                }
+
+                fn is_finished(&self) -> bool {
+                    #state_wrapper::is_finished(self)
+                }
+
+                fn is_idle(&self) -> bool {
+                    #state_wrapper::is_idle(self)
+                }
+
+                fn allowed_actions(&self) -> &'static [&'static str] {
+                    #state_wrapper::allowed_actions(self)
+                }
+
+                fn name(&self) -> &'static str {
+                    #state_wrapper::name(self)
+                }
+
+                #active_state
+            }
+        }
+    };
+
+    quote! {
+        impl #state_wrapper {
+            #state_vis #asyncness fn next(self, action: #action_wrapper #ctx_param) -> Result<#state_wrapper, #error> {
+                #state_wrapper::next_with(self, action, false #ctx_arg) #await_
+            }
+
+            /// Same as `next`, reporting the transition or the rejection to `observer`.
+            #state_vis #asyncness fn next_observed(self, observer: &mut impl ::state_machine::TransitionObserver, action: #action_wrapper #ctx_param) -> Result<#state_wrapper, #error> {
+                let from = self.name();
+                let action_name = action.name();
+                let next = #state_wrapper::next(self, action #ctx_arg) #await_;
+                match &next {
+                    Ok(to) => observer.on_transition(from, action_name, to.name()),
+                    Err(_) => observer.on_rejection(from, action_name),
+                }
+                next
+            }
+
+            /// Same as `next`, `experimental` telling whether to take the transitions marked as
+            /// such.
+            #state_vis #asyncness fn next_with(self, action: #action_wrapper, experimental: bool #ctx_param) -> Result<#state_wrapper, #error> {
+                #state_wrapper::next_flagged(self, action, experimental, 0 #ctx_arg) #await_
+                    .map_err(|(state, action, invalid)| #error { invalid, ..#error::with_switches(state, action, experimental, 0) })
+            }
+
+            /// `enabled` holds the flags of the `optional` transitions to take.
+            #[allow(unused_variables)]
+            #asyncness fn next_flagged(self, action: #action_wrapper, experimental: bool, enabled: u64 #ctx_param) -> Result<#state_wrapper, (#state_wrapper, #action_wrapper, Option<::state_machine::InvalidTransition>)> {
+                #[allow(deprecated)]
+                let action = match action {
+                    #deprecation_acc
+                    action => action,
+                };
+
+                #global_dispatch
+
+                Ok(match self  {
+                    #acc
+                    #[allow(unreachable_patterns)]
+                    terminal_state => terminal_state
+                })
+            }
+        }
+
+        #machine_impl
+    }
+}
+
+/// `{State}Flagged`, pairing the state wrapper with the flags of the `optional` transitions the
+/// instance takes, one bit per flag.
+fn define_flagged(smd: &StateMachineDefinition) -> proc_macro2::TokenStream {
+    let flags = smd.flags();
+    if flags.is_empty() {
+        return quote! {};
+    }
+    let state_vis = &smd.state_vis;
+    let state_wrapper = &smd.state_wrapper;
+    let action_wrapper = &smd.action_wrapper;
+    let flagged = format_ident!("{}Flagged", state_wrapper);
+    let error = format_ident!("{}Error", state_wrapper);
+    let consts = flags.iter().map(|f| screaming_snake_case(f));
+    let bits = 0..flags.len() as u64;
+
+    let (ctx_param, ctx_arg) = match &smd.ctx {
+        Some(ctx) => (quote! { , ctx: &mut #ctx }, quote! { , ctx }),
+        None => (quote! {}, quote! {}),
+    };
+    let (asyncness, await_) = match &smd.asyncness {
+        Some(asyncness) => (quote! { #asyncness }, quote! { .await }),
+        None => (quote! {}, quote! {}),
+    };
+
+    let machine_impl = if smd.ctx.is_some() || smd.asyncness.is_some() {
+        quote! {}
+    } else {
+        quote! {
+            impl ::state_machine::Machine for #flagged {
+                type Action = #action_wrapper;
+                type Error = #error<#flagged>;
+
+                fn next(self, action: #action_wrapper) -> Result<#flagged, #error<#flagged>> {
+                    #flagged::next(self, action)
+                }
+
+                fn is_deferred(&self, action: &#action_wrapper) -> bool {
+                    self.state.is_deferred(action)
+                }
+
+                fn next_experimental(self, action: #action_wrapper) -> Result<#flagged, #error<#flagged>> {
+                    let enabled = self.enabled;
+                    match self.state.next_flagged(action, true, enabled) {
+                        Ok(state) => Ok(#flagged { state, enabled }),
+                        Err((state, action, invalid)) => Err(#error { invalid, ..#error::with_switches(#flagged { state, enabled }, action, true, enabled) }),
+                    }
+                }
+
+                fn is_finished(&self) -> bool {
+                    self.state.is_finished()
+                }
+
+                fn is_idle(&self) -> bool {
+                    self.state.is_idle()
+                }
+
+                fn allowed_actions(&self) -> &'static [&'static str] {
+                    self.state.allowed_actions()
+                }
+
+                fn name(&self) -> &'static str {
+                    self.state.name()
+                }
+
+                fn active_state(&self, depth: usize) -> Option<&'static str> {
+                    ::state_machine::Machine::active_state(&self.state, depth)
+                }
+            }
+        }
+    };
+
+    quote! {
+        #state_vis struct #flagged {
+            state: #state_wrapper,
+            enabled: u64,
+        }
+
+        impl #flagged {
+            #(#state_vis const #consts: u64 = 1 << #bits;)*
+
+            /// `state`, only taking the `optional` transitions whose flag is set in `enabled`.
+            #state_vis fn new(state: #state_wrapper, enabled: u64) -> #flagged {
+                #flagged { state, enabled }
+            }
+
+            #state_vis fn state(&self) -> &#state_wrapper {
+                &self.state
+            }
+
+            #state_vis fn enabled(&self) -> u64 {
+                self.enabled
+            }
+
+            #state_vis fn into_inner(self) -> #state_wrapper {
+                self.state
+            }
+
+            #state_vis #asyncness fn next(self, action: #action_wrapper #ctx_param) -> Result<#flagged, #error<#flagged>> {
+                let enabled = self.enabled;
+                match #state_wrapper::next_flagged(self.state, action, false, enabled #ctx_arg) #await_ {
+                    Ok(state) => Ok(#flagged { state, enabled }),
+                    Err((state, action, invalid)) => Err(#error { invalid, ..#error::with_switches(#flagged { state, enabled }, action, false, enabled) }),
+                }
+            }
+        }
+
+        impl AsRef<#state_wrapper> for #flagged {
+            fn as_ref(&self) -> &#state_wrapper {
+                &self.state
+            }
+        }
+
+        // The wrapper may not be Debug if its derives were forwarded without it.
+        impl ::core::fmt::Debug for #flagged
+        where
+            for<'a> #state_wrapper: ::core::fmt::Debug,
+        {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                f.debug_struct(stringify!(#flagged))
+                    .field("state", &self.state)
+                    .field("enabled", &self.enabled)
+                    .finish()
+            }
+        }
+
+        #machine_impl
+    }
+}
+
+/// `{State}Error`, given back with the machine and the action when the current state rejects it.
+/// Generic over the machine so that `{State}Flagged` can be given back as well.
+fn define_error(smd: &StateMachineDefinition) -> proc_macro2::TokenStream {
+    let state_vis = &smd.state_vis;
+    let state_wrapper = &smd.state_wrapper;
+    let action_wrapper = &smd.action_wrapper;
+    let error = format_ident!("{}Error", state_wrapper);
+    let error_name = error.to_string();
+
+    let std_impls = if cfg!(feature = "std") {
+        quote! {
+            impl<S: AsRef<#state_wrapper>> ::std::error::Error for #error<S> {}
+
+            impl<S: AsRef<#state_wrapper>> From<#error<S>> for ::state_machine::errors::MachineError {
+                fn from(e: #error<S>) -> ::state_machine::errors::MachineError {
+                    match e.invalid {
+                        Some(invalid) => invalid.into(),
+                        None => ::state_machine::errors::Rejected {
+                            state: e.state.as_ref().name(),
+                            action: e.action.name(),
+                        }
+                        .into(),
+                    }
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    quote! {
+        /// Action rejected by the current state, given back along with the machine.
+        #state_vis struct #error<S = #state_wrapper> {
+            #state_vis state: S,
+            #state_vis action: #action_wrapper,
+            /// Names of the actions the state would have taken instead.
+            #state_vis expected: ::state_machine::ExpectedActions,
+            /// With `on_invalid_transition Err`, set when the handler went to a state its
+            /// transition does not declare. `state` is then the one it went to.
+            #state_vis invalid: Option<::state_machine::InvalidTransition>,
+        }
+
+        impl<S: AsRef<#state_wrapper>> #error<S> {
+            /// `action` rejected by `state`, as `next` would.
+            #state_vis fn new(state: S, action: #action_wrapper) -> #error<S> {
+                #error::with_switches(state, action, false, 0)
+            }
+
+            /// `action` rejected by `state` with the experimental transitions and the `enabled`
+            /// optional ones.
+            fn with_switches(state: S, action: #action_wrapper, experimental: bool, enabled: u64) -> #error<S> {
+                let expected = state.as_ref().expected_actions(&action, experimental, enabled);
+                #error { state, action, expected, invalid: None }
+            }
+        }
+
+        impl<S> From<#error<S>> for (S, #action_wrapper) {
+            fn from(e: #error<S>) -> (S, #action_wrapper) {
+                (e.state, e.action)
+            }
+        }
+
+        impl<S: AsRef<#state_wrapper>> ::core::fmt::Display for #error<S> {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                if let Some(invalid) = &self.invalid {
+                    return invalid.fmt(f);
+                }
+                write!(
+                    f,
+                    "State {} rejected action {}, expected one of {:?}",
+                    self.state.as_ref().name(),
+                    self.action.name(),
+                    self.expected
+                )
+            }
+        }
+
+        // Written by hand, the states and actions need not implement `Debug`.
+        impl<S: AsRef<#state_wrapper>> ::core::fmt::Debug for #error<S> {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                f.debug_struct(#error_name)
+                    .field("state", &self.state.as_ref().name())
+                    .field("action", &self.action.name())
+                    .field("expected", &self.expected)
+                    .field("invalid", &self.invalid)
+                    .finish()
+            }
+        }
+
+        #std_impls
+
+        impl AsRef<#state_wrapper> for #state_wrapper {
+            fn as_ref(&self) -> &#state_wrapper {
+                self
+            }
+        }
+    }
+}
+
+/// `FromStr` parsing `Name payload`, the payload going through its own `FromStr`, or `Default`
+/// when there is none. Payloads implementing neither cannot be parsed.
+fn define_parsing(smd: &StateMachineDefinition) -> proc_macro2::TokenStream {
+    let action_wrapper = &smd.action_wrapper;
+    let actions = smd.actions();
+    let names = actions.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+
+    quote! {
+        impl ::core::str::FromStr for #action_wrapper {
+            type Err = ::state_machine::ParseActionError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                #[allow(unused_imports)]
+                use ::state_machine::__payload::{Payload, Unparsable, ViaDefault, ViaFromStr};
+
+                let s = s.trim();
+                let (name, payload) = s.split_once(char::is_whitespace).unwrap_or((s, ""));
+                let payload = payload.trim_start();
+                #[allow(deprecated)]
+                match name {
+                    #(#names => (&&&Payload::<#actions>(::core::marker::PhantomData))
+                        .parse_payload(payload)
+                        .map(#action_wrapper::#actions)
+                        .ok_or(::state_machine::ParseActionError::InvalidPayload(
+                            ::state_machine::InvalidAction { action: #names },
+                        )),)*
+                    _ => Err(::state_machine::ParseActionError::UnknownAction),
+                }
+            }
+        }
+
+        impl TryFrom<&str> for #action_wrapper {
+            type Error = ::state_machine::ParseActionError;
+
+            fn try_from(s: &str) -> Result<Self, Self::Error> {
+                s.parse()
+            }
+        }
+    }
+}
+
+fn define_step(smd: &StateMachineDefinition) -> proc_macro2::TokenStream {
+    let state_vis = &smd.state_vis;
+    let state_wrapper = &smd.state_wrapper;
+    let action_wrapper = &smd.action_wrapper;
+
+    let kind = format_ident!("{}Kind", action_wrapper);
+    let old = smd.deprecated.iter().map(|d| &d.old);
+    let new = smd.deprecated.iter().map(|d| &d.new);
+
+    // Awaiting the handler would leave `self` moved out across a suspension point, and without an
+    // initial state there is nothing to leave the machine in if a handler panics.
+    let step = match &smd.initial {
+        Some(initial) if smd.asyncness.is_none() => {
+            let (ctx_param, ctx_arg) = match &smd.ctx {
+                Some(ctx) => (quote! { , ctx: &mut #ctx }, quote! { , ctx }),
+                None => (quote! {}, quote! {}),
+            };
+            // The `for<'a>` only has rustc check the bounds when the methods are used, as for
+            // `new`.
+            quote! {
+                /// Moves the state out to `f` and puts back the one it returns. If `f` panics,
+                /// the machine is reset to its initial state, without going through the hooks,
+                /// before the panic goes on.
+                #[inline]
+                fn replace_state<T>(&mut self, f: impl FnOnce(#state_wrapper) -> (#state_wrapper, T)) -> T
+                where
+                    for<'a> #initial: Default,
+                {
+                    struct OnUnwind<F: FnMut()>(F);
+                    impl<F: FnMut()> Drop for OnUnwind<F> {
+                        fn drop(&mut self) {
+                            (self.0)()
+                        }
+                    }
+
+                    let this: *mut #state_wrapper = self;
+                    // SAFETY: `this` comes from `&mut self`, which is not used until the guard
+                    // is done with it. The guard writes a state back if `f` unwinds, after the
+                    // moved out state was consumed.
+                    let guard = OnUnwind(move || unsafe {
+                        ::core::ptr::write(this, #initial::default().into())
+                    });
+                    let (state, result) = f(unsafe { ::core::ptr::read(this) });
+                    ::core::mem::forget(guard);
+                    // SAFETY: the state read above was consumed, this puts a new one in its place.
+                    unsafe { ::core::ptr::write(this, state) };
+                    result
+                }
+
+                /// In-place `next`: an action the current state rejects is given back, the
+                /// machine staying as it was.
+                ///
+                /// Only usable when the `initial` state implements `Default`, and not generated
+                /// for `async` machines or those without an `initial` state: if a handler panics,
+                /// the machine is reset to a default initial state before the panic goes on,
+                /// without going through the hooks. Whatever state it was in is lost, and nothing
+                /// but the panic itself tells a caller catching it that the machine was reset.
+                #state_vis fn step(&mut self, action: #action_wrapper #ctx_param) -> Result<(), #action_wrapper>
+                where
+                    for<'a> #initial: Default,
+                {
+                    self.replace_state(|state| match #state_wrapper::next(state, action #ctx_arg) {
+                        Ok(state) => (state, Ok(())),
+                        Err(e) => (e.state, Err(e.action)),
+                    })
+                }
+
+                /// Steps through `actions` in place, returning how many were applied. Stops at
+                /// the first one the current state rejects, the machine staying as it was then.
+                ///
+                /// Available under the same conditions as `step`, with actions implementing
+                /// `Clone`. A panicking handler resets the machine to its initial state, as with
+                /// `step`.
+                ///
+                /// The state is only moved out of `self` once for the whole slice, and each
+                /// action goes straight to the dispatcher, without building a rejection error.
+                #[inline]
+                #state_vis fn step_slice(&mut self, actions: &[#action_wrapper] #ctx_param) -> Result<usize, ::state_machine::StepError>
+                where
+                    for<'a> #initial: Default,
+                    for<'a> #action_wrapper: Clone,
+                {
+                    self.replace_state(|mut state| {
+                        for (index, action) in actions.iter().enumerate() {
+                            match #state_wrapper::next_flagged(state, action.clone(), false, 0 #ctx_arg) {
+                                Ok(next) => state = next,
+                                Err((next, action, invalid)) => {
+                                    let error = ::state_machine::StepError {
+                                        index,
+                                        state: next.name(),
+                                        action: action.name(),
+                                        invalid,
+                                        position: None,
+                                    };
+                                    return (next, Err(error));
+                                }
+                            }
+                        }
+                        (state, Ok(actions.len()))
+                    })
+                }
+
+                /// Steps through the characters of `text` in place, as `step_slice` does, the
+                /// error telling the line and column of the rejected one.
+                ///
+                /// Available under the same conditions as `step`, with actions built from
+                /// `char`.
+                #state_vis fn step_str(&mut self, text: &str #ctx_param) -> Result<usize, ::state_machine::StepError>
+                where
+                    for<'a> #initial: Default,
+                    for<'a> #action_wrapper: From<char>,
+                {
+                    self.step_positioned(text.chars().map(|c| (c.into(), c.len_utf8() as u32, c == '\n')) #ctx_arg)
+                }
+
+                /// Same as `step_str` for the bytes of `bytes`, the column being counted in
+                /// bytes.
+                #state_vis fn step_bytes(&mut self, bytes: &[u8] #ctx_param) -> Result<usize, ::state_machine::StepError>
+                where
+                    for<'a> #initial: Default,
+                    for<'a> #action_wrapper: From<u8>,
+                {
+                    self.step_positioned(bytes.iter().map(|&b| (b.into(), 1, b == b'\n')) #ctx_arg)
+                }
+
+                /// `units` holding the action, its length in bytes and whether it ends a line.
+                fn step_positioned(&mut self, units: impl Iterator<Item = (#action_wrapper, u32, bool)> #ctx_param) -> Result<usize, ::state_machine::StepError>
+                where
+                    for<'a> #initial: Default,
+                {
+                    self.replace_state(|mut state| {
+                        let mut next_position = ::state_machine::Position::START;
+                        let mut index = 0;
+                        for (action, len, newline) in units {
+                            let position = next_position;
+                            next_position = position.after(len, newline);
+                            match #state_wrapper::next_flagged(state, action, false, 0 #ctx_arg) {
+                                Ok(next) => state = next,
+                                Err((next, action, invalid)) => {
+                                    let error = ::state_machine::StepError {
+                                        index,
+                                        state: next.name(),
+                                        action: action.name(),
+                                        invalid,
+                                        position: Some(position),
+                                    };
+                                    return (next, Err(error));
+                                }
+                            }
+                            index += 1;
+                        }
+                        (state, Ok(index))
+                    })
+                }
+            }
+        }
+        _ => quote! {},
+    };
+
+    quote! {
+        impl #state_wrapper {
+            /// Whether the current state has a transition for `action`, guards aside.
+            #state_vis fn accepts_action(&self, action: &#action_wrapper) -> bool {
+                #[allow(deprecated)]
+                let action = match action {
+                    #(#action_wrapper::#old(_) => #kind::#new,)*
+                    action => action.kind(),
+                };
+                self.kind().edges().iter().any(|&(a, _)| a == action)
+            }
+
+            #step
+        }
+    }
+}
+
+fn define_deferred(smd: &StateMachineDefinition) -> proc_macro2::TokenStream {
+    let state_vis = &smd.state_vis;
+    let state_wrapper = &smd.state_wrapper;
+    let action_wrapper = &smd.action_wrapper;
+
+    let mut acc = quote! {};
+    for st in smd
+        .state_transitions
+        .iter()
+        .filter(|st| !st.deferred.is_empty())
+    {
+        let state = &st.state;
+        let deferred = &st.deferred;
+        acc = quote! {
+            #acc
+            #state_wrapper::#state(_) => matches!(action, #(#action_wrapper::#deferred(_))|*),
+        };
+    }
+
+    quote! {
+        impl #state_wrapper {
+            /// Whether the current state defers `action` until a later state accepts it.
+            #[allow(unused_variables)]
+            #state_vis fn is_deferred(&self, action: &#action_wrapper) -> bool {
+                match self {
+                    #acc
+                    #[allow(unreachable_patterns)]
+                    _ => false,
+                }
+            }
+        }
+    }
+}
+
+/// States the machine may end up in when `action` is sent in `state`, ignoring guards: the
+/// action-kind view of the table. Each state is listed once.
+fn kind_successors<'a>(
+    smd: &'a StateMachineDefinition,
+    state: &'a StateId,
+    action: &'a ActionId,
+) -> Vec<&'a StateId> {
+    let mut distinct = Vec::new();
+    for successor in all_kind_successors(smd, state, action) {
+        if !distinct.contains(&successor) {
+            distinct.push(successor);
+        }
+    }
+    distinct
+}
+
+fn all_kind_successors<'a>(
+    smd: &'a StateMachineDefinition,
+    state: &'a StateId,
+    action: &'a ActionId,
+) -> Vec<&'a StateId> {
+    let action = smd
+        .deprecated
+        .iter()
+        .find(|d| &d.old == action)
+        .map_or(action, |d| &d.new);
+    let targets = |t: &'a Transition| -> Vec<&'a StateId> {
+        if t.internal {
+            vec![state]
+        } else {
+            t.next_states.iter().collect()
+        }
+    };
+
+    let st = smd.state_transitions.iter().find(|st| &st.state == state);
+    if let Some(st) = st {
+        if st.rejected.contains(action) {
+            return Vec::new();
+        }
+        if st.ignored.contains(action) {
+            return vec![state];
+        }
+    }
+    let mentioned = st.map_or(Vec::new(), |st| {
+        st.transitions
+            .iter()
+            .filter(|t| t.actions.contains(action))
+            .collect()
+    });
+    let global = smd
+        .global
+        .iter()
+        .filter(|t| t.actions.contains(action) && !smd.terminal.contains(state))
+        .flat_map(targets)
+        .collect::<Vec<_>>();
+    if mentioned.is_empty() && !global.is_empty() {
+        return global;
+    }
+    let Some(st) = st else {
+        return vec![state];
+    };
+
+    let mut successors = Vec::new();
+    if st.child_actions.contains(action) {
+        successors.push(state);
+        successors.extend(&st.done);
+    }
+    successors.extend(mentioned.iter().flat_map(|t| targets(t)));
+    if mentioned.iter().all(|t| t.is_conditional()) {
+        let wildcards = st.transitions.iter().filter(|t| t.wildcard.is_some());
+        successors.extend(wildcards.flat_map(targets));
+    }
+    successors
+}
+
+/// Condition under which `state` accepts `action`, from the `experimental` switch and the
+/// `enabled` flags, or `None` if it never does: the runtime counterpart of `kind_successors`.
+fn acceptance(
+    smd: &StateMachineDefinition,
+    state: &StateId,
+    action: &ActionId,
+) -> Option<proc_macro2::TokenStream> {
+    let any = |ways: Vec<&Transition>| {
+        if ways.is_empty() {
+            return None;
+        }
+        let mut conditions = Vec::new();
+        for t in ways {
+            let switches = t.switches(smd);
+            if switches.is_empty() {
+                return Some(quote! { true });
+            }
+            conditions.push(quote! { (#(#switches)&&*) });
+        }
+        Some(quote! { #(#conditions)||* })
+    };
+
+    let st = smd.state_transitions.iter().find(|st| &st.state == state);
+    if let Some(st) = st {
+        if st.rejected.contains(action) {
+            return None;
+        }
+        if st.ignored.contains(action) {
+            return Some(quote! { true });
+        }
+    }
+    let mentioned = st.map_or(Vec::new(), |st| {
+        st.transitions
+            .iter()
+            .filter(|t| t.actions.contains(action))
+            .collect::<Vec<_>>()
+    });
+    if mentioned.is_empty() && !smd.terminal.contains(state) {
+        let global = smd.global.iter().filter(|t| t.actions.contains(action));
+        if let Some(condition) = any(global.collect()) {
+            return Some(condition);
+        }
+    }
+    let Some(st) = st else {
+        return Some(quote! { true });
+    };
+    if st.child_actions.contains(action) {
+        return Some(quote! { true });
+    }
+
+    // Unless the action is always taken, whatever its transitions leave goes to the wildcard.
+    let mut ways = mentioned.clone();
+    if mentioned.iter().all(|t| t.is_conditional()) {
+        ways.extend(st.transitions.iter().filter(|t| t.wildcard.is_some()));
+    }
+    any(ways)
+}
+
+fn define_automaton(smd: &StateMachineDefinition) -> proc_macro2::TokenStream {
+    let state_vis = &smd.state_vis;
+    let state_wrapper = &smd.state_wrapper;
+    let action_vis = &smd.action_vis;
+    let action_wrapper = &smd.action_wrapper;
+    let kind = format_ident!("{}Kind", action_wrapper);
+    let state_kind = format_ident!("{}Kind", state_wrapper);
+
+    let actions = smd.actions();
+    let all_states = smd.states();
+    let mut edges_acc = quote! {};
+    for s in &all_states {
+        let mut edges = quote! {};
+        for a in actions.iter().filter(|a| !smd.is_deprecated(a)) {
+            let successors = kind_successors(smd, s, a);
+            if !successors.is_empty() {
+                edges = quote! { #edges (#kind::#a, &[#(#state_kind::#successors),*]), };
+            }
+        }
+        edges_acc = quote! {
+            #edges_acc
+            #state_kind::#s => &[#edges],
+        };
+    }
+
+    let kinds = quote! {
+        /// Actions without their payload.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        #action_vis enum #kind {
+            #(#actions,)*
+        }
+
+        impl #action_wrapper {
+            #action_vis fn kind(&self) -> #kind {
+                #[allow(deprecated)]
+                match self {
+                    #(#action_wrapper::#actions(_) => #kind::#actions,)*
+                }
+            }
+        }
+
+        /// States without their payload.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        #state_vis enum #state_kind {
+            #(#all_states,)*
+        }
+
+        impl #state_wrapper {
+            #state_vis fn kind(&self) -> #state_kind {
+                match self {
+                    #(#state_wrapper::#all_states(_) => #state_kind::#all_states,)*
+                }
+            }
+        }
+
+        impl #state_kind {
+            /// Actions accepted in this state, with the states each one may lead to. Guards are
+            /// assumed to be able to go either way.
+            #state_vis fn edges(self) -> &'static [(#kind, &'static [#state_kind])] {
+                match self {
+                    #edges_acc
+                }
+            }
+        }
+
+        impl IntoIterator for #state_kind {
+            type Item = (#kind, &'static [#state_kind]);
+            type IntoIter = ::core::iter::Copied<::core::slice::Iter<'static, (#kind, &'static [#state_kind])>>;
+
+            fn into_iter(self) -> Self::IntoIter {
+                self.edges().iter().copied()
+            }
+        }
+    };
+    let kinds = if cfg!(feature = "petgraph") {
+        quote! {
+            #kinds
+
+            impl #state_wrapper {
+                /// Transition graph with a node per state kind, the edges being weighted by the
+                /// action taking them.
+                #state_vis fn to_graph() -> ::state_machine::petgraph::graph::DiGraph<#state_kind, #kind> {
+                    let mut graph = ::state_machine::petgraph::graph::DiGraph::new();
+                    let nodes = [#(graph.add_node(#state_kind::#all_states)),*];
+                    for (from, state) in [#(#state_kind::#all_states),*].into_iter().enumerate() {
+                        for (action, successors) in state {
+                            for &to in successors {
+                                graph.add_edge(nodes[from], nodes[to as usize], action);
+                            }
+                        }
+                    }
+                    graph
+                }
+            }
+        }
+    } else {
+        kinds
+    };
+    let kinds = match &smd.initial {
+        Some(initial) => {
+            let validator = format_ident!("{}Validator", state_wrapper);
+            let state_count = all_states.len();
+            let terminal = &smd.terminal;
+            quote! {
+                #kinds
+
+                /// Kind-level copy of the machine, without payloads nor handlers, telling whether
+                /// action sequences are legal. Guards are assumed to be able to go either way, so
+                /// it tracks every state the machine may be in.
+                // Only ever built by its own functions, which a crate may well not call.
+                #[allow(dead_code)]
+                #[derive(Debug, Clone, PartialEq, Eq)]
+                #state_vis struct #validator {
+                    possible: [bool; #state_count],
+                }
+
+                impl #validator {
+                    const STATES: [#state_kind; #state_count] = [#(#state_kind::#all_states),*];
+
+                    #state_vis fn new() -> Self {
+                        let mut possible = [false; #state_count];
+                        possible[#state_kind::#initial as usize] = true;
+                        #validator { possible }
+                    }
+
+                    /// Follows `action`, or gives it back if no possible state accepts it.
+                    #state_vis fn step(&mut self, action: #kind) -> Result<(), #kind> {
+                        let mut next = [false; #state_count];
+                        for (state, _) in Self::STATES.iter().zip(self.possible).filter(|(_, p)| *p) {
+                            for &(a, successors) in state.edges() {
+                                if a == action {
+                                    for &successor in successors {
+                                        next[successor as usize] = true;
+                                    }
+                                }
+                            }
+                        }
+                        if !next.contains(&true) {
+                            return Err(action);
+                        }
+                        self.possible = next;
+                        Ok(())
+                    }
+
+                    /// Checks a whole sequence from the initial state, giving the index of the
+                    /// first illegal action.
+                    #state_vis fn validate(actions: &[#kind]) -> Result<Self, usize> {
+                        let mut validator = Self::new();
+                        for (i, &action) in actions.iter().enumerate() {
+                            validator.step(action).map_err(|_| i)?;
+                        }
+                        Ok(validator)
+                    }
+
+                    /// The states the machine may be in.
+                    #state_vis fn possible_states(&self) -> impl Iterator<Item = #state_kind> + '_ {
+                        Self::STATES
+                            .into_iter()
+                            .zip(self.possible)
+                            .filter(|(_, p)| *p)
+                            .map(|(state, _)| state)
+                    }
+
+                    /// Whether the machine may be in a terminal state.
+                    #state_vis fn is_accepting(&self) -> bool {
+                        self.possible_states()
+                            .any(|state| [#(#state_kind::#terminal),*].contains(&state))
+                    }
+                }
+
+                impl Default for #validator {
+                    fn default() -> Self {
+                        Self::new()
+                    }
+                }
+            }
+        }
+        None => kinds,
+    };
+    // The queries below allocate.
+    if smd.initial.is_none() || !cfg!(feature = "std") {
+        return kinds;
+    }
+
+    let validator = format_ident!("{}Validator", state_wrapper);
+    let actions = actions
+        .into_iter()
+        .filter(|a| !smd.is_deprecated(a))
+        .collect::<Vec<_>>();
+    let state_count = all_states.len();
+    let initial = &smd.initial;
+    let terminal = &smd.terminal;
+
+    quote! {
+        #kinds
+
+        impl #state_wrapper {
+            /// Shortest action sequence leading from the initial state to `state`, or `None` if
+            /// it cannot be reached. Guards are assumed to be able to go either way.
+            #state_vis fn reachability_witness(state: #state_kind) -> Option<Vec<#kind>> {
+                let target = state as usize;
+                let mut paths = vec![None; #state_count];
+                let mut queue = ::std::collections::VecDeque::new();
+                for initial in #validator::new().possible_states() {
+                    paths[initial as usize] = Some(Vec::new());
+                    queue.push_back(initial);
+                }
+                while let Some(current) = queue.pop_front() {
+                    if current as usize == target {
+                        return paths[target].clone();
+                    }
+                    for &(action, successors) in current.edges() {
+                        for &successor in successors {
+                            if paths[successor as usize].is_none() {
+                                let mut path: Vec<#kind> = paths[current as usize].clone().unwrap();
+                                path.push(action);
+                                paths[successor as usize] = Some(path);
+                                queue.push_back(successor);
+                            }
+                        }
+                    }
+                }
+                None
+            }
+
+            /// Whether some run of the machine, from its initial state, ends in a terminal state
+            /// after `actions`. Guards are assumed to be able to go either way.
+            #state_vis fn accepts(actions: &[#kind]) -> bool {
+                #validator::validate(actions).is_ok_and(|validator| validator.is_accepting())
+            }
+
+            /// Every action sequence of at most `max_len` actions that [`accepts`](Self::accepts)
+            /// returns true for, shortest first.
+            #state_vis fn accepted_sequences(max_len: usize) -> Vec<Vec<#kind>> {
+                let mut accepted = Vec::new();
+                let mut frontier = vec![(Vec::new(), #validator::new())];
+                for len in 0..=max_len {
+                    let mut next_frontier = Vec::new();
+                    for (sequence, validator) in frontier {
+                        if validator.is_accepting() {
+                            accepted.push(sequence.clone());
+                        }
+                        if len == max_len {
+                            continue;
+                        }
+                        for action in [#(#kind::#actions),*] {
+                            let mut next = validator.clone();
+                            if next.step(action).is_ok() {
+                                let mut sequence = sequence.clone();
+                                sequence.push(action);
+                                next_frontier.push((sequence, next));
+                            }
+                        }
+                    }
+                    frontier = next_frontier;
+                }
+                accepted
+            }
+
+            /// The action sequences [`accepts`](Self::accepts) returns true for, to combine with
+            /// other languages over the same actions.
+            #state_vis fn language() -> ::state_machine::automaton::Language<#kind> {
+                let edges = [#(#state_kind::#all_states),*]
+                    .into_iter()
+                    .map(|state| {
+                        state
+                            .edges()
+                            .iter()
+                            .map(|&(action, successors)| {
+                                (action, successors.iter().map(|&s| s as usize).collect())
+                            })
+                            .collect()
+                    })
+                    .collect();
+                ::state_machine::automaton::Language::from_nfa(
+                    vec![#(#kind::#actions),*],
+                    #state_kind::#initial as usize,
+                    &[#(#state_kind::#terminal as usize),*],
+                    edges,
+                )
+            }
+        }
+    }
+}
+
+fn define_debug_summary(smd: &StateMachineDefinition) -> proc_macro2::TokenStream {
+    if !smd.debug_summary {
+        return quote! {};
+    }
+    let state_wrapper = &smd.state_wrapper;
+
+    let mut acc = quote! {};
+    for s in smd.states() {
+        let accepted = smd
+            .actions()
+            .into_iter()
+            .filter(|a| !smd.is_deprecated(a))
+            .filter(|a| !kind_successors(smd, s, a).is_empty())
+            .map(|a| a.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let prefix = format!("{}::{} {{ ", state_wrapper, s);
+        let suffix = format!(" }} accepts [{}]", accepted);
+        acc = quote! {
+            #acc
+            #state_wrapper::#s(s) => {
+                f.write_str(#prefix)?;
+                ::core::fmt::Debug::fmt(s, f)?;
+                f.write_str(#suffix)
+            }
+        };
+    }
+
+    quote! {
+        impl ::core::fmt::Debug for #state_wrapper {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                match self {
+                    #acc
+                }
+            }
+        }
+    }
+}
+
+/// Graphviz rendering of the transition graph.
+fn dot(smd: &StateMachineDefinition) -> String {
+    let escape = |label: &str| label.replace('\\', "\\\\").replace('"', "\\\"");
+    let mut dot = format!("digraph {} {{\n", smd.state_wrapper);
+    if let Some(initial) = &smd.initial {
+        dot += &format!("    __start [shape=point];\n    __start -> {};\n", initial);
+    }
+    for terminal in &smd.terminal {
+        dot += &format!("    {} [shape=doublecircle];\n", terminal);
+    }
+    for (from, label, to) in smd.edges() {
+        dot += &format!("    {} -> {} [label=\"{}\"];\n", from, to, escape(&label));
+    }
+    dot += "}\n";
+    dot
+}
+
+/// PlantUML state diagram of the transition graph. States embedding a submachine are annotated
+/// with it.
+fn plantuml(smd: &StateMachineDefinition) -> String {
+    let mut uml = "@startuml\n".to_owned();
+    for st in &smd.state_transitions {
+        if let Some(child) = &st.submachine {
+            uml += &format!("state {} : submachine {}\n", st.state, child);
+        }
+    }
+    if let Some(initial) = &smd.initial {
+        uml += &format!("[*] --> {}\n", initial);
+    }
+    for (from, label, to) in smd.edges() {
+        uml += &format!("{} --> {} : {}\n", from, to, label);
+    }
+    for terminal in &smd.terminal {
+        uml += &format!("{} --> [*]\n", terminal);
+    }
+    uml += "@enduml\n";
+    uml
+}
+
+/// W3C SCXML document of the machine, which `state_machine_from_scxml!` reads back as long as
+/// there are no guards. Global transitions are repeated in every state neither terminal nor
+/// overriding them.
+///
+/// Ignored, rejected and deferred actions, and those only there for a submachine, are written as
+/// targetless transitions marked with `sm:kind`, which the reader turns back into the same items.
+/// Other tools see them as transitions leaving the state as it is. The states a submachine hands
+/// control back to are listed in `sm:done`.
+fn scxml(smd: &StateMachineDefinition) -> String {
+    let escape = |text: &str| {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    };
+    let transition = |t: &Transition, actions: Vec<String>| {
+        let mut element = format!("    <transition event=\"{}\"", actions.join(" "));
+        if !t.next_states.is_empty() {
+            let targets = t.next_states.iter().map(|s| s.to_string());
+            element += &format!(" target=\"{}\"", targets.collect::<Vec<_>>().join(" "));
+        }
+        if let Some(guard) = &t.guard {
+            element += &format!(" cond=\"{}\"", escape(&quote! { #guard }.to_string()));
+        }
+        element + "/>\n"
+    };
+
+    let mut scxml = format!(
+        "<scxml xmlns=\"http://www.w3.org/2005/07/scxml\" xmlns:sm=\"{}\" version=\"1.0\" name=\"{}\"",
+        scxml::NAMESPACE,
+        smd.state_wrapper
+    );
+    if let Some(initial) = &smd.initial {
+        scxml += &format!(" initial=\"{}\"", initial);
+    }
+    scxml += ">\n";
+    for state in smd.states() {
+        let blocks = smd
+            .state_transitions
+            .iter()
+            .filter(|st| &st.state == state)
+            .collect::<Vec<_>>();
+        let mut transitions = String::new();
+        let mut submachine = None;
+        for st in &blocks {
+            if st.submachine.is_some() {
+                submachine = Some(st);
+            }
+            let marked = [
+                ("submachine", &st.child_actions),
+                ("ignore", &st.ignored),
+                ("reject", &st.rejected),
+                ("defer", &st.deferred),
+            ];
+            for (kind, actions) in marked {
+                if !actions.is_empty() {
+                    let actions = actions.iter().map(|a| a.to_string());
+                    transitions += &format!(
+                        "    <transition event=\"{}\" sm:kind=\"{}\"/>\n",
+                        actions.collect::<Vec<_>>().join(" "),
+                        kind
+                    );
+                }
+            }
+            for t in &st.transitions {
+                let actions = match t.wildcard {
+                    Some(_) => vec!["*".to_owned()],
+                    None => t.actions.iter().map(|a| a.to_string()).collect(),
+                };
+                transitions += &transition(t, actions);
+            }
+        }
+        for t in &smd.global {
+            let actions = t
+                .actions
+                .iter()
+                .filter(|a| smd.takes_global(state, a))
+                .map(|a| a.to_string())
+                .collect::<Vec<_>>();
+            if !actions.is_empty() {
+                transitions += &transition(t, actions);
+            }
+        }
+
+        let submachine = match submachine {
+            Some(st) if st.done.is_empty() => {
+                format!(" sm:submachine=\"{}\"", st.submachine.as_ref().unwrap())
+            }
+            Some(st) => {
+                let done = st.done.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+                format!(
+                    " sm:submachine=\"{}\" sm:done=\"{}\"",
+                    st.submachine.as_ref().unwrap(),
+                    done.join(" ")
+                )
+            }
+            None => String::new(),
+        };
+        if transitions.is_empty() && submachine.is_empty() && smd.terminal.contains(state) {
+            scxml += &format!("  <final id=\"{}\"/>\n", state);
+        } else {
+            scxml += &format!(
+                "  <state id=\"{}\"{}>\n{}  </state>\n",
+                state, submachine, transitions
             );
         }
     }
+    scxml += "</scxml>\n";
+    scxml
+}
+
+/// JSON description of the machine for external tools. The layout is versioned by `schema`, and
+/// the hash is a hex string since it does not fit in a JSON number.
+fn introspection(smd: &StateMachineDefinition) -> String {
+    let list = |names: &mut dyn Iterator<Item = &Ident>| {
+        let names = names.map(|n| format!("\"{}\"", n)).collect::<Vec<_>>();
+        format!("[{}]", names.join(", "))
+    };
+
+    let states = smd.states();
+    let mut transitions = Vec::new();
+    for s in &states {
+        for a in smd.actions() {
+            let next = kind_successors(smd, s, a);
+            if !next.is_empty() {
+                let blend = match smd.blend(s, a) {
+                    Some(b) => format!(
+                        ", \"blend\": {{\"millis\": {}, \"easing\": \"{}\"}}",
+                        b.millis.base10_digits(),
+                        b.easing
+                    ),
+                    None => String::new(),
+                };
+                let requires = smd
+                    .roles()
+                    .into_iter()
+                    .filter(|role| smd.permits(s, a, role))
+                    .collect::<Vec<_>>();
+                let requires = if smd.permits_anyone(s, a) {
+                    String::new()
+                } else {
+                    format!(", \"requires\": {}", list(&mut requires.into_iter()))
+                };
+                transitions.push(format!(
+                    "    {{\"state\": \"{}\", \"action\": \"{}\", \"next\": {}{}{}}}",
+                    s,
+                    a,
+                    list(&mut next.into_iter()),
+                    blend,
+                    requires
+                ));
+            }
+        }
+    }
+    let initial = match &smd.initial {
+        Some(initial) => format!("\"{}\"", initial),
+        None => "null".to_owned(),
+    };
+
+    format!(
+        concat!(
+            "{{\n",
+            "  \"schema\": 1,\n",
+            "  \"name\": \"{}\",\n",
+            "  \"hash\": \"{:016x}\",\n",
+            "  \"initial\": {},\n",
+            "  \"terminal\": {},\n",
+            "  \"states\": {},\n",
+            "  \"actions\": {},\n",
+            "  \"deprecated\": {},\n",
+            "  \"transitions\": [\n{}\n  ]\n",
+            "}}\n"
+        ),
+        smd.state_wrapper,
+        machine_hash(smd),
+        initial,
+        list(&mut smd.terminal.iter()),
+        list(&mut states.into_iter()),
+        list(&mut smd.actions().into_iter()),
+        list(&mut smd.deprecated.iter().map(|d| &d.old)),
+        transitions.join(",\n"),
+    )
+}
+
+fn define_diagrams(smd: &StateMachineDefinition) -> proc_macro2::TokenStream {
+    let state_vis = &smd.state_vis;
+    let state_wrapper = &smd.state_wrapper;
+    let dot = dot(smd);
+    let plantuml = plantuml(smd);
+    let introspection = introspection(smd);
+    // Returns an owned document, which needs an allocator.
+    let scxml = if cfg!(feature = "std") {
+        let scxml = scxml(smd);
+        quote! {
+            /// W3C SCXML document of the machine, for modeling and verification tools.
+            #state_vis fn to_scxml() -> ::std::string::String {
+                #scxml.to_owned()
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    quote! {
+        impl #state_wrapper {
+            /// Graphviz DOT rendering of the transition graph.
+            #state_vis const fn dot() -> &'static str {
+                #dot
+            }
+
+            /// PlantUML state diagram of the transition graph.
+            #state_vis const fn plantuml() -> &'static str {
+                #plantuml
+            }
+
+            #scxml
+
+            /// JSON description of the states, actions and transitions, for design and
+            /// visualization tools.
+            #state_vis const fn introspection() -> &'static str {
+                #introspection
+            }
+        }
+    }
+}
+
+fn define_tables(smd: &StateMachineDefinition) -> proc_macro2::TokenStream {
+    let state_vis = &smd.state_vis;
+    let state_wrapper = &smd.state_wrapper;
+    let action_wrapper = &smd.action_wrapper;
+    let states = smd.states();
+    let actions = smd.actions();
+
+    let mut transitions_acc = quote! {};
+    let mut allowed_acc = quote! {};
+    let mut expected_acc = quote! {};
+    for s in &states {
+        let mut expected = quote! {};
+        for (i, a) in actions.iter().enumerate() {
+            if smd.is_deprecated(a) {
+                continue;
+            }
+            let bit = quote! { expected |= 1 << #i; };
+            expected = match acceptance(smd, s, a) {
+                None => continue,
+                Some(condition) if condition.to_string() == "true" => quote! { #expected #bit },
+                Some(condition) => quote! { #expected if #condition { #bit } },
+            };
+        }
+        expected_acc = quote! {
+            #expected_acc
+            #state_wrapper::#s(_) => { #expected }
+        };
+
+        let mut allowed = Vec::new();
+        // Like `edges`, which deprecated actions only reach through their replacement.
+        for a in actions.iter().filter(|a| !smd.is_deprecated(a)) {
+            let next = kind_successors(smd, s, a);
+            if !next.is_empty() {
+                let (s, a) = (s.to_string(), a.to_string());
+                let next = next.iter().map(|n| n.to_string());
+                transitions_acc = quote! { #transitions_acc (#s, #a, &[#(#next),*]), };
+                allowed.push(a);
+            }
+        }
+        allowed_acc = quote! {
+            #allowed_acc
+            #state_wrapper::#s(_) => &[#(#allowed),*],
+        };
+    }
+    let roles = define_roles(smd);
+    let states = states.iter().map(|s| s.to_string());
+    let actions = actions.iter().map(|a| a.to_string());
+
+    quote! {
+        #roles
+
+        impl #state_wrapper {
+            /// Every state, by name.
+            #state_vis const STATES: &'static [&'static str] = &[#(#states),*];
+            /// Every action, by name.
+            #state_vis const ACTIONS: &'static [&'static str] = &[#(#actions),*];
+            /// `(state, action, next states)` for every action a state accepts, deprecated ones aside.
+            #state_vis const TRANSITIONS: &'static [(&'static str, &'static str, &'static [&'static str])] =
+                &[#transitions_acc];
+
+            /// Names of the actions the current state accepts, deprecated ones aside.
+            #state_vis fn allowed_actions(&self) -> &'static [&'static str] {
+                match self {
+                    #allowed_acc
+                }
+            }
+
+            /// Names of the actions other than `action` that the current state would take, with
+            /// the experimental transitions and the `enabled` optional ones.
+            #[allow(unused_variables)]
+            fn expected_actions(&self, action: &#action_wrapper, experimental: bool, enabled: u64) -> ::state_machine::ExpectedActions {
+                let mut expected = 0u128;
+                match self {
+                    #expected_acc
+                }
+                expected &= !(1 << action.kind() as u32);
+                ::state_machine::ExpectedActions::new(#state_wrapper::ACTIONS, expected)
+            }
+        }
+    }
+}
+
+/// `{State}Role`, naming the roles of the `requires` annotations, and the role-filtered
+/// `allowed_actions_for`.
+fn define_roles(smd: &StateMachineDefinition) -> proc_macro2::TokenStream {
+    let roles = smd.roles();
+    if roles.is_empty() {
+        return quote! {};
+    }
+    let state_vis = &smd.state_vis;
+    let state_wrapper = &smd.state_wrapper;
+    let role_enum = format_ident!("{}Role", state_wrapper);
+
+    let mut acc = quote! {};
+    for s in smd.states() {
+        for role in &roles {
+            let allowed = smd
+                .actions()
+                .into_iter()
+                .filter(|a| !smd.is_deprecated(a))
+                .filter(|a| !kind_successors(smd, s, a).is_empty() && smd.permits(s, a, role))
+                .map(|a| a.to_string());
+            acc = quote! {
+                #acc
+                (#state_wrapper::#s(_), #role_enum::#role) => &[#(#allowed),*],
+            };
+        }
+    }
+
+    quote! {
+        /// Roles the transitions may require of their caller.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        #state_vis enum #role_enum {
+            #(#roles,)*
+        }
+
+        impl #state_wrapper {
+            /// Same as `allowed_actions`, leaving out those whose transition requires roles other
+            /// than `role`.
+            #state_vis fn allowed_actions_for(&self, role: #role_enum) -> &'static [&'static str] {
+                match (self, role) {
+                    #acc
+                }
+            }
+        }
+    }
+}
+
+fn define_blends(smd: &StateMachineDefinition) -> proc_macro2::TokenStream {
+    let state_vis = &smd.state_vis;
+    let state_wrapper = &smd.state_wrapper;
+    let action_wrapper = &smd.action_wrapper;
+    let state_kind = format_ident!("{}Kind", state_wrapper);
+    let kind = format_ident!("{}Kind", action_wrapper);
+
+    let mut acc = quote! {};
+    for s in smd.states() {
+        for a in smd.actions() {
+            if let Some(Blend { millis, easing }) = smd.blend(s, a) {
+                acc = quote! {
+                    #acc
+                    (#state_kind::#s, #kind::#a) => Some(::state_machine::blend::Blend {
+                        duration: ::core::time::Duration::from_millis(#millis),
+                        easing: ::state_machine::blend::Easing::#easing,
+                    }),
+                };
+            }
+        }
+    }
+    if acc.is_empty() {
+        return quote! {};
+    }
+
+    quote! {
+        impl #state_wrapper {
+            /// Blend of the transition the current state takes for `action`, if it declares one.
+            #state_vis fn blend(&self, action: &#action_wrapper) -> Option<::state_machine::blend::Blend> {
+                #[allow(deprecated)]
+                match (self.kind(), action.kind()) {
+                    #acc
+                    _ => None,
+                }
+            }
+        }
+    }
+}
+
+fn define_validations(smd: &StateMachineDefinition) -> proc_macro2::TokenStream {
+    let action_vis = &smd.action_vis;
+
+    let mut acc = quote! {};
+    for v in &smd.validations {
+        let action = &v.action;
+        let payload = &v.payload;
+        let pattern = &v.pattern;
+        let action_as_str = action.to_string();
+        acc = quote! {
+            #acc
+
+            impl #action {
+                #action_vis fn new(payload: #payload) -> Result<#action, ::state_machine::InvalidAction> {
+                    if matches!(payload, #pattern) {
+                        Ok(#action(payload))
+                    } else {
+                        Err(::state_machine::InvalidAction { action: #action_as_str })
+                    }
+                }
+            }
+        };
+    }
+    acc
+}
+
+/// `EndOfString` -> `END_OF_STRING`
+fn screaming_snake_case(ident: &Ident) -> Ident {
+    let mut name = String::new();
+    for (i, c) in ident.to_string().chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            name.push('_');
+        }
+        name.extend(c.to_uppercase());
+    }
+    Ident::new(&name, ident.span())
+}
+
+fn define_unit_actions(smd: &StateMachineDefinition) -> proc_macro2::TokenStream {
+    if smd.unit_actions.is_empty() {
+        return quote! {};
+    }
+    let action_vis = &smd.action_vis;
+    let action_wrapper = &smd.action_wrapper;
+    let actions = &smd.unit_actions;
+    let consts = actions.iter().map(screaming_snake_case);
+    let deprecations = actions.iter().map(|a| {
+        let d = smd.deprecated.iter().find(|d| &d.old == a)?;
+        let note = format!("use {} instead", d.new);
+        Some(quote! { #[deprecated(note = #note)] })
+    });
+
+    quote! {
+        impl #action_wrapper {
+            #(
+                #deprecations
+                #[allow(deprecated)]
+                #action_vis const #consts: #action_wrapper = #action_wrapper::#actions(#actions);
+            )*
+        }
+    }
+}
+
+fn define_hooks(smd: &StateMachineDefinition) -> proc_macro2::TokenStream {
+    if !smd.hooks {
+        return quote! {};
+    }
+    let state_wrapper = &smd.state_wrapper;
+
+    let mut enter_acc = quote! {};
+    let mut exit_acc = quote! {};
+    for s in smd.states() {
+        let state = if smd.is_boxed(s) {
+            quote! { &mut **s }
+        } else {
+            quote! { s }
+        };
+        enter_acc = quote! {
+            #enter_acc
+            #state_wrapper::#s(s) => ::state_machine::StateHooks::on_enter(#state),
+        };
+        exit_acc = quote! {
+            #exit_acc
+            #state_wrapper::#s(s) => ::state_machine::StateHooks::on_exit(#state),
+        };
+    }
+
+    quote! {
+        impl ::state_machine::StateHooks for #state_wrapper {
+            fn on_enter(&mut self) {
+                match self {
+                    #enter_acc
+                }
+            }
+
+            fn on_exit(&mut self) {
+                match self {
+                    #exit_acc
+                }
+            }
+        }
+    }
+}
+
+fn define_terminal(smd: &StateMachineDefinition) -> proc_macro2::TokenStream {
+    let state_vis = &smd.state_vis;
+    let state_wrapper = &smd.state_wrapper;
+
+    let terminal = &smd.terminal;
+    let is_finished = quote! {
+        /// Whether the machine reached one of its terminal states.
+        #state_vis fn is_finished(&self) -> bool {
+            false #(|| matches!(self, #state_wrapper::#terminal(_)))*
+        }
+    };
+
+    let idle = &smd.idle;
+    let is_idle = quote! {
+        /// Whether the machine rests in one of its `idle` states.
+        #state_vis fn is_idle(&self) -> bool {
+            false #(|| matches!(self, #state_wrapper::#idle(_)))*
+        }
+    };
+
+    // Only a single terminal state has a type to be extracted as.
+    let into_terminal = match terminal.as_slice() {
+        [terminal] => {
+            let unboxed = if smd.is_boxed(terminal) {
+                quote! { *state }
+            } else {
+                quote! { state }
+            };
+            quote! {
+                /// Extracts the terminal state, or gives the machine back if it is not finished.
+                #state_vis fn into_terminal(self) -> Result<#terminal, #state_wrapper> {
+                    match self {
+                        #state_wrapper::#terminal(state) => Ok(#unboxed),
+                        other => Err(other),
+                    }
+                }
+            }
+        }
+        _ => quote! {},
+    };
+
+    quote! {
+        impl #state_wrapper {
+            #is_finished
+            #is_idle
+            #into_terminal
+        }
+    }
+}
+
+fn define_constructor(smd: &StateMachineDefinition) -> proc_macro2::TokenStream {
+    let state_vis = &smd.state_vis;
+    let state_wrapper = &smd.state_wrapper;
+    let Some(initial) = &smd.initial else {
+        return quote! {};
+    };
+
+    let body = if smd.hooks {
+        quote! {
+            let mut state: #state_wrapper = #initial::default().into();
+            ::state_machine::StateHooks::on_enter(&mut state);
+            state
+        }
+    } else {
+        quote! { #initial::default().into() }
+    };
+
+    // The `for<'a>` turns the bounds into ones rustc only checks on use, so that the machine
+    // still compiles when the initial state is not `Default`.
+    quote! {
+        impl #state_wrapper {
+            #state_vis fn new() -> #state_wrapper
+            where
+                for<'a> #initial: Default,
+            {
+                #body
+            }
+        }
+
+        impl Default for #state_wrapper
+        where
+            for<'a> #initial: Default,
+        {
+            fn default() -> #state_wrapper {
+                #state_wrapper::new()
+            }
+        }
+    }
+}
+
+/// Reports the warnings through the only channel a proc macro has on stable: the use of a
+/// deprecated item, spanned on the offending state.
+fn define_warnings(smd: &StateMachineDefinition) -> proc_macro2::TokenStream {
+    if smd.strict {
+        return quote! {};
+    }
+
+    let mut acc = quote! {};
+    for (state, message) in smd.warnings() {
+        acc = quote! {
+            #acc
+            const _: () = {
+                #[deprecated(note = #message)]
+                #[allow(non_upper_case_globals)]
+                const #state: () = ();
+                #state
+            };
+        };
+    }
+    acc
+}
+
+fn define_size_assertion(smd: &StateMachineDefinition) -> proc_macro2::TokenStream {
+    let state_wrapper = &smd.state_wrapper;
+    let Some(max_size) = &smd.max_size else {
+        return quote! {};
+    };
+
+    let message = format!(
+        "{} is larger than its max_size of {} bytes",
+        state_wrapper, max_size
+    );
+    quote! {
+        const _: () = assert!(::core::mem::size_of::<#state_wrapper>() <= #max_size, #message);
+    }
+}
+
+/// Condition of `t` along with its rank among the conditional transitions tried before it for
+/// the same action, `earlier`, as they are tried in order.
+fn guard_rank<'a>(t: &Transition, earlier: impl Iterator<Item = &'a Transition>) -> String {
+    if !t.is_conditional() {
+        return String::new();
+    }
+    let rank = earlier.filter(|o| o.is_conditional()).count();
+    format!("#{}{}", rank, t.canonical_condition())
+}
+
+/// FNV-1a over a canonical rendering of the machine: sorted states, actions and transitions.
+/// Only depends on the definition itself, not on the order it is written in.
+fn machine_hash(smd: &StateMachineDefinition) -> u64 {
+    let mut states = std::collections::BTreeSet::new();
+    let mut actions = std::collections::BTreeSet::new();
+    let mut transitions = std::collections::BTreeSet::new();
+    for st in &smd.state_transitions {
+        states.insert(st.state.to_string());
+        if let Some(child) = &st.submachine {
+            transitions.insert(format!("{}.submachine>{}", st.state, child));
+            let mut done = st.done.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+            done.sort();
+            done.dedup();
+            for next_s in &done {
+                states.insert(next_s.clone());
+            }
+            if !done.is_empty() {
+                transitions.insert(format!("{}.done>{}", st.state, done.join(",")));
+            }
+        }
+        for a in &st.deferred {
+            actions.insert(a.to_string());
+            transitions.insert(format!("{}.{}>defer", st.state, a));
+        }
+        for a in &st.ignored {
+            actions.insert(a.to_string());
+            transitions.insert(format!("{}.{}>ignore", st.state, a));
+        }
+        for a in &st.rejected {
+            actions.insert(a.to_string());
+            transitions.insert(format!("{}.{}>reject", st.state, a));
+        }
+        for a in &st.child_actions {
+            actions.insert(a.to_string());
+            transitions.insert(format!(
+                "{}.{}>{}",
+                st.state,
+                a,
+                st.submachine.as_ref().unwrap()
+            ));
+        }
+        for t in &st.transitions {
+            let mut next_states = t
+                .next_states
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>();
+            next_states.sort();
+            next_states.dedup();
+            for next_s in &next_states {
+                states.insert(next_s.clone());
+            }
+            if t.internal {
+                next_states.push("@internal".to_owned());
+            }
+
+            let earlier = st.transitions.iter().take_while(|o| !std::ptr::eq(*o, t));
+            if t.wildcard.is_some() {
+                let guard = guard_rank(t, earlier.clone().filter(|o| o.wildcard.is_some()));
+                transitions.insert(format!("{}._{}>{}", st.state, guard, next_states.join("|")));
+            }
+            for a in &t.actions {
+                actions.insert(a.to_string());
+                let guard = guard_rank(t, earlier.clone().filter(|o| o.actions.contains(a)));
+                transitions.insert(format!(
+                    "{}.{}{}>{}",
+                    st.state,
+                    a,
+                    guard,
+                    next_states.join("|")
+                ));
+            }
+        }
+    }
 
-    let wrappers = define_wrappers(&smd);
-    let fsm_impl = define_loop(&smd);
+    for t in &smd.global {
+        let mut next_states = t
+            .next_states
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>();
+        next_states.sort();
+        next_states.dedup();
+        for next_s in &next_states {
+            states.insert(next_s.clone());
+        }
+        if t.internal {
+            next_states.push("@internal".to_owned());
+        }
+
+        let earlier = smd.global.iter().take_while(|o| !std::ptr::eq(*o, t));
+        for a in &t.actions {
+            actions.insert(a.to_string());
+            let guard = guard_rank(t, earlier.clone().filter(|o| o.actions.contains(a)));
+            transitions.insert(format!("*.{}{}>{}", a, guard, next_states.join("|")));
+        }
+    }
+
+    for d in &smd.deprecated {
+        actions.insert(d.old.to_string());
+        transitions.insert(format!("deprecated.{}>{}", d.old, d.new));
+    }
+
+    let canonical = format!(
+        "states:{};actions:{};transitions:{}",
+        states.into_iter().collect::<Vec<_>>().join(","),
+        actions.into_iter().collect::<Vec<_>>().join(","),
+        transitions.into_iter().collect::<Vec<_>>().join(",")
+    );
+    canonical.bytes().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Value recorded by `frozen`, tying `version` to the machine `hash`.
+fn frozen_pin(version: u64, hash: u64) -> u64 {
+    let canonical = format!("version:{};hash:{:016x}", version, hash);
+    canonical.bytes().fold(0xcbf29ce484222325, |pin, b| {
+        (pin ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn define_hash(smd: &StateMachineDefinition) -> proc_macro2::TokenStream {
+    let state_vis = &smd.state_vis;
+    let state_wrapper = &smd.state_wrapper;
+    let hash = machine_hash(smd);
+    let version = smd.frozen.as_ref().map(|frozen| {
+        let version = &frozen.version;
+        quote! {
+            /// Version of the frozen definition.
+            #state_vis const VERSION: u64 = #version;
+        }
+    });
+
+    let snapshot = if cfg!(feature = "serde") {
+        quote! {
+            #state_vis fn snapshot(&self) -> ::state_machine::snapshot::Snapshot<&#state_wrapper> {
+                ::state_machine::snapshot::Snapshot {
+                    machine_hash: #state_wrapper::MACHINE_HASH,
+                    state: self,
+                }
+            }
+
+            /// Gives back the machine saved in `snapshot`, unless it was taken from another
+            /// definition of the machine.
+            #state_vis fn restore(
+                snapshot: ::state_machine::snapshot::Snapshot<#state_wrapper>,
+            ) -> Result<#state_wrapper, ::state_machine::snapshot::IncompatibleSnapshot> {
+                if snapshot.machine_hash == #state_wrapper::MACHINE_HASH {
+                    Ok(snapshot.state)
+                } else {
+                    Err(::state_machine::snapshot::IncompatibleSnapshot {
+                        expected_hash: #state_wrapper::MACHINE_HASH,
+                        found_hash: snapshot.machine_hash,
+                    })
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let persistent = if cfg!(feature = "persist") {
+        quote! {
+            impl ::state_machine::persist::Persistent for #state_wrapper {
+                const MACHINE_HASH: u64 = #state_wrapper::MACHINE_HASH;
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    quote! {
+        impl #state_wrapper {
+            /// Hash of the transition table, to check two builds agree on the machine definition.
+            #state_vis const MACHINE_HASH: u64 = #hash;
+
+            #version
+
+            #snapshot
+        }
+
+        #persistent
+    }
+}
+
+/// Either the DSL, or `mermaid "..."` / `dot "..."` followed by the wrappers: a diagram read as
+/// the equivalent items.
+struct MachineSource(StateMachineDefinition);
+
+impl Parse for MachineSource {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        if !((input.peek(kw::mermaid) || input.peek(kw::dot)) && input.peek2(syn::LitStr)) {
+            return input.parse().map(MachineSource);
+        }
+
+        let format = input.parse::<Ident>()?;
+        let source = input.parse::<syn::LitStr>()?;
+        input.parse::<Token![,]>()?;
+        let mut wrappers = input
+            .parse::<proc_macro2::TokenStream>()?
+            .into_iter()
+            .collect::<Vec<_>>();
+        if matches!(wrappers.last(), Some(proc_macro2::TokenTree::Punct(p)) if p.as_char() == ',') {
+            wrappers.pop();
+        }
+
+        let document = if format == "mermaid" {
+            diagram::mermaid(&source.value())
+        } else {
+            diagram::dot(&source.value())
+        };
+        let items = document
+            .map_err(|e| syn::Error::new(source.span(), e))?
+            .to_dsl()
+            .parse::<proc_macro2::TokenStream>()
+            .map_err(|e| syn::Error::new(source.span(), e))?;
+        syn::parse2(quote! { #(#wrappers)*, #items }).map(MachineSource)
+    }
+}
+
+#[proc_macro]
+pub fn state_machine(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let MachineSource(smd) = parse_macro_input!(item as MachineSource);
+    expand(&smd).into()
+}
+
+fn expand(smd: &StateMachineDefinition) -> proc_macro2::TokenStream {
+    if let Err(e) = smd.validate() {
+        return e.to_compile_error();
+    }
+
+    let wrappers = define_wrappers(smd);
+    let fsm_impl = define_loop(smd);
+    let flagged = define_flagged(smd);
+    let error = define_error(smd);
+    let hash = define_hash(smd);
+    let warnings = define_warnings(smd);
+    let size_assertion = define_size_assertion(smd);
+    let constructor = define_constructor(smd);
+    let terminal = define_terminal(smd);
+    let hooks = define_hooks(smd);
+    let debug_summary = define_debug_summary(smd);
+    let diagrams = define_diagrams(smd);
+    let tables = define_tables(smd);
+    let validations = define_validations(smd);
+    let blends = define_blends(smd);
+    let unit_actions = define_unit_actions(smd);
+    let parsing = define_parsing(smd);
+    let step = define_step(smd);
+    let deferred = define_deferred(smd);
+    let automaton = define_automaton(smd);
+    let defmt = define_defmt(smd);
 
     quote! {
         #wrappers
         #fsm_impl
+        #flagged
+        #error
+        #hash
+        #warnings
+        #size_assertion
+        #constructor
+        #terminal
+        #hooks
+        #debug_summary
+        #diagrams
+        #tables
+        #validations
+        #blends
+        #unit_actions
+        #parsing
+        #step
+        #deferred
+        #automaton
+        #defmt
+    }
+}
+
+/// With `defmt`, the wrappers and the kinds are logged as the name of the state or action they
+/// hold, so that neither the states nor the actions need to implement `Format`.
+fn define_defmt(smd: &StateMachineDefinition) -> proc_macro2::TokenStream {
+    if !cfg!(feature = "defmt") {
+        return quote! {};
+    }
+
+    let state_wrapper = &smd.state_wrapper;
+    let action_wrapper = &smd.action_wrapper;
+    let state_kind = format_ident!("{}Kind", state_wrapper);
+    let action_kind = format_ident!("{}Kind", action_wrapper);
+    let states = smd.states();
+    let state_names = states.iter().map(|s| s.to_string());
+    let actions = smd.actions();
+    let action_names = actions.iter().map(|a| a.to_string());
+
+    quote! {
+        impl ::state_machine::defmt::Format for #state_wrapper {
+            fn format(&self, f: ::state_machine::defmt::Formatter<'_>) {
+                ::state_machine::defmt::Format::format(#state_wrapper::name(self), f)
+            }
+        }
+
+        impl ::state_machine::defmt::Format for #action_wrapper {
+            fn format(&self, f: ::state_machine::defmt::Formatter<'_>) {
+                ::state_machine::defmt::Format::format(#action_wrapper::name(self), f)
+            }
+        }
+
+        impl ::state_machine::defmt::Format for #state_kind {
+            fn format(&self, f: ::state_machine::defmt::Formatter<'_>) {
+                let name = match self {
+                    #(#state_kind::#states => #state_names,)*
+                };
+                ::state_machine::defmt::Format::format(name, f)
+            }
+        }
+
+        impl ::state_machine::defmt::Format for #action_kind {
+            fn format(&self, f: ::state_machine::defmt::Formatter<'_>) {
+                #[allow(deprecated)]
+                let name = match self {
+                    #(#action_kind::#actions => #action_names,)*
+                };
+                ::state_machine::defmt::Format::format(name, f)
+            }
+        }
+    }
+}
+
+/// `[vis] State, [vis] Action, "path.scxml"`, the path being relative to the crate root. Without
+/// the wrappers, they are named after the `name` of the `<scxml>` element.
+struct ScxmlImport {
+    wrappers: Option<proc_macro2::TokenStream>,
+    path: syn::LitStr,
+}
+
+impl Parse for ScxmlImport {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let mut wrappers = proc_macro2::TokenStream::new();
+        while !input.peek(syn::LitStr) {
+            let tt = input.parse::<proc_macro2::TokenTree>()?;
+            wrappers.extend([tt]);
+        }
+        let path = input.parse()?;
+        input.parse::<Option<Token![,]>>()?;
+        Ok(ScxmlImport {
+            wrappers: (!wrappers.is_empty()).then_some(wrappers),
+            path,
+        })
+    }
+}
+
+/// `Name<P1, P2, ...> { items }`: a machine body where the parameters stand for state or action
+/// names, given by `instantiate_machine!`.
+struct MachineTemplate {
+    name: Ident,
+    params: Vec<Ident>,
+    body: proc_macro2::TokenStream,
+}
+
+impl Parse for MachineTemplate {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let name = input.parse::<Ident>()?;
+        input.parse::<Token![<]>()?;
+        let mut params = Vec::new();
+        while !input.peek(Token![>]) {
+            params.push(input.parse::<Ident>()?);
+            if input.peek(Token![>]) {
+                break;
+            }
+            input.parse::<Token![,]>()?;
+        }
+        input.parse::<Token![>]>()?;
+        let body_def;
+        braced!(body_def in input);
+        let body = body_def.parse()?;
+        Ok(MachineTemplate { name, params, body })
+    }
+}
+
+/// Replaces every parameter in `tokens` by the `macro_rules` variable of the same name.
+fn substitute_params(
+    tokens: proc_macro2::TokenStream,
+    params: &[Ident],
+) -> proc_macro2::TokenStream {
+    tokens
+        .into_iter()
+        .map(|tt| match tt {
+            proc_macro2::TokenTree::Ident(ident) if params.contains(&ident) => quote! { $#ident },
+            proc_macro2::TokenTree::Group(group) => {
+                let mut substituted = proc_macro2::Group::new(
+                    group.delimiter(),
+                    substitute_params(group.stream(), params),
+                );
+                substituted.set_span(group.span());
+                quote! { #substituted }
+            }
+            tt => quote! { #tt },
+        })
+        .collect()
+}
 
+#[proc_macro]
+pub fn state_machine_template(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let template = parse_macro_input!(item as MachineTemplate);
+    let name = &template.name;
+    let params = &template.params;
+    let body = substitute_params(template.body, params);
+
+    quote! {
+        macro_rules! #name {
+            (#(#params = $#params:ident),* $(,)?; $($wrappers:tt)*) => {
+                ::state_machine::state_machine! {
+                    $($wrappers)*,
+                    #body
+                }
+            };
+        }
+    }
+    .into()
+}
+
+/// `[attrs] name { items }`: items spliced into the machines invoking `name!`.
+struct MachineFragment {
+    attrs: Vec<Attribute>,
+    name: Ident,
+    items: proc_macro2::TokenStream,
+}
+
+impl Parse for MachineFragment {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let name = input.parse::<Ident>()?;
+        let items_def;
+        braced!(items_def in input);
+        let items = items_def.parse()?;
+        Ok(MachineFragment { attrs, name, items })
+    }
+}
+
+#[proc_macro]
+pub fn state_machine_fragment(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let fragment = parse_macro_input!(item as MachineFragment);
+    let attrs = &fragment.attrs;
+    let name = &fragment.name;
+    let items = &fragment.items;
+
+    // `name! { => other; ... }` hands the machine over to another fragment instead of
+    // `state_machine!`, so fragments can be chained.
+    quote! {
+        #(#attrs)*
+        macro_rules! #name {
+            (=> $next:ident; $($machine:tt)*) => {
+                $next! { $($machine)*, #items }
+            };
+            ($($machine:tt)*) => {
+                ::state_machine::state_machine! { $($machine)*, #items }
+            };
+        }
+    }
+    .into()
+}
+
+/// Generates a machine from a W3C SCXML document, as `state_machine!` would from the same states
+/// and transitions.
+#[proc_macro]
+pub fn state_machine_from_scxml(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let import = parse_macro_input!(item as ScxmlImport);
+    let root = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+    let path = std::path::Path::new(&root).join(import.path.value());
+    let error = |message: String| syn::Error::new(import.path.span(), message).to_compile_error();
+
+    let source = match std::fs::read_to_string(&path) {
+        Ok(source) => source,
+        Err(e) => return error(format!("cannot read {}: {}", path.display(), e)).into(),
+    };
+    let document = match scxml::parse(&source) {
+        Ok(document) => document,
+        Err(e) => return error(format!("{}: {}", path.display(), e)).into(),
+    };
+    let wrappers = match (import.wrappers, &document.name) {
+        (Some(wrappers), _) => wrappers,
+        (None, Some(name)) => {
+            let state_wrapper = format_ident!("{}", name, span = import.path.span());
+            let action_wrapper = format_ident!("{}Action", name, span = import.path.span());
+            quote! { #state_wrapper, #action_wrapper, }
+        }
+        (None, None) => {
+            return error("the <scxml> element has no name, give the wrappers".to_owned()).into()
+        }
+    };
+    let items = match document.to_dsl().parse::<proc_macro2::TokenStream>() {
+        Ok(items) => items,
+        Err(e) => return error(e.to_string()).into(),
+    };
+    let smd = match syn::parse2::<StateMachineDefinition>(quote! { #wrappers #items }) {
+        Ok(smd) => smd,
+        Err(e) => return e.to_compile_error().into(),
+    };
+
+    let machine = expand(&smd);
+    let path = path.display().to_string();
+    quote! {
+        #machine
+        // Rebuilds the machine whenever the document changes.
+        const _: &str = ::core::include_str!(#path);
     }
     .into()
 }
@@ -0,0 +1,599 @@
+//! Reader for the subset of W3C SCXML that maps onto the DSL: flat `<state>` and `<final>`
+//! elements holding `<transition>`s. Executable content (`<onentry>`, `<script>`, ...) and the
+//! data model are skipped, that behavior lives in the `State` impls.
+//!
+//! The DSL items with no SCXML counterpart are read from attributes in the [`NAMESPACE`], whatever
+//! prefix the document binds it to, written `sm` here: `sm:submachine="Child"` on a `<state>`,
+//! with `sm:done` listing the states it may go to once the child finishes, and `sm:kind` on a
+//! targetless `<transition>` whose events are ignored, rejected, deferred or only there for the
+//! submachine.
+
+/// Namespace of the attributes written by the generated `to_scxml`.
+pub const NAMESPACE: &str = "urn:state_machine";
+
+/// Values of `sm:kind`, named after the DSL keywords.
+const KINDS: [&str; 4] = ["submachine", "ignore", "reject", "defer"];
+
+pub struct Document {
+    /// `name` attribute of the root element.
+    pub name: Option<String>,
+    pub initial: Option<String>,
+    pub states: Vec<ScxmlState>,
+}
+
+pub struct ScxmlState {
+    pub id: String,
+    pub is_final: bool,
+    /// `sm:submachine`.
+    pub submachine: Option<String>,
+    /// `sm:done`.
+    pub done: Vec<String>,
+    pub transitions: Vec<ScxmlTransition>,
+}
+
+pub struct ScxmlTransition {
+    pub events: Vec<String>,
+    /// Empty for a targetless transition.
+    pub targets: Vec<String>,
+    /// `sm:kind`, one of [`KINDS`].
+    pub kind: Option<String>,
+}
+
+struct Tag {
+    name: String,
+    attributes: Vec<(String, String)>,
+    closing: bool,
+    self_closing: bool,
+}
+
+impl Tag {
+    /// Unprefixed attribute, in no namespace.
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Namespace prefixes this tag binds, with their URI.
+    fn bindings(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.attributes
+            .iter()
+            .filter_map(|(n, v)| Some((n.strip_prefix("xmlns:")?, v.as_str())))
+    }
+
+    /// Attribute `name` of [`NAMESPACE`], `bindings` holding the prefixes in scope, innermost last.
+    fn sm_attribute(&self, bindings: &[(String, String)], name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| {
+                n.split_once(':').is_some_and(|(prefix, local)| {
+                    local == name
+                        && bindings
+                            .iter()
+                            .rev()
+                            .find(|(p, _)| p == prefix)
+                            .is_some_and(|(_, uri)| uri == NAMESPACE)
+                })
+            })
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Replaces the predefined entities and the character references in an attribute value.
+fn unescape(value: &str) -> Result<String, String> {
+    let mut unescaped = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+        let end = rest
+            .find(';')
+            .ok_or_else(|| format!("unterminated entity in `{}`", value))?;
+        let entity = &rest[..end];
+        let c = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => match entity.strip_prefix('#') {
+                Some(code) => match code.strip_prefix('x') {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => code.parse().ok(),
+                }
+                .and_then(char::from_u32),
+                None => None,
+            },
+        };
+        unescaped.push(c.ok_or_else(|| format!("unknown entity `&{};`", entity))?);
+        rest = &rest[end + 1..];
+    }
+    unescaped.push_str(rest);
+    Ok(unescaped)
+}
+
+/// Splits `source` into its tags, dropping text, comments, processing instructions, doctype and
+/// CDATA sections.
+fn tags(source: &str) -> Result<Vec<Tag>, String> {
+    let mut tags = Vec::new();
+    let mut rest = source;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        let skipped = [
+            ("<!--", "-->"),
+            ("<![CDATA[", "]]>"),
+            ("<?", "?>"),
+            ("<!", ">"),
+        ]
+        .iter()
+        .find(|(open, _)| rest.starts_with(open));
+        if let Some((open, close)) = skipped {
+            let end = rest
+                .find(close)
+                .ok_or_else(|| format!("unterminated `{}`", open))?;
+            rest = &rest[end + close.len()..];
+            continue;
+        }
+
+        let end = rest.find('>').ok_or("unterminated tag")?;
+        let mut body = &rest[1..end];
+        rest = &rest[end + 1..];
+
+        let closing = body.starts_with('/');
+        if closing {
+            body = &body[1..];
+        }
+        let self_closing = body.ends_with('/');
+        if self_closing {
+            body = &body[..body.len() - 1];
+        }
+
+        let name_end = body.find(|c: char| c.is_whitespace()).unwrap_or(body.len());
+        // Namespace prefixes are ignored.
+        let name = body[..name_end].rsplit(':').next().unwrap_or_default();
+        let mut attributes = Vec::new();
+        let mut attrs = body[name_end..].trim_start();
+        while !attrs.is_empty() {
+            let eq = attrs
+                .find('=')
+                .ok_or_else(|| format!("malformed attributes in `<{}>`", name))?;
+            let key = attrs[..eq].trim();
+            let value = attrs[eq + 1..].trim_start();
+            let quote = value
+                .chars()
+                .next()
+                .filter(|&c| c == '"' || c == '\'')
+                .ok_or_else(|| format!("unquoted value for `{}` in `<{}>`", key, name))?;
+            let value = &value[1..];
+            let value_end = value
+                .find(quote)
+                .ok_or_else(|| format!("unterminated value for `{}` in `<{}>`", key, name))?;
+            attributes.push((key.to_owned(), unescape(&value[..value_end])?));
+            attrs = value[value_end + 1..].trim_start();
+        }
+
+        tags.push(Tag {
+            name: name.to_owned(),
+            attributes,
+            closing,
+            self_closing,
+        });
+    }
+    Ok(tags)
+}
+
+pub fn parse(source: &str) -> Result<Document, String> {
+    let mut document = Document {
+        name: None,
+        initial: None,
+        states: Vec::new(),
+    };
+    // Names of the currently open elements, with how many prefixes each one binds.
+    let mut open = Vec::<(String, usize)>::new();
+    // Namespace prefixes in scope and their URI, innermost last.
+    let mut bindings = Vec::<(String, String)>::new();
+    for tag in tags(source)? {
+        if tag.closing {
+            match open.pop() {
+                Some((name, bound)) if name == tag.name => {
+                    bindings.truncate(bindings.len() - bound);
+                }
+                _ => return Err(format!("unexpected `</{}>`", tag.name)),
+            }
+            continue;
+        }
+
+        let bound = bindings.len();
+        bindings.extend(
+            tag.bindings()
+                .map(|(p, uri)| (p.to_owned(), uri.to_owned())),
+        );
+        for (name, _) in &tag.attributes {
+            let Some((prefix, _)) = name.split_once(':') else {
+                continue;
+            };
+            if !["xml", "xmlns"].contains(&prefix) && !bindings.iter().any(|(p, _)| p == prefix) {
+                return Err(format!("unbound namespace prefix `{}`", prefix));
+            }
+        }
+        let bound = bindings.len() - bound;
+
+        let parent = open.last().map(|(name, _)| name.as_str());
+        match (tag.name.as_str(), parent) {
+            ("scxml", None) => {
+                document.name = tag.attribute("name").map(str::to_owned);
+                document.initial = tag.attribute("initial").map(str::to_owned);
+            }
+            (_, None) => return Err(format!("expected `<scxml>`, got `<{}>`", tag.name)),
+            ("state" | "final", Some("scxml")) => {
+                let id = tag
+                    .attribute("id")
+                    .ok_or_else(|| format!("`<{}>` without an `id`", tag.name))?;
+                document.states.push(ScxmlState {
+                    id: id.to_owned(),
+                    is_final: tag.name == "final",
+                    submachine: tag.sm_attribute(&bindings, "submachine").map(str::to_owned),
+                    done: tag
+                        .sm_attribute(&bindings, "done")
+                        .unwrap_or_default()
+                        .split_whitespace()
+                        .map(str::to_owned)
+                        .collect(),
+                    transitions: Vec::new(),
+                });
+            }
+            ("state" | "final" | "initial", Some("state")) => {
+                return Err("nested states are not supported".to_owned())
+            }
+            ("parallel" | "history", _) => {
+                return Err(format!("`<{}>` is not supported", tag.name))
+            }
+            ("transition", Some("state")) => {
+                let events = tag
+                    .attribute("event")
+                    .ok_or("eventless transitions are not supported")?;
+                if tag.attribute("cond").is_some() {
+                    return Err("conditional transitions are not supported".to_owned());
+                }
+                let state = document.states.last_mut().expect("open `<state>`");
+                let transition = ScxmlTransition {
+                    events: events.split_whitespace().map(str::to_owned).collect(),
+                    targets: tag
+                        .attribute("target")
+                        .unwrap_or_default()
+                        .split_whitespace()
+                        .map(str::to_owned)
+                        .collect(),
+                    kind: tag.sm_attribute(&bindings, "kind").map(str::to_owned),
+                };
+                if let Some(kind) = &transition.kind {
+                    if !KINDS.contains(&kind.as_str()) {
+                        return Err(format!("unknown `sm:kind` `{}`", kind));
+                    }
+                    if !transition.targets.is_empty() {
+                        return Err(format!("`sm:kind=\"{}\"` transition with a target", kind));
+                    }
+                    if transition.events.iter().any(|e| e == "*") {
+                        return Err(format!("`sm:kind=\"{}\"` transition on `*`", kind));
+                    }
+                    if kind == "submachine" && state.submachine.is_none() {
+                        return Err(format!("state {} has no `sm:submachine`", state.id));
+                    }
+                }
+                state.transitions.push(transition);
+            }
+            // Executable content and data model.
+            _ => {}
+        }
+
+        if tag.self_closing {
+            bindings.truncate(bindings.len() - bound);
+        } else {
+            open.push((tag.name, bound));
+        }
+    }
+
+    if let Some((name, _)) = open.last() {
+        return Err(format!("unclosed `<{}>`", name));
+    }
+    document.check()?;
+    Ok(document)
+}
+
+impl Document {
+    /// Checks there is a state, and that every name can be used in the DSL.
+    pub fn check(&self) -> Result<(), String> {
+        if self.states.is_empty() {
+            return Err("no state in the document".to_owned());
+        }
+
+        let names = self.states.iter().flat_map(|s| {
+            core::iter::once(&s.id)
+                .chain(&s.submachine)
+                .chain(&s.done)
+                .chain(
+                    s.transitions
+                        .iter()
+                        .flat_map(|t| t.events.iter().filter(|e| *e != "*").chain(&t.targets)),
+                )
+        });
+        for name in self.name.iter().chain(&self.initial).chain(names) {
+            if syn::parse_str::<syn::Ident>(name).is_err() {
+                return Err(format!("`{}` is not a valid Rust identifier", name));
+            }
+        }
+        Ok(())
+    }
+
+    /// The items of the equivalent `state_machine!` body, after the wrappers.
+    pub fn to_dsl(&self) -> String {
+        let mut dsl = String::new();
+        let initial = self.initial.as_ref().unwrap_or(&self.states[0].id);
+        dsl.push_str(&format!("initial {},\n", initial));
+        for s in self.states.iter().filter(|s| s.is_final) {
+            dsl.push_str(&format!("terminal {},\n", s.id));
+        }
+        for s in &self.states {
+            dsl.push_str(&format!("{} {{\n", s.id));
+            for kind in KINDS {
+                let events = s
+                    .transitions
+                    .iter()
+                    .filter(|t| t.kind.as_deref() == Some(kind))
+                    .flat_map(|t| &t.events)
+                    .map(String::as_str)
+                    .collect::<Vec<_>>();
+                match (kind, &s.submachine) {
+                    ("submachine", Some(child)) => {
+                        dsl.push_str(&format!("    submachine {}", child));
+                        if !events.is_empty() {
+                            dsl.push_str(&format!(": {}", events.join(" | ")));
+                        }
+                        if !s.done.is_empty() {
+                            dsl.push_str(&format!(" => {}", s.done.join(" | ")));
+                        }
+                        dsl.push_str(",\n");
+                    }
+                    _ if !events.is_empty() => {
+                        dsl.push_str(&format!("    {} {},\n", kind, events.join(" | ")));
+                    }
+                    _ => {}
+                }
+            }
+            for t in s.transitions.iter().filter(|t| t.kind.is_none()) {
+                let events = if t.events.iter().any(|e| e == "*") {
+                    "_".to_owned()
+                } else {
+                    t.events.join(" | ")
+                };
+                let targets = if t.targets.is_empty() {
+                    "@internal".to_owned()
+                } else {
+                    t.targets.join(" | ")
+                };
+                dsl.push_str(&format!("    {} => {},\n", events, targets));
+            }
+            dsl.push_str("},\n");
+        }
+        dsl
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOOR: &str = r#"<?xml version="1.0"?>
+<!-- A door, with its lock. -->
+<scxml xmlns="http://www.w3.org/2005/07/scxml" version="1.0" name="Door" initial="Closed">
+  <datamodel><data id="count" expr="0"/></datamodel>
+  <state id="Closed">
+    <onentry><script><![CDATA[ if (a < b) { count++; } ]]></script></onentry>
+    <transition event="Open" target="Opened"/>
+    <transition event="Lock Bolt" target='Locked'/>
+    <transition event="Knock"/>
+  </state>
+  <state id="Opened">
+    <transition event="Close" target="Closed"/>
+  </state>
+  <state id="Locked">
+    <transition event="*" target="Locked"/>
+  </state>
+  <final id="Gone"/>
+</scxml>"#;
+
+    #[test]
+    fn reads_states_and_transitions() {
+        let document = parse(DOOR).unwrap();
+        assert_eq!(document.name.as_deref(), Some("Door"));
+        assert_eq!(document.initial.as_deref(), Some("Closed"));
+        let ids = document
+            .states
+            .iter()
+            .map(|s| s.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, ["Closed", "Opened", "Locked", "Gone"]);
+        assert!(document.states[3].is_final);
+
+        let closed = &document.states[0].transitions;
+        assert_eq!(closed.len(), 3);
+        assert_eq!(closed[1].events, ["Lock", "Bolt"]);
+        assert_eq!(closed[1].targets, ["Locked"]);
+        assert!(closed[2].targets.is_empty());
+    }
+
+    #[test]
+    fn writes_the_dsl() {
+        assert_eq!(
+            parse(DOOR).unwrap().to_dsl(),
+            "initial Closed,\n\
+             terminal Gone,\n\
+             Closed {\n    Open => Opened,\n    Lock | Bolt => Locked,\n    Knock => @internal,\n},\n\
+             Opened {\n    Close => Closed,\n},\n\
+             Locked {\n    _ => Locked,\n},\n\
+             Gone {\n},\n"
+        );
+    }
+
+    #[test]
+    fn reads_back_the_exported_items() {
+        let definition = "Door, DoorAction,\
+            initial Closed,\
+            terminal Gone,\
+            Closed {\
+                submachine Lock: Turn | Pick => Opened,\
+                ignore Knock,\
+                reject Kick,\
+                defer Ring,\
+                Open => Opened,\
+                _ => Closed,\
+            },\
+            Opened { submachine Bell, Close => Closed | Gone },\
+            Gone {},";
+        let smd = syn::parse_str::<crate::StateMachineDefinition>(definition).unwrap();
+        let exported = crate::scxml(&smd);
+        let document = parse(&exported).unwrap();
+        assert_eq!(
+            document.to_dsl(),
+            "initial Closed,\n\
+             terminal Gone,\n\
+             Closed {\n    submachine Lock: Turn | Pick => Opened,\n    ignore Knock,\n    reject Kick,\n    \
+             defer Ring,\n    Open => Opened,\n    _ => Closed,\n},\n\
+             Opened {\n    submachine Bell,\n    Close => Closed | Gone,\n},\n\
+             Gone {\n},\n"
+        );
+
+        let items = format!("Door, DoorAction, {}", document.to_dsl());
+        let smd = syn::parse_str::<crate::StateMachineDefinition>(&items).unwrap();
+        assert_eq!(crate::scxml(&smd), exported);
+    }
+
+    #[test]
+    fn rejects_misused_markers() {
+        let cases = [
+            (
+                r#"<scxml xmlns:sm="urn:state_machine"><state id="A"><transition event="E" sm:kind="skip"/></state></scxml>"#,
+                "unknown `sm:kind` `skip`",
+            ),
+            (
+                r#"<scxml xmlns:sm="urn:state_machine"><state id="A"><transition event="E" target="A" sm:kind="ignore"/></state></scxml>"#,
+                "`sm:kind=\"ignore\"` transition with a target",
+            ),
+            (
+                r#"<scxml xmlns:sm="urn:state_machine"><state id="A"><transition event="*" sm:kind="reject"/></state></scxml>"#,
+                "`sm:kind=\"reject\"` transition on `*`",
+            ),
+            (
+                r#"<scxml xmlns:sm="urn:state_machine"><state id="A"><transition event="E" sm:kind="submachine"/></state></scxml>"#,
+                "state A has no `sm:submachine`",
+            ),
+        ];
+        for (source, error) in cases {
+            assert_eq!(parse(source).err().as_deref(), Some(error), "{}", source);
+        }
+    }
+
+    #[test]
+    fn resolves_markers_by_namespace() {
+        let document = parse(
+            r#"<scxml xmlns:x="urn:state_machine" xmlns:sm="urn:other">
+  <state id="A" x:submachine="Child" sm:done="B">
+    <transition event="E" x:kind="submachine"/>
+    <transition event="F" sm:kind="skip" target="B"/>
+  </state>
+  <state id="B" xmlns:sm="urn:state_machine" sm:submachine="Other"/>
+</scxml>"#,
+        )
+        .unwrap();
+        let a = &document.states[0];
+        assert_eq!(a.submachine.as_deref(), Some("Child"));
+        assert!(a.done.is_empty());
+        assert_eq!(a.transitions[0].kind.as_deref(), Some("submachine"));
+        assert_eq!(a.transitions[1].kind, None);
+        assert_eq!(document.states[1].submachine.as_deref(), Some("Other"));
+
+        assert_eq!(
+            parse(r#"<scxml><state id="A" sm:submachine="Child"/></scxml>"#)
+                .err()
+                .as_deref(),
+            Some("unbound namespace prefix `sm`")
+        );
+    }
+
+    #[test]
+    fn decodes_entities() {
+        let document = parse(
+            r#"<scxml name="D&#111;or"><state id="Cl&#x6f;sed">
+  <transition event="Open" target="Opened" expr="a &lt; b &amp;&amp; c &gt; &quot;d&apos;"/>
+</state><state id="Opened"/></scxml>"#,
+        )
+        .unwrap();
+        assert_eq!(document.name.as_deref(), Some("Door"));
+        assert_eq!(document.states[0].id, "Closed");
+        assert_eq!(
+            unescape("a &lt; b &amp;&amp; c &gt; &quot;d&apos;").unwrap(),
+            "a < b && c > \"d'"
+        );
+        assert_eq!(
+            parse(r#"<scxml><state id="A&nbsp;"/></scxml>"#)
+                .err()
+                .as_deref(),
+            Some("unknown entity `&nbsp;`")
+        );
+    }
+
+    #[test]
+    fn defaults_to_the_first_state() {
+        let document = parse(r#"<scxml><state id="A"/><state id="B"/></scxml>"#).unwrap();
+        assert!(document.to_dsl().starts_with("initial A,\n"));
+    }
+
+    #[test]
+    fn rejects_unsupported_elements() {
+        let cases = [
+            (
+                r#"<scxml><state id="A"><state id="B"/></state></scxml>"#,
+                "nested states are not supported",
+            ),
+            (
+                r#"<scxml><parallel id="A"/></scxml>"#,
+                "`<parallel>` is not supported",
+            ),
+            (
+                r#"<scxml><state id="A"><transition target="A"/></state></scxml>"#,
+                "eventless transitions are not supported",
+            ),
+            (
+                r#"<scxml><state id="A"><transition event="E" cond="x" target="A"/></state></scxml>"#,
+                "conditional transitions are not supported",
+            ),
+        ];
+        for (source, error) in cases {
+            assert_eq!(parse(source).err().as_deref(), Some(error), "{}", source);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_documents() {
+        let cases = [
+            ("<state id=\"A\"/>", "expected `<scxml>`, got `<state>`"),
+            ("<scxml><state id=\"A\">", "unclosed `<state>`"),
+            ("<scxml></state>", "unexpected `</state>`"),
+            ("<scxml><state/></scxml>", "`<state>` without an `id`"),
+            (
+                "<scxml><state id=A/></scxml>",
+                "unquoted value for `id` in `<state>`",
+            ),
+            ("<scxml><!-- </scxml>", "unterminated `<!--`"),
+            ("<scxml></scxml>", "no state in the document"),
+            (
+                "<scxml><state id=\"two words\"/></scxml>",
+                "`two words` is not a valid Rust identifier",
+            ),
+        ];
+        for (source, error) in cases {
+            assert_eq!(parse(source).err().as_deref(), Some(error), "{}", source);
+        }
+    }
+}
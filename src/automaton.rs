@@ -0,0 +1,299 @@
+use std::collections::{HashMap, VecDeque};
+
+/// Set of action sequences, as a deterministic automaton over the alphabet `A`: the sequences a
+/// machine accepts, as given by its generated `language`, or the ones a specification allows.
+///
+/// Languages combine with [`union`](Language::union), [`intersection`](Language::intersection),
+/// [`difference`](Language::difference) and [`complement`](Language::complement). Checking that a
+/// machine accepts nothing its specification does not allow then reads as
+/// `machine.difference(&spec).shortest_word() == None`.
+#[derive(Debug, Clone)]
+pub struct Language<A> {
+    alphabet: Vec<A>,
+    // Next state for each state and symbol index, `None` being the dead state accepting nothing.
+    // The initial state is 0.
+    transitions: Vec<Vec<Option<usize>>>,
+    accepting: Vec<bool>,
+}
+
+impl<A: Copy + Eq> Language<A> {
+    /// Determinizes the automaton with states `0..edges.len()`, `edges[s]` listing the symbols
+    /// accepted in `s` with the states each one may lead to. Symbols outside `alphabet` are
+    /// ignored.
+    pub fn from_nfa(
+        alphabet: Vec<A>,
+        initial: usize,
+        accepting: &[usize],
+        edges: Vec<Vec<(A, Vec<usize>)>>,
+    ) -> Self {
+        let mut start = vec![false; edges.len()];
+        start[initial] = true;
+        let mut language = Language {
+            transitions: Vec::new(),
+            accepting: Vec::new(),
+            alphabet,
+        };
+        let mut subsets = HashMap::new();
+        let mut pending = VecDeque::new();
+        subsets.insert(start.clone(), 0);
+        pending.push_back(start);
+        while let Some(subset) = pending.pop_front() {
+            language
+                .accepting
+                .push(accepting.iter().any(|&state| subset[state]));
+            let mut row = Vec::with_capacity(language.alphabet.len());
+            for symbol in &language.alphabet {
+                let mut next = vec![false; edges.len()];
+                for (state, _) in subset.iter().enumerate().filter(|(_, s)| **s) {
+                    for (_, successors) in edges[state].iter().filter(|(a, _)| a == symbol) {
+                        for &successor in successors {
+                            next[successor] = true;
+                        }
+                    }
+                }
+                if !next.contains(&true) {
+                    row.push(None);
+                    continue;
+                }
+                let count = subsets.len();
+                let index = *subsets.entry(next.clone()).or_insert_with(|| {
+                    pending.push_back(next);
+                    count
+                });
+                row.push(Some(index));
+            }
+            language.transitions.push(row);
+        }
+        language
+    }
+
+    pub fn alphabet(&self) -> &[A] {
+        &self.alphabet
+    }
+
+    pub fn accepts(&self, word: &[A]) -> bool {
+        let mut state = Some(0);
+        for symbol in word {
+            state = state.and_then(|s| self.step(s, symbol));
+        }
+        state.is_some_and(|s| self.accepting[s])
+    }
+
+    /// Shortest sequence in the language, or `None` if it is empty.
+    pub fn shortest_word(&self) -> Option<Vec<A>> {
+        let mut parents = vec![None; self.accepting.len()];
+        let mut visited = vec![false; self.accepting.len()];
+        let mut pending = VecDeque::from([0]);
+        visited[0] = true;
+        while let Some(state) = pending.pop_front() {
+            if self.accepting[state] {
+                let mut word = Vec::new();
+                let mut state = state;
+                while let Some((parent, symbol)) = parents[state] {
+                    word.push(self.alphabet[symbol]);
+                    state = parent;
+                }
+                word.reverse();
+                return Some(word);
+            }
+            for (symbol, next) in self.transitions[state].iter().enumerate() {
+                if let Some(next) = *next {
+                    if !visited[next] {
+                        visited[next] = true;
+                        parents[next] = Some((state, symbol));
+                        pending.push_back(next);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shortest_word().is_none()
+    }
+
+    /// Sequences over the alphabet that are not in the language. Sequences using symbols outside
+    /// the alphabet are in neither.
+    pub fn complement(&self) -> Self {
+        // The dead state turns accepting, so it becomes a state of its own.
+        let dead = self.accepting.len();
+        let mut transitions = self
+            .transitions
+            .iter()
+            .map(|row| row.iter().map(|next| Some(next.unwrap_or(dead))).collect())
+            .collect::<Vec<_>>();
+        transitions.push(vec![Some(dead); self.alphabet.len()]);
+        let mut accepting = self.accepting.iter().map(|a| !a).collect::<Vec<_>>();
+        accepting.push(true);
+        Language {
+            alphabet: self.alphabet.clone(),
+            transitions,
+            accepting,
+        }
+    }
+
+    /// Sequences in either language, over both alphabets.
+    pub fn union(&self, other: &Self) -> Self {
+        self.product(other, |a, b| a || b)
+    }
+
+    /// Sequences in both languages.
+    pub fn intersection(&self, other: &Self) -> Self {
+        self.product(other, |a, b| a && b)
+    }
+
+    /// Sequences in this language but not in `other`, over both alphabets.
+    pub fn difference(&self, other: &Self) -> Self {
+        self.product(other, |a, b| a && !b)
+    }
+
+    /// Renames the symbols, to compare languages over different alphabets. Symbols mapped to the
+    /// same one merge.
+    pub fn map<B: Copy + Eq>(&self, f: impl Fn(A) -> B) -> Language<B> {
+        let mut alphabet = Vec::new();
+        for &symbol in &self.alphabet {
+            if !alphabet.contains(&f(symbol)) {
+                alphabet.push(f(symbol));
+            }
+        }
+        let accepting = (0..self.accepting.len())
+            .filter(|&state| self.accepting[state])
+            .collect::<Vec<_>>();
+        let edges = self
+            .transitions
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .zip(&self.alphabet)
+                    .filter_map(|(next, &symbol)| next.map(|next| (f(symbol), vec![next])))
+                    .collect()
+            })
+            .collect();
+        Language::from_nfa(alphabet, 0, &accepting, edges)
+    }
+
+    fn step(&self, state: usize, symbol: &A) -> Option<usize> {
+        let index = self.alphabet.iter().position(|a| a == symbol)?;
+        self.transitions[state][index]
+    }
+
+    /// Runs both automata side by side over the union of the alphabets, a pair of states
+    /// accepting as told by `accept`, which must reject two dead states.
+    fn product(&self, other: &Self, accept: impl Fn(bool, bool) -> bool) -> Self {
+        let mut alphabet = self.alphabet.clone();
+        for symbol in &other.alphabet {
+            if !alphabet.contains(symbol) {
+                alphabet.push(*symbol);
+            }
+        }
+        let accepting =
+            |language: &Self, state: Option<usize>| state.is_some_and(|s| language.accepting[s]);
+
+        let mut product = Language {
+            alphabet,
+            transitions: Vec::new(),
+            accepting: Vec::new(),
+        };
+        let mut pairs = HashMap::new();
+        let mut pending = VecDeque::new();
+        pairs.insert((Some(0), Some(0)), 0);
+        pending.push_back((Some(0), Some(0)));
+        while let Some((a, b)) = pending.pop_front() {
+            product
+                .accepting
+                .push(accept(accepting(self, a), accepting(other, b)));
+            let mut row = Vec::with_capacity(product.alphabet.len());
+            for symbol in &product.alphabet {
+                let next = (
+                    a.and_then(|a| self.step(a, symbol)),
+                    b.and_then(|b| other.step(b, symbol)),
+                );
+                if next == (None, None) {
+                    row.push(None);
+                    continue;
+                }
+                let count = pairs.len();
+                let index = *pairs.entry(next).or_insert_with(|| {
+                    pending.push_back(next);
+                    count
+                });
+                row.push(Some(index));
+            }
+            product.transitions.push(row);
+        }
+        product
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Sequences of a and b ending with a.
+    fn ends_with_a() -> Language<char> {
+        Language::from_nfa(
+            vec!['a', 'b'],
+            0,
+            &[1],
+            vec![vec![('a', vec![0, 1]), ('b', vec![0])], vec![]],
+        )
+    }
+
+    // Sequences of a of even length.
+    fn even_a() -> Language<char> {
+        Language::from_nfa(
+            vec!['a'],
+            0,
+            &[0],
+            vec![vec![('a', vec![1])], vec![('a', vec![0])]],
+        )
+    }
+
+    #[test]
+    fn determinizes_the_automaton() {
+        let language = ends_with_a();
+        assert!(language.accepts(&['b', 'a']));
+        assert!(language.accepts(&['a', 'b', 'a']));
+        assert!(!language.accepts(&['a', 'b']));
+        assert!(!language.accepts(&['c', 'a']));
+        assert_eq!(language.shortest_word(), Some(vec!['a']));
+    }
+
+    #[test]
+    fn complements_over_the_alphabet() {
+        let language = ends_with_a().complement();
+        assert!(language.accepts(&[]));
+        assert!(language.accepts(&['a', 'b']));
+        assert!(!language.accepts(&['b', 'a']));
+        assert!(!language.accepts(&['c']));
+        assert!(ends_with_a().intersection(&language).is_empty());
+    }
+
+    #[test]
+    fn combines_languages_over_both_alphabets() {
+        let both = ends_with_a().intersection(&even_a());
+        assert_eq!(both.shortest_word(), Some(vec!['a', 'a']));
+        assert!(!both.accepts(&['a']));
+        assert!(!both.accepts(&['b', 'a']));
+
+        let either = ends_with_a().union(&even_a());
+        assert!(either.accepts(&[]));
+        assert!(either.accepts(&['b', 'a']));
+        assert!(!either.accepts(&['a', 'b']));
+
+        let difference = ends_with_a().difference(&even_a());
+        assert_eq!(difference.shortest_word(), Some(vec!['a']));
+        assert!(difference.accepts(&['a', 'a', 'b', 'a']));
+        assert!(!difference.accepts(&['a', 'a']));
+        assert!(even_a().difference(&ends_with_a()).accepts(&[]));
+    }
+
+    #[test]
+    fn merges_renamed_symbols() {
+        let language = ends_with_a().map(|_| 'x');
+        assert_eq!(language.alphabet(), ['x']);
+        assert!(language.accepts(&['x', 'x']));
+        assert!(!language.accepts(&[]));
+    }
+}
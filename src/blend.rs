@@ -0,0 +1,155 @@
+//! Animation blending between the outputs of two states, timed by the `blend(Millis, Easing)`
+//! declared on the transition going from one to the other.
+
+use core::time::Duration;
+
+/// Curve the blend progresses along.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    /// Eased progress for the linear progress `t`, both between 0 and 1.
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// Metadata of a transition declaring `blend(Millis, Easing)`, returned by the generated `blend`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Blend {
+    pub duration: Duration,
+    pub easing: Easing,
+}
+
+/// Values a blend can interpolate.
+pub trait Lerp {
+    /// `self` for `t == 0`, `other` for `t == 1`.
+    fn lerp(&self, other: &Self, t: f32) -> Self;
+}
+
+impl Lerp for f32 {
+    fn lerp(&self, other: &f32, t: f32) -> f32 {
+        self + (other - self) * t
+    }
+}
+
+impl Lerp for f64 {
+    fn lerp(&self, other: &f64, t: f32) -> f64 {
+        self + (other - self) * f64::from(t)
+    }
+}
+
+impl<T: Lerp, const N: usize> Lerp for [T; N] {
+    fn lerp(&self, other: &[T; N], t: f32) -> [T; N] {
+        core::array::from_fn(|i| self[i].lerp(&other[i], t))
+    }
+}
+
+/// Output blending from the one of the state left to the one of the state entered, e.g. the pose
+/// of an animation controller, as time is advanced.
+#[derive(Debug, Clone)]
+pub struct BlendState<O> {
+    from: O,
+    to: O,
+    blend: Blend,
+    elapsed: Duration,
+}
+
+impl<O: Lerp> BlendState<O> {
+    pub fn new(from: O, to: O, blend: Blend) -> Self {
+        BlendState {
+            from,
+            to,
+            blend,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    pub fn advance(&mut self, dt: Duration) {
+        self.elapsed = (self.elapsed + dt).min(self.blend.duration);
+    }
+
+    /// Eased progress, from 0 when the blend starts to 1 once it is done.
+    pub fn progress(&self) -> f32 {
+        if self.blend.duration.is_zero() {
+            return 1.0;
+        }
+        let t = self.elapsed.as_secs_f32() / self.blend.duration.as_secs_f32();
+        self.blend.easing.apply(t)
+    }
+
+    pub fn value(&self) -> O {
+        self.from.lerp(&self.to, self.progress())
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.elapsed >= self.blend.duration
+    }
+
+    /// Output of the state entered.
+    pub fn into_target(self) -> O {
+        self.to
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blend(millis: u64, easing: Easing) -> Blend {
+        Blend {
+            duration: Duration::from_millis(millis),
+            easing,
+        }
+    }
+
+    #[test]
+    fn easings_start_at_zero_and_end_at_one() {
+        for easing in [
+            Easing::Linear,
+            Easing::EaseIn,
+            Easing::EaseOut,
+            Easing::EaseInOut,
+        ] {
+            assert_eq!(easing.apply(0.0), 0.0);
+            assert_eq!(easing.apply(1.0), 1.0);
+            assert_eq!(easing.apply(2.0), 1.0);
+        }
+        assert!(Easing::EaseIn.apply(0.5) < 0.5);
+        assert!(Easing::EaseOut.apply(0.5) > 0.5);
+        assert_eq!(Easing::EaseInOut.apply(0.5), 0.5);
+    }
+
+    #[test]
+    fn interpolates_over_the_duration() {
+        let mut state = BlendState::new([0.0, 10.0], [1.0, 20.0], blend(200, Easing::Linear));
+        assert_eq!(state.value(), [0.0, 10.0]);
+
+        state.advance(Duration::from_millis(50));
+        assert_eq!(state.value(), [0.25, 12.5]);
+        assert!(!state.is_done());
+
+        state.advance(Duration::from_millis(500));
+        assert!(state.is_done());
+        assert_eq!(state.value(), [1.0, 20.0]);
+        assert_eq!(state.into_target(), [1.0, 20.0]);
+    }
+
+    #[test]
+    fn instant_blends_are_done_right_away() {
+        let state = BlendState::new(0.0f64, 1.0, blend(0, Easing::EaseIn));
+        assert!(state.is_done());
+        assert_eq!(state.value(), 1.0);
+    }
+}
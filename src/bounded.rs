@@ -0,0 +1,384 @@
+//! Fixed-capacity variants of the runners, holding their queues and traces in [`heapless`]
+//! buffers sized by const generics: usable without `std` nor an allocator.
+//!
+//! Where the allocating runners would grow, these give the action back.
+
+use heapless::{Deque, HistoryBuffer, Vec};
+
+use crate::{Machine, MachineId, TransitionObserver};
+
+/// A step reported to a [`Trace`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub from: &'static str,
+    pub action: &'static str,
+    /// State reached, `None` if `from` rejected the action.
+    pub to: Option<&'static str>,
+}
+
+/// Observer keeping the last `N` steps of a machine driven through its generated
+/// `next_observed`.
+pub struct Trace<const N: usize> {
+    entries: HistoryBuffer<TraceEntry, N>,
+}
+
+impl<const N: usize> Trace<N> {
+    pub const fn new() -> Self {
+        Trace {
+            entries: HistoryBuffer::new(),
+        }
+    }
+
+    /// The steps kept, oldest first.
+    pub fn entries(&self) -> impl Iterator<Item = &TraceEntry> {
+        self.entries.oldest_ordered()
+    }
+
+    pub fn last(&self) -> Option<&TraceEntry> {
+        self.entries.recent()
+    }
+}
+
+impl<const N: usize> Default for Trace<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> TransitionObserver for Trace<N> {
+    fn on_transition(&mut self, from: &'static str, action: &'static str, to: &'static str) {
+        self.entries.write(TraceEntry {
+            from,
+            action,
+            to: Some(to),
+        });
+    }
+
+    fn on_rejection(&mut self, state: &'static str, action: &'static str) {
+        self.entries.write(TraceEntry {
+            from: state,
+            action,
+            to: None,
+        });
+    }
+}
+
+/// Same as `deferred::Deferring`, keeping at most `N` deferred actions.
+pub struct Deferring<M: Machine, const N: usize> {
+    // Only ever `None` while an action is being applied.
+    state: Option<M>,
+    deferred: Deque<M::Action, N>,
+}
+
+impl<M: Machine, const N: usize> Deferring<M, N> {
+    pub fn new(state: M) -> Self {
+        Deferring {
+            state: Some(state),
+            deferred: Deque::new(),
+        }
+    }
+
+    pub fn state(&self) -> &M {
+        self.state
+            .as_ref()
+            .expect("Deferring poisoned by a panicking handler")
+    }
+
+    /// Actions waiting for a state to accept them, oldest first.
+    pub fn deferred(&self) -> impl Iterator<Item = &M::Action> {
+        self.deferred.iter()
+    }
+
+    pub fn into_inner(self) -> M {
+        self.state
+            .expect("Deferring poisoned by a panicking handler")
+    }
+
+    /// Applies `action`, or keeps it aside if the current state defers it. Rejected actions are
+    /// given back, as are deferred ones finding `N` actions already kept aside.
+    pub fn send(&mut self, action: M::Action) -> Result<(), M::Action> {
+        if self.state().is_deferred(&action) {
+            return self.deferred.push_back(action);
+        }
+
+        self.apply(action)?;
+        self.replay();
+        Ok(())
+    }
+
+    /// Tries the deferred actions in order against the new state, again and again as long as one
+    /// of them goes through. Those still deferred or rejected wait for the next transition.
+    fn replay(&mut self) {
+        let mut progress = true;
+        while progress {
+            progress = false;
+            let mut waiting = Deque::new();
+            while let Some(action) = self.deferred.pop_front() {
+                let kept = if self.state().is_deferred(&action) {
+                    waiting.push_back(action)
+                } else if let Err(action) = self.apply(action) {
+                    waiting.push_back(action)
+                } else {
+                    progress = true;
+                    Ok(())
+                };
+                // At most as many actions wait as there were deferred.
+                debug_assert!(kept.is_ok());
+            }
+            self.deferred = waiting;
+        }
+    }
+
+    fn apply(&mut self, action: M::Action) -> Result<(), M::Action> {
+        let state = self
+            .state
+            .take()
+            .expect("Deferring poisoned by a panicking handler");
+        let (state, result) = match state.next(action).map_err(Into::into) {
+            Ok(state) => (state, Ok(())),
+            Err((state, action)) => (state, Err(action)),
+        };
+        self.state = Some(state);
+        result
+    }
+}
+
+struct Managed<M: Machine, const QUEUE: usize> {
+    // Only ever `None` while an action is being applied.
+    state: Option<M>,
+    queue: Deque<M::Action, QUEUE>,
+}
+
+impl<M: Machine, const QUEUE: usize> Managed<M, QUEUE> {
+    fn state(&self) -> &M {
+        self.state
+            .as_ref()
+            .expect("Bus poisoned by a panicking handler")
+    }
+
+    fn is_settled(&self) -> bool {
+        let state = self.state();
+        self.queue.is_empty() && (state.is_finished() || state.is_idle())
+    }
+}
+
+/// Same as `bus::Bus`, managing at most `MACHINES` machines with at most `QUEUE`
+/// actions queued for each.
+pub struct Bus<M: Machine, const MACHINES: usize, const QUEUE: usize> {
+    machines: Vec<Managed<M, QUEUE>, MACHINES>,
+}
+
+impl<M: Machine, const MACHINES: usize, const QUEUE: usize> Bus<M, MACHINES, QUEUE> {
+    pub const fn new() -> Self {
+        Bus {
+            machines: Vec::new(),
+        }
+    }
+
+    /// Gives the machine back if the bus already manages `MACHINES` of them.
+    pub fn add(&mut self, state: M) -> Result<MachineId, M> {
+        self.machines
+            .push(Managed {
+                state: Some(state),
+                queue: Deque::new(),
+            })
+            .map_err(|managed| managed.state.unwrap())?;
+        Ok(MachineId(self.machines.len() - 1))
+    }
+
+    pub fn state(&self, id: MachineId) -> &M {
+        self.machines[id.0].state()
+    }
+
+    /// Queues `action` for the machine `id`, giving it back if its queue is full.
+    pub fn send(&mut self, id: MachineId, action: impl Into<M::Action>) -> Result<(), M::Action> {
+        self.machines[id.0].queue.push_back(action.into())
+    }
+
+    /// Number of actions queued for the machine `id`.
+    pub fn queued(&self, id: MachineId) -> usize {
+        self.machines[id.0].queue.len()
+    }
+
+    /// Delivers the oldest queued action of each machine, in the order the machines were added.
+    /// `rejected` is called with the actions the machines rejected, and the machine rejecting
+    /// each.
+    pub fn step(&mut self, mut rejected: impl FnMut(MachineId, M::Action)) {
+        for (index, machine) in self.machines.iter_mut().enumerate() {
+            let action = match machine.queue.pop_front() {
+                Some(action) => action,
+                None => continue,
+            };
+            let state = machine
+                .state
+                .take()
+                .expect("Bus poisoned by a panicking handler");
+            machine.state = Some(match state.next(action).map_err(Into::into) {
+                Ok(state) => state,
+                Err((state, action)) => {
+                    rejected(MachineId(index), action);
+                    state
+                }
+            });
+        }
+    }
+
+    /// Steps until every queue is empty.
+    pub fn run(&mut self, mut rejected: impl FnMut(MachineId, M::Action)) {
+        while self
+            .machines
+            .iter()
+            .any(|machine| !machine.queue.is_empty())
+        {
+            self.step(&mut rejected);
+        }
+    }
+
+    /// Whether every queue is empty and every machine is finished or idle.
+    pub fn is_quiescent(&self) -> bool {
+        self.machines.iter().all(Managed::is_settled)
+    }
+
+    /// Machines keeping the bus from being quiescent, having queued actions or resting in a state
+    /// neither terminal nor idle.
+    pub fn busy(&self) -> impl Iterator<Item = MachineId> + '_ {
+        self.machines
+            .iter()
+            .enumerate()
+            .filter(|(_, machine)| !machine.is_settled())
+            .map(|(index, _)| MachineId(index))
+    }
+}
+
+impl<M: Machine, const MACHINES: usize, const QUEUE: usize> Default for Bus<M, MACHINES, QUEUE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+
+    use crate::{state_machine, Action, State};
+
+    #[derive(Debug)]
+    struct Waiting;
+    #[derive(Debug)]
+    struct Working;
+    #[derive(Debug)]
+    struct Stopped;
+
+    #[derive(Debug)]
+    struct Job;
+    #[derive(Debug)]
+    struct Finish;
+    #[derive(Debug)]
+    struct Stop;
+
+    state_machine! {
+        #[derive(Debug)] Worker,
+        #[derive(Debug)] WorkerAction,
+        idle Waiting,
+        terminal Stopped,
+        Waiting {
+            Job => Working,
+            Stop => Stopped,
+        },
+        Working {
+            Finish => Waiting,
+            defer Stop,
+        },
+        Stopped {},
+    }
+
+    impl State<Worker, Job> for Waiting {
+        fn next(self, _action: Job) -> Worker {
+            Working.into()
+        }
+    }
+
+    impl State<Worker, Stop> for Waiting {
+        fn next(self, _action: Stop) -> Worker {
+            Stopped.into()
+        }
+    }
+
+    impl State<Worker, Finish> for Working {
+        fn next(self, _action: Finish) -> Worker {
+            Waiting.into()
+        }
+    }
+
+    #[test]
+    fn keeps_the_last_steps() {
+        let mut trace = Trace::<2>::new();
+        let worker = Worker::from(Waiting);
+        let worker = worker.next_observed(&mut trace, Job.into()).unwrap();
+        let worker = worker.next_observed(&mut trace, Job.into()).unwrap_err();
+        worker
+            .state
+            .next_observed(&mut trace, Finish.into())
+            .unwrap();
+
+        let entries = trace.entries().copied().collect::<std::vec::Vec<_>>();
+        assert_eq!(
+            entries,
+            [
+                TraceEntry {
+                    from: "Working",
+                    action: "Job",
+                    to: None
+                },
+                TraceEntry {
+                    from: "Working",
+                    action: "Finish",
+                    to: Some("Waiting")
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn gives_back_what_does_not_fit() {
+        let mut worker = Deferring::<Worker, 1>::new(Worker::from(Working));
+        worker.send(Stop.into()).unwrap();
+        assert!(matches!(
+            worker.send(Stop.into()),
+            Err(WorkerAction::Stop(_))
+        ));
+        worker.send(Finish.into()).unwrap();
+        assert!(worker.state().is_finished());
+
+        let mut bus = Bus::<Worker, 1, 1>::new();
+        let id = bus.add(Worker::from(Waiting)).unwrap();
+        assert!(bus.add(Worker::from(Waiting)).is_err());
+        bus.send(id, Job).unwrap();
+        assert!(bus.send(id, Finish).is_err());
+        assert!(!bus.is_quiescent());
+    }
+
+    #[test]
+    fn is_quiescent_once_every_machine_settled() {
+        let mut bus = Bus::<Worker, 2, 4>::new();
+        let a = bus.add(Worker::from(Waiting)).unwrap();
+        let b = bus.add(Worker::from(Waiting)).unwrap();
+        bus.send(a, Job).unwrap();
+        bus.send(b, Stop).unwrap();
+        bus.send(b, Job).unwrap();
+
+        let mut rejected = 0;
+        bus.run(|id, _| {
+            assert_eq!(id, b);
+            rejected += 1;
+        });
+        assert_eq!(rejected, 1);
+        assert_eq!(bus.busy().collect::<std::vec::Vec<_>>(), [a]);
+
+        bus.send(a, Finish).unwrap();
+        bus.run(|_, _| panic!("rejected"));
+        assert!(bus.is_quiescent());
+    }
+}
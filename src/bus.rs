@@ -0,0 +1,221 @@
+use std::collections::VecDeque;
+
+use crate::{Machine, MachineId};
+
+struct Managed<M: Machine> {
+    // Only ever `None` while an action is being applied.
+    state: Option<M>,
+    queue: VecDeque<M::Action>,
+}
+
+impl<M: Machine> Managed<M> {
+    fn state(&self) -> &M {
+        self.state
+            .as_ref()
+            .expect("Bus poisoned by a panicking handler")
+    }
+
+    fn is_settled(&self) -> bool {
+        let state = self.state();
+        self.queue.is_empty() && (state.is_finished() || state.is_idle())
+    }
+}
+
+/// In-process bus owning machines, each with its own queue of actions delivered in order.
+///
+/// The bus is quiescent once every queue is empty and every machine rests in a terminal state or
+/// in a state declared `idle`: nothing happens anymore until an action is sent from outside.
+pub struct Bus<M: Machine> {
+    machines: Vec<Managed<M>>,
+}
+
+impl<M: Machine> Bus<M> {
+    pub fn new() -> Self {
+        Bus {
+            machines: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, state: M) -> MachineId {
+        self.machines.push(Managed {
+            state: Some(state),
+            queue: VecDeque::new(),
+        });
+        MachineId(self.machines.len() - 1)
+    }
+
+    pub fn state(&self, id: MachineId) -> &M {
+        self.machines[id.0].state()
+    }
+
+    /// Queues `action` for the machine `id`.
+    pub fn send(&mut self, id: MachineId, action: impl Into<M::Action>) {
+        self.machines[id.0].queue.push_back(action.into());
+    }
+
+    /// Queues a copy of `action` for every machine.
+    pub fn broadcast(&mut self, action: impl Into<M::Action>)
+    where
+        M::Action: Clone,
+    {
+        let action = action.into();
+        for machine in &mut self.machines {
+            machine.queue.push_back(action.clone());
+        }
+    }
+
+    /// Number of actions queued for the machine `id`.
+    pub fn queued(&self, id: MachineId) -> usize {
+        self.machines[id.0].queue.len()
+    }
+
+    /// Delivers the oldest queued action of each machine, in the order the machines were added.
+    /// Returns the actions the machines rejected, with the machine rejecting each.
+    pub fn step(&mut self) -> Vec<(MachineId, M::Action)> {
+        let mut rejected = Vec::new();
+        for (index, machine) in self.machines.iter_mut().enumerate() {
+            let action = match machine.queue.pop_front() {
+                Some(action) => action,
+                None => continue,
+            };
+            let state = machine
+                .state
+                .take()
+                .expect("Bus poisoned by a panicking handler");
+            machine.state = Some(match state.next(action).map_err(Into::into) {
+                Ok(state) => state,
+                Err((state, action)) => {
+                    rejected.push((MachineId(index), action));
+                    state
+                }
+            });
+        }
+        rejected
+    }
+
+    /// Steps until every queue is empty. Returns the actions the machines rejected along the
+    /// way, with the machine rejecting each.
+    pub fn run(&mut self) -> Vec<(MachineId, M::Action)> {
+        let mut rejected = Vec::new();
+        while self
+            .machines
+            .iter()
+            .any(|machine| !machine.queue.is_empty())
+        {
+            rejected.extend(self.step());
+        }
+        rejected
+    }
+
+    /// Whether every queue is empty and every machine is finished or idle.
+    pub fn is_quiescent(&self) -> bool {
+        self.machines.iter().all(Managed::is_settled)
+    }
+
+    /// Machines keeping the bus from being quiescent, having queued actions or resting in a state
+    /// neither terminal nor idle.
+    pub fn busy(&self) -> impl Iterator<Item = MachineId> + '_ {
+        self.machines
+            .iter()
+            .enumerate()
+            .filter(|(_, machine)| !machine.is_settled())
+            .map(|(index, _)| MachineId(index))
+    }
+}
+
+impl<M: Machine> Default for Bus<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{state_machine, Action, State};
+
+    #[derive(Debug)]
+    struct Waiting;
+    #[derive(Debug)]
+    struct Working(u32);
+    #[derive(Debug)]
+    struct Stopped;
+
+    #[derive(Debug, Clone)]
+    struct Job(u32);
+    #[derive(Debug, Clone)]
+    struct Finish;
+    #[derive(Debug, Clone)]
+    struct Stop;
+
+    state_machine! {
+        #[derive(Debug)] Worker,
+        #[derive(Debug, Clone)] WorkerAction,
+        idle Waiting,
+        terminal Stopped,
+        Waiting {
+            Job => Working,
+            Stop => Stopped,
+        },
+        Working { Finish => Waiting },
+        Stopped {},
+    }
+
+    impl State<Worker, Job> for Waiting {
+        fn next(self, action: Job) -> Worker {
+            Working(action.0).into()
+        }
+    }
+
+    impl State<Worker, Stop> for Waiting {
+        fn next(self, _action: Stop) -> Worker {
+            Stopped.into()
+        }
+    }
+
+    impl State<Worker, Finish> for Working {
+        fn next(self, _action: Finish) -> Worker {
+            Waiting.into()
+        }
+    }
+
+    #[test]
+    fn is_quiescent_once_every_machine_settled() {
+        let mut bus = Bus::new();
+        let a = bus.add(Worker::from(Waiting));
+        let b = bus.add(Worker::from(Waiting));
+        assert!(bus.is_quiescent());
+
+        bus.send(a, Job(1));
+        bus.send(b, Job(2));
+        bus.send(b, Finish);
+        assert_eq!(bus.busy().collect::<Vec<_>>(), [a, b]);
+
+        assert!(bus.step().is_empty());
+        assert_eq!(bus.queued(b), 1);
+        assert!(bus.run().is_empty());
+        assert_eq!(bus.busy().collect::<Vec<_>>(), [a]);
+        assert!(matches!(bus.state(a), Worker::Working(Working(1))));
+        assert!(!bus.is_quiescent());
+
+        bus.send(a, Finish);
+        bus.run();
+        assert!(bus.is_quiescent());
+        assert!(bus.state(a).is_idle());
+    }
+
+    #[test]
+    fn counts_terminal_machines_as_settled() {
+        let mut bus = Bus::new();
+        let a = bus.add(Worker::from(Waiting));
+        let b = bus.add(Worker::from(Working(0)));
+        bus.broadcast(Stop);
+
+        let rejected = bus.run();
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(rejected[0].0, b);
+        assert!(bus.state(a).is_finished());
+        assert_eq!(bus.busy().collect::<Vec<_>>(), [b]);
+    }
+}
@@ -0,0 +1,185 @@
+use crate::Machine;
+
+type Disturbance<A> = Box<dyn FnMut() -> A>;
+
+/// Test wrapper injecting disturbance actions (timeouts, disconnects, ...) before the actions
+/// sent to the machine, to check how it copes with adverse event orderings.
+///
+/// Injections are driven by a seeded generator, so a failing run can be replayed with the same
+/// seed.
+pub struct Chaos<M: Machine> {
+    // Only ever `None` while an action is being applied.
+    state: Option<M>,
+    disturbances: Vec<Disturbance<M::Action>>,
+    rate: f64,
+    rng: u64,
+    injected: Vec<usize>,
+}
+
+impl<M: Machine> Chaos<M> {
+    pub fn new(state: M, seed: u64) -> Self {
+        Chaos {
+            state: Some(state),
+            disturbances: Vec::new(),
+            rate: 0.1,
+            rng: seed,
+            injected: Vec::new(),
+        }
+    }
+
+    /// Adds a disturbance to pick from, built by `make_action` each time it is injected.
+    pub fn disturbance(mut self, make_action: impl FnMut() -> M::Action + 'static) -> Self {
+        self.disturbances.push(Box::new(make_action));
+        self
+    }
+
+    /// Probability, between 0 and 1, of injecting a disturbance before each action sent.
+    pub fn rate(mut self, rate: f64) -> Self {
+        self.rate = rate;
+        self
+    }
+
+    pub fn state(&self) -> &M {
+        self.state
+            .as_ref()
+            .expect("Chaos poisoned by a panicking handler")
+    }
+
+    pub fn into_inner(self) -> M {
+        self.state.expect("Chaos poisoned by a panicking handler")
+    }
+
+    /// Indices of the disturbances injected so far, in injection order.
+    pub fn injected(&self) -> &[usize] {
+        &self.injected
+    }
+
+    /// Applies `action`, possibly preceded by a disturbance. A disturbance the current state does
+    /// not accept is dropped; a rejected `action` is given back.
+    pub fn send(&mut self, action: M::Action) -> Result<(), M::Action> {
+        if !self.disturbances.is_empty() && self.next_f64() < self.rate {
+            let index = (self.next_u64() % self.disturbances.len() as u64) as usize;
+            self.injected.push(index);
+            let disturbance = (self.disturbances[index])();
+            let _ = self.apply(disturbance);
+        }
+
+        self.apply(action)
+    }
+
+    fn apply(&mut self, action: M::Action) -> Result<(), M::Action> {
+        let state = self
+            .state
+            .take()
+            .expect("Chaos poisoned by a panicking handler");
+        let (state, result) = match state.next(action).map_err(Into::into) {
+            Ok(state) => (state, Ok(())),
+            Err((state, action)) => (state, Err(action)),
+        };
+        self.state = Some(state);
+        result
+    }
+
+    // splitmix64
+    fn next_u64(&mut self) -> u64 {
+        self.rng = self.rng.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{state_machine, Action, State};
+
+    #[derive(Debug)]
+    struct Count(u32);
+    #[derive(Debug)]
+    struct Stopped;
+
+    #[derive(Debug)]
+    struct Add(u32);
+    #[derive(Debug)]
+    struct Reset;
+    #[derive(Debug)]
+    struct Stop;
+
+    state_machine! {
+        Counter,
+        CounterAction,
+        terminal Stopped,
+        Count {
+            Add => Count,
+            Reset => Count,
+            Stop => Stopped,
+        },
+        Stopped {},
+    }
+
+    impl State<Counter, Add> for Count {
+        fn next(self, action: Add) -> Counter {
+            Count(self.0 + action.0).into()
+        }
+    }
+
+    impl State<Counter, Reset> for Count {
+        fn next(self, _action: Reset) -> Counter {
+            Count(0).into()
+        }
+    }
+
+    impl State<Counter, Stop> for Count {
+        fn next(self, _action: Stop) -> Counter {
+            Stopped.into()
+        }
+    }
+
+    fn run(seed: u64, rate: f64) -> Chaos<Counter> {
+        let mut chaos = Chaos::new(Counter::from(Count(0)), seed)
+            .disturbance(|| Reset.into())
+            .disturbance(|| Add(100).into())
+            .rate(rate);
+        for _ in 0..50 {
+            chaos.send(Add(1).into()).unwrap();
+        }
+        chaos
+    }
+
+    #[test]
+    fn replays_the_same_injections_from_the_same_seed() {
+        let first = run(7, 0.3);
+        let second = run(7, 0.3);
+        assert!(!first.injected().is_empty());
+        assert_eq!(first.injected(), second.injected());
+        assert_ne!(first.injected(), run(8, 0.3).injected());
+    }
+
+    #[test]
+    fn injects_at_the_given_rate() {
+        assert!(run(7, 0.0).injected().is_empty());
+        assert!(matches!(
+            run(7, 0.0).into_inner(),
+            Counter::Count(Count(50))
+        ));
+        assert_eq!(run(7, 1.0).injected().len(), 50);
+    }
+
+    #[test]
+    fn drops_the_disturbances_the_state_rejects() {
+        let mut chaos = Chaos::new(Counter::from(Count(0)), 1)
+            .disturbance(|| Reset.into())
+            .rate(1.0);
+        chaos.send(Stop.into()).unwrap();
+        assert_eq!(chaos.send(Add(1).into()).unwrap_err().name(), "Add");
+        assert_eq!(chaos.injected(), [0, 0]);
+        assert_eq!(chaos.state().name(), "Stopped");
+    }
+}
@@ -0,0 +1,83 @@
+use std::collections::VecDeque;
+
+use crate::Machine;
+
+/// Runner keeping aside the actions the current state defers, and replaying them after every
+/// transition until a state accepts them.
+pub struct Deferring<M: Machine> {
+    // Only ever `None` while an action is being applied.
+    state: Option<M>,
+    deferred: VecDeque<M::Action>,
+}
+
+impl<M: Machine> Deferring<M> {
+    pub fn new(state: M) -> Self {
+        Deferring {
+            state: Some(state),
+            deferred: VecDeque::new(),
+        }
+    }
+
+    pub fn state(&self) -> &M {
+        self.state
+            .as_ref()
+            .expect("Deferring poisoned by a panicking handler")
+    }
+
+    /// Actions waiting for a state to accept them, oldest first.
+    pub fn deferred(&self) -> impl Iterator<Item = &M::Action> {
+        self.deferred.iter()
+    }
+
+    pub fn into_inner(self) -> M {
+        self.state
+            .expect("Deferring poisoned by a panicking handler")
+    }
+
+    /// Applies `action`, or keeps it aside if the current state defers it. Rejected actions are
+    /// given back.
+    pub fn send(&mut self, action: M::Action) -> Result<(), M::Action> {
+        if self.state().is_deferred(&action) {
+            self.deferred.push_back(action);
+            return Ok(());
+        }
+
+        self.apply(action)?;
+        self.replay();
+        Ok(())
+    }
+
+    /// Tries the deferred actions in order against the new state, again and again as long as one
+    /// of them goes through, since the states it leads to may accept the earlier ones. Those still
+    /// deferred or rejected wait for the next transition.
+    fn replay(&mut self) {
+        let mut progress = true;
+        while progress {
+            progress = false;
+            let mut waiting = VecDeque::new();
+            while let Some(action) = self.deferred.pop_front() {
+                if self.state().is_deferred(&action) {
+                    waiting.push_back(action);
+                } else if let Err(action) = self.apply(action) {
+                    waiting.push_back(action);
+                } else {
+                    progress = true;
+                }
+            }
+            self.deferred = waiting;
+        }
+    }
+
+    fn apply(&mut self, action: M::Action) -> Result<(), M::Action> {
+        let state = self
+            .state
+            .take()
+            .expect("Deferring poisoned by a panicking handler");
+        let (state, result) = match state.next(action).map_err(Into::into) {
+            Ok(state) => (state, Ok(())),
+            Err((state, action)) => (state, Err(action)),
+        };
+        self.state = Some(state);
+        result
+    }
+}
@@ -0,0 +1,257 @@
+//! Failures of a machine and of the runners driving it, all convertible into [`MachineError`] so
+//! that applications can handle them in one place.
+
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+use crate::{remote, soak, InvalidAction, InvalidTransition, ParseActionError, StepError};
+
+/// The current state does not accept the action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rejected {
+    pub state: &'static str,
+    pub action: &'static str,
+}
+
+impl fmt::Display for Rejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "State {} rejected action {}", self.state, self.action)
+    }
+}
+
+impl Error for Rejected {}
+
+/// A handler accepted the action but failed to apply it. Handlers cannot fail themselves, so this
+/// is for the runners wrapping fallible work around a step.
+#[derive(Debug)]
+pub struct HandlerError {
+    pub state: &'static str,
+    pub action: &'static str,
+    pub source: Box<dyn Error + Send + Sync>,
+}
+
+impl fmt::Display for HandlerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Handler for state {} and action {} failed: {}",
+            self.state, self.action, self.source
+        )
+    }
+}
+
+impl Error for HandlerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&*self.source)
+    }
+}
+
+/// The machine kept stepping without making progress, e.g. actions fed back by its own handlers
+/// going around a cycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Livelock {
+    pub state: &'static str,
+    pub steps: u64,
+}
+
+impl fmt::Display for Livelock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "No progress after {} steps, stuck around state {}",
+            self.steps, self.state
+        )
+    }
+}
+
+impl Error for Livelock {}
+
+/// The machine did not get the action it was waiting for in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeout {
+    pub state: &'static str,
+    pub after: Duration,
+}
+
+impl fmt::Display for Timeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Timed out in state {} after {:?}",
+            self.state, self.after
+        )
+    }
+}
+
+impl Error for Timeout {}
+
+/// An action came with a sequence number other than the next one: already used, or skipping some.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReplayError {
+    pub sequence: u64,
+    pub expected_sequence: u64,
+}
+
+impl ReplayError {
+    /// Whether the sequence number was already used, rather than skipping some.
+    pub fn is_replayed(&self) -> bool {
+        self.sequence < self.expected_sequence
+    }
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let problem = if self.is_replayed() {
+            "replayed"
+        } else {
+            "out of order"
+        };
+        write!(
+            f,
+            "Action with sequence number {} {}, expected {}",
+            self.sequence, problem, self.expected_sequence
+        )
+    }
+}
+
+impl Error for ReplayError {}
+
+/// Any of the above, or of the errors returned elsewhere in the crate, displayed as the error it
+/// holds.
+#[derive(Debug)]
+pub enum MachineError {
+    Rejected(Rejected),
+    Handler(HandlerError),
+    Livelock(Livelock),
+    Timeout(Timeout),
+    InvalidTransition(InvalidTransition),
+    InvalidAction(InvalidAction),
+    Parse(ParseActionError),
+    Step(StepError),
+    Replay(ReplayError),
+    /// An action from a peer was not applied; the action itself is dropped.
+    Remote(remote::Rejection<()>),
+    Soak(soak::Violation),
+    #[cfg(feature = "serde")]
+    Snapshot(crate::snapshot::IncompatibleSnapshot),
+    #[cfg(feature = "rollback")]
+    Rollback(crate::rollback::OutOfHistory),
+}
+
+impl fmt::Display for MachineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MachineError::Rejected(e) => e.fmt(f),
+            MachineError::Handler(e) => e.fmt(f),
+            MachineError::Livelock(e) => e.fmt(f),
+            MachineError::Timeout(e) => e.fmt(f),
+            MachineError::InvalidTransition(e) => e.fmt(f),
+            MachineError::InvalidAction(e) => e.fmt(f),
+            MachineError::Parse(e) => e.fmt(f),
+            MachineError::Step(e) => e.fmt(f),
+            MachineError::Replay(e) => e.fmt(f),
+            MachineError::Remote(e) => e.fmt(f),
+            MachineError::Soak(e) => e.fmt(f),
+            #[cfg(feature = "serde")]
+            MachineError::Snapshot(e) => e.fmt(f),
+            #[cfg(feature = "rollback")]
+            MachineError::Rollback(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for MachineError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MachineError::Handler(e) => e.source(),
+            MachineError::Parse(ParseActionError::InvalidPayload(e)) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<Rejected> for MachineError {
+    fn from(e: Rejected) -> MachineError {
+        MachineError::Rejected(e)
+    }
+}
+
+impl From<HandlerError> for MachineError {
+    fn from(e: HandlerError) -> MachineError {
+        MachineError::Handler(e)
+    }
+}
+
+impl From<Livelock> for MachineError {
+    fn from(e: Livelock) -> MachineError {
+        MachineError::Livelock(e)
+    }
+}
+
+impl From<Timeout> for MachineError {
+    fn from(e: Timeout) -> MachineError {
+        MachineError::Timeout(e)
+    }
+}
+
+impl From<InvalidTransition> for MachineError {
+    fn from(e: InvalidTransition) -> MachineError {
+        MachineError::InvalidTransition(e)
+    }
+}
+
+impl From<InvalidAction> for MachineError {
+    fn from(e: InvalidAction) -> MachineError {
+        MachineError::InvalidAction(e)
+    }
+}
+
+impl From<ParseActionError> for MachineError {
+    fn from(e: ParseActionError) -> MachineError {
+        MachineError::Parse(e)
+    }
+}
+
+impl From<StepError> for MachineError {
+    fn from(e: StepError) -> MachineError {
+        MachineError::Step(e)
+    }
+}
+
+impl From<ReplayError> for MachineError {
+    fn from(e: ReplayError) -> MachineError {
+        MachineError::Replay(e)
+    }
+}
+
+impl<A> From<remote::Rejection<A>> for MachineError {
+    fn from(e: remote::Rejection<A>) -> MachineError {
+        MachineError::Remote(remote::Rejection {
+            violation: e.violation,
+            sequence: e.sequence,
+            expected_sequence: e.expected_sequence,
+            action: (),
+        })
+    }
+}
+
+impl From<soak::Violation> for MachineError {
+    fn from(e: soak::Violation) -> MachineError {
+        MachineError::Soak(e)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<crate::snapshot::IncompatibleSnapshot> for MachineError {
+    fn from(e: crate::snapshot::IncompatibleSnapshot) -> MachineError {
+        MachineError::Snapshot(e)
+    }
+}
+
+#[cfg(feature = "rollback")]
+impl From<crate::rollback::OutOfHistory> for MachineError {
+    fn from(e: crate::rollback::OutOfHistory) -> MachineError {
+        MachineError::Rollback(e)
+    }
+}
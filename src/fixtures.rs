@@ -0,0 +1,175 @@
+//! Small, well-known machines to test code that is generic over [`Machine`](crate::Machine).
+
+pub mod turnstile {
+    //! Coin-operated turnstile: a coin unlocks it, pushing through locks it again.
+
+    use crate::{state_machine, Action, State};
+
+    #[derive(Debug, Default)]
+    pub struct Locked;
+    #[derive(Debug)]
+    pub struct Unlocked;
+
+    #[derive(Debug)]
+    pub struct Coin;
+    #[derive(Debug)]
+    pub struct Push;
+
+    state_machine! {
+        pub Turnstile,
+        pub TurnstileAction,
+        initial Locked,
+        Locked {
+            Coin => Unlocked,
+            Push => Locked
+        },
+        Unlocked {
+            Coin => Unlocked,
+            Push => Locked
+        },
+    }
+
+    impl State<Turnstile, Coin> for Locked {
+        fn next(self, _action: Coin) -> Turnstile {
+            Unlocked.into()
+        }
+    }
+
+    impl State<Turnstile, Push> for Locked {
+        fn next(self, _action: Push) -> Turnstile {
+            self.into()
+        }
+    }
+
+    impl State<Turnstile, Coin> for Unlocked {
+        fn next(self, _action: Coin) -> Turnstile {
+            self.into()
+        }
+    }
+
+    impl State<Turnstile, Push> for Unlocked {
+        fn next(self, _action: Push) -> Turnstile {
+            Locked.into()
+        }
+    }
+}
+
+pub mod traffic_light {
+    //! Traffic light cycling red, green, yellow on each tick. A fault makes it blink until it is
+    //! reset, which brings it back to red.
+
+    use crate::{state_machine, Action, State};
+
+    #[derive(Debug, Default)]
+    pub struct Red;
+    #[derive(Debug)]
+    pub struct Green;
+    #[derive(Debug)]
+    pub struct Yellow;
+    #[derive(Debug)]
+    pub struct Blinking;
+
+    #[derive(Debug)]
+    pub struct Tick;
+    #[derive(Debug)]
+    pub struct Fault;
+    #[derive(Debug)]
+    pub struct Reset;
+
+    state_machine! {
+        pub TrafficLight,
+        pub TrafficLightAction,
+        initial Red,
+        Red {
+            Tick => Green,
+            Fault => Blinking
+        },
+        Green {
+            Tick => Yellow,
+            Fault => Blinking
+        },
+        Yellow {
+            Tick => Red,
+            Fault => Blinking
+        },
+        Blinking { Reset => Red },
+    }
+
+    impl State<TrafficLight, Tick> for Red {
+        fn next(self, _action: Tick) -> TrafficLight {
+            Green.into()
+        }
+    }
+
+    impl State<TrafficLight, Tick> for Green {
+        fn next(self, _action: Tick) -> TrafficLight {
+            Yellow.into()
+        }
+    }
+
+    impl State<TrafficLight, Tick> for Yellow {
+        fn next(self, _action: Tick) -> TrafficLight {
+            Red.into()
+        }
+    }
+
+    impl State<TrafficLight, Fault> for Red {
+        fn next(self, _action: Fault) -> TrafficLight {
+            Blinking.into()
+        }
+    }
+
+    impl State<TrafficLight, Fault> for Green {
+        fn next(self, _action: Fault) -> TrafficLight {
+            Blinking.into()
+        }
+    }
+
+    impl State<TrafficLight, Fault> for Yellow {
+        fn next(self, _action: Fault) -> TrafficLight {
+            Blinking.into()
+        }
+    }
+
+    impl State<TrafficLight, Reset> for Blinking {
+        fn next(self, _action: Reset) -> TrafficLight {
+            Red.into()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::traffic_light::*;
+    use super::turnstile::*;
+
+    #[test]
+    fn turnstile_unlocks_on_a_coin() {
+        let turnstile = Turnstile::default();
+        assert_eq!(turnstile.name(), "Locked");
+        let turnstile = turnstile.next(Push.into()).unwrap();
+        assert_eq!(turnstile.name(), "Locked");
+        let turnstile = turnstile.next(Coin.into()).unwrap();
+        assert_eq!(turnstile.name(), "Unlocked");
+        let turnstile = turnstile.next(Coin.into()).unwrap();
+        assert_eq!(turnstile.name(), "Unlocked");
+        let turnstile = turnstile.next(Push.into()).unwrap();
+        assert_eq!(turnstile.name(), "Locked");
+    }
+
+    #[test]
+    fn traffic_light_cycles_until_a_fault() {
+        let mut light = TrafficLight::default();
+        for name in ["Green", "Yellow", "Red"] {
+            light = light.next(Tick.into()).unwrap();
+            assert_eq!(light.name(), name);
+        }
+        let light = light.next(Fault.into()).unwrap();
+        assert_eq!(light.name(), "Blinking");
+        let e = light.next(Tick.into()).unwrap_err();
+        assert_eq!(e.expected, ["Reset"]);
+        let light = e.state;
+        let light = light.next(Reset.into()).unwrap();
+        assert_eq!(light.name(), "Red");
+    }
+}
@@ -1,6 +1,449 @@
-pub use macro_impl::state_machine;
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate self as state_machine;
+
+#[cfg(feature = "defmt")]
+#[doc(hidden)]
+pub use defmt;
+#[cfg(feature = "either")]
+pub use either::Either;
+pub use macro_impl::{
+    state_machine, state_machine_fragment, state_machine_from_scxml, state_machine_template,
+};
+#[cfg(feature = "petgraph")]
+#[doc(hidden)]
+pub use petgraph;
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+pub use serde;
+
+/// Stamps out a machine from a [`state_machine_template!`], giving a name to each of its
+/// parameters: `instantiate_machine!(Template, states = { P1 = A, P2 = B }, Wrapper, ActionWrapper)`.
+#[macro_export]
+macro_rules! instantiate_machine {
+    ($template:ident, states = { $($params:tt)* }, $($wrappers:tt)*) => {
+        $template! { $($params)*; $($wrappers)* }
+    };
+}
+
+#[cfg(feature = "std")]
+pub mod automaton;
+pub mod blend;
+#[cfg(feature = "heapless")]
+pub mod bounded;
+#[cfg(feature = "std")]
+pub mod bus;
+#[cfg(feature = "std")]
+pub mod chaos;
+#[cfg(feature = "std")]
+pub mod deferred;
+#[cfg(feature = "std")]
+pub mod errors;
+#[cfg(feature = "fixtures")]
+pub mod fixtures;
+#[cfg(feature = "defmt")]
+pub mod logging;
+#[cfg(any(feature = "http", feature = "tcp"))]
+pub mod machines;
+#[cfg(feature = "std")]
+pub mod pausable;
+#[cfg(feature = "persist")]
+pub mod persist;
+pub mod queue;
+#[cfg(feature = "either")]
+pub mod race;
+pub mod remote;
+#[cfg(feature = "std")]
+pub mod repl;
+#[cfg(feature = "rollback")]
+pub mod rollback;
+#[cfg(feature = "serde")]
+pub mod snapshot;
+#[cfg(feature = "std")]
+pub mod soak;
+#[cfg(feature = "std")]
+pub mod store;
+#[cfg(feature = "std")]
+pub mod timers;
+#[cfg(feature = "tui")]
+pub mod tui;
+#[cfg(feature = "std")]
+pub mod wizard;
 
 pub trait Action {}
 pub trait State<W, A: Action> {
     fn next(self, action: A) -> W;
 }
+
+/// Same as [`State`] for `async` machines, whose handlers can await. Implementations can be
+/// written as `async fn next`.
+pub trait AsyncState<W, A: Action> {
+    fn next(self, action: A) -> impl core::future::Future<Output = W>;
+}
+
+/// Same as [`State`] for machines declaring a `ctx Type`: the handler also gets the context shared
+/// by all states, given to the generated `next`.
+pub trait ContextState<W, A: Action, C> {
+    fn next(self, action: A, ctx: &mut C) -> W;
+}
+
+/// Implemented by the states declaring a `submachine M`, which hold the child machine: the actions
+/// listed for it step the child first, and only reach the state's own transitions if the child
+/// rejects them. The child is moved out while it steps, leaving a default one in its place.
+pub trait Submachine<M: Machine + Default> {
+    fn child(&self) -> &M;
+
+    fn child_mut(&mut self) -> &mut M;
+}
+
+/// Action handled by a state declaring `submachine M => Next` once its child reaches a terminal
+/// state, holding the finished child.
+#[derive(Debug)]
+pub struct Done<M>(pub M);
+
+impl<M> Action for Done<M> {}
+
+/// Handle of a machine added to a `Bus`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MachineId(usize);
+
+/// Told about every step of a machine driven through its generated `next_observed`, by state and
+/// action names.
+pub trait TransitionObserver {
+    fn on_transition(&mut self, from: &'static str, action: &'static str, to: &'static str);
+
+    fn on_rejection(&mut self, _state: &'static str, _action: &'static str) {}
+}
+
+/// The payload given to a validated action's `new` is not one the machine accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidAction {
+    pub action: &'static str,
+}
+
+impl core::fmt::Display for InvalidAction {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Invalid payload for action {}", self.action)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InvalidAction {}
+
+/// The text parsed into an action wrapper does not name one of its actions, or holds a payload
+/// that does not parse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseActionError {
+    UnknownAction,
+    InvalidPayload(InvalidAction),
+}
+
+impl core::fmt::Display for ParseActionError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ParseActionError::UnknownAction => write!(f, "Unknown action"),
+            ParseActionError::InvalidPayload(e) => e.fmt(f),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseActionError {}
+
+/// Used by the generated `FromStr` to parse each payload with `FromStr` if it implements it, else
+/// to build it with `Default` when no payload is given. The generated code calls `parse_payload`
+/// on a `&&&Payload<P>`: method resolution tries the impls from the most references down.
+#[doc(hidden)]
+pub mod __payload {
+    use core::marker::PhantomData;
+
+    pub struct Payload<P>(pub PhantomData<P>);
+
+    pub trait ViaFromStr<P> {
+        fn parse_payload(&self, text: &str) -> Option<P>;
+    }
+
+    impl<P: core::str::FromStr> ViaFromStr<P> for &&Payload<P> {
+        fn parse_payload(&self, text: &str) -> Option<P> {
+            text.parse().ok()
+        }
+    }
+
+    pub trait ViaDefault<P> {
+        fn parse_payload(&self, text: &str) -> Option<P>;
+    }
+
+    impl<P: Default> ViaDefault<P> for &Payload<P> {
+        fn parse_payload(&self, text: &str) -> Option<P> {
+            text.is_empty().then(P::default)
+        }
+    }
+
+    pub trait Unparsable<P> {
+        fn parse_payload(&self, text: &str) -> Option<P>;
+    }
+
+    impl<P> Unparsable<P> for Payload<P> {
+        fn parse_payload(&self, _text: &str) -> Option<P> {
+            None
+        }
+    }
+}
+
+/// Names of the actions a state would have taken instead of the one it rejected, given the
+/// experimental and optional transitions enabled for the call. Guards are assumed to be able to go
+/// either way.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct ExpectedActions {
+    names: &'static [&'static str],
+    mask: u128,
+}
+
+impl ExpectedActions {
+    /// `names[i]` for every bit `i` set in `mask`.
+    #[doc(hidden)]
+    pub const fn new(names: &'static [&'static str], mask: u128) -> ExpectedActions {
+        ExpectedActions { names, mask }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &'static str> + '_ {
+        let mask = self.mask;
+        self.names
+            .iter()
+            .enumerate()
+            .filter(move |(i, _)| mask & 1 << i != 0)
+            .map(|(_, name)| *name)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.iter().any(|n| n == name)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mask == 0
+    }
+}
+
+impl core::fmt::Debug for ExpectedActions {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<const N: usize> PartialEq<[&str; N]> for ExpectedActions {
+    fn eq(&self, other: &[&str; N]) -> bool {
+        self.iter().eq(other.iter().copied())
+    }
+}
+
+/// A handler went to a state its transition does not declare.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidTransition {
+    pub state: &'static str,
+    pub action: &'static str,
+    pub got: &'static str,
+    pub allowed: &'static [&'static str],
+}
+
+impl core::fmt::Display for InvalidTransition {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "For state {} and action {}, got wrong state {}, expected one of {:?}",
+            self.state, self.action, self.got, self.allowed
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InvalidTransition {}
+
+/// An action of the slice given to the generated `step_slice` was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepError {
+    /// Index of the rejected action in the slice, i.e. how many were applied before it.
+    pub index: usize,
+    pub state: &'static str,
+    pub action: &'static str,
+    /// With `on_invalid_transition Err`, set when the handler went to a state its transition
+    /// does not declare. `state` is then the one it went to.
+    pub invalid: Option<InvalidTransition>,
+    /// Where the rejected character or byte is, for the errors of `step_str` and `step_bytes`.
+    pub position: Option<Position>,
+}
+
+impl core::fmt::Display for StepError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match &self.position {
+            Some(position) => write!(f, "Line {}, column {}", position.line, position.column)?,
+            None => write!(f, "Action {}", self.index)?,
+        }
+        match &self.invalid {
+            Some(invalid) => write!(f, ": {}", invalid),
+            None => write!(f, ": state {} rejected action {}", self.state, self.action),
+        }
+    }
+}
+
+/// Position of a character or byte in the input of a text or byte driver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    /// Starting at 1.
+    pub line: u32,
+    /// Starting at 1, counted in characters for text and in bytes otherwise.
+    pub column: u32,
+    /// Byte offset from the start of the input.
+    pub offset: u32,
+}
+
+impl Position {
+    /// Start of the input.
+    pub const START: Position = Position {
+        line: 1,
+        column: 1,
+        offset: 0,
+    };
+
+    /// Position of what follows this character or byte, `len` bytes long, a newline or not.
+    #[doc(hidden)]
+    pub fn after(self, len: u32, newline: bool) -> Position {
+        let offset = self.offset + len;
+        if newline {
+            Position {
+                line: self.line + 1,
+                column: 1,
+                offset,
+            }
+        } else {
+            Position {
+                column: self.column + 1,
+                offset,
+                ..self
+            }
+        }
+    }
+}
+
+#[cfg(feature = "codespan")]
+impl StepError {
+    /// Diagnostic pointing at the start of the rejected character or byte in `file`, for
+    /// `codespan-reporting` to render. Without a position, the diagnostic has no label.
+    pub fn to_diagnostic<FileId>(
+        &self,
+        file: FileId,
+    ) -> codespan_reporting::diagnostic::Diagnostic<FileId> {
+        use codespan_reporting::diagnostic::{Diagnostic, Label};
+
+        let (message, label) = match &self.invalid {
+            Some(invalid) => (
+                std::format!(
+                    "state {} went to {} on action {}",
+                    invalid.state,
+                    invalid.got,
+                    invalid.action
+                ),
+                std::format!("expected one of {:?}", invalid.allowed),
+            ),
+            None => (
+                std::format!("state {} rejected action {}", self.state, self.action),
+                std::format!("rejected by {}", self.state),
+            ),
+        };
+        let diagnostic = Diagnostic::error().with_message(message);
+        match &self.position {
+            Some(position) => diagnostic.with_labels(std::vec![Label::primary(
+                file,
+                position.offset as usize..position.offset as usize
+            )
+            .with_message(label)]),
+            None => diagnostic,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for StepError {}
+
+/// Default `on_invalid_transition` policy.
+pub fn panic_on_invalid_transition(error: InvalidTransition) {
+    panic!("{}", error)
+}
+
+/// `on_invalid_transition` policy reporting the error on stderr and keeping the state the
+/// handler went to.
+#[cfg(feature = "std")]
+pub fn log_invalid_transition(error: InvalidTransition) {
+    eprintln!("{}", error)
+}
+
+/// Entry and exit hooks, for machines declaring `hooks`: every state then implements it, and
+/// the generated `next` calls `on_exit` on the old state before the handler and `on_enter` on the
+/// new one after it. The initial state built by `new` is entered as well.
+pub trait StateHooks {
+    fn on_enter(&mut self) {}
+    fn on_exit(&mut self) {}
+}
+
+/// Implemented by every state wrapper generated by [`state_machine!`], so utilities can be
+/// written once for any machine.
+pub trait Machine: Sized {
+    type Action;
+    /// Given back when the current state rejects an action, holding the machine and the action.
+    type Error: Into<(Self, Self::Action)>;
+
+    fn next(self, action: Self::Action) -> Result<Self, Self::Error>;
+
+    /// Whether the current state declares `action` as deferred, to be kept aside by the runner
+    /// and applied once the machine reaches a state accepting it.
+    fn is_deferred(&self, _action: &Self::Action) -> bool {
+        false
+    }
+
+    /// Same as [`next`](Machine::next), also taking the transitions marked as `experimental`.
+    fn next_experimental(self, action: Self::Action) -> Result<Self, Self::Error> {
+        self.next(action)
+    }
+
+    /// Whether the machine reached a terminal state.
+    fn is_finished(&self) -> bool {
+        false
+    }
+
+    /// Whether the machine rests in a state declared `idle`, waiting for an action from outside.
+    fn is_idle(&self) -> bool {
+        false
+    }
+
+    /// Names of the actions the current state accepts.
+    fn allowed_actions(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Name of the current state, the machine's type name if it has no states to tell apart.
+    fn name(&self) -> &'static str {
+        core::any::type_name::<Self>()
+    }
+
+    /// Name of the active state `depth` levels down the submachines of the active states,
+    /// `active_state(0)` being [`name`](Machine::name). `None` below the innermost machine.
+    fn active_state(&self, depth: usize) -> Option<&'static str> {
+        (depth == 0).then(|| self.name())
+    }
+
+    /// Steps through `actions` in order. On rejection, returns the error, holding the machine as
+    /// it was and the rejected action, along with its index in `actions`; the remaining actions
+    /// are not consumed.
+    #[inline]
+    fn step_all<I>(mut self, actions: I) -> Result<Self, (Self::Error, usize)>
+    where
+        I: IntoIterator<Item = Self::Action>,
+    {
+        for (index, action) in actions.into_iter().enumerate() {
+            self = match self.next(action) {
+                Ok(state) => state,
+                Err(e) => return Err((e, index)),
+            };
+        }
+        Ok(self)
+    }
+}
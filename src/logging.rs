@@ -0,0 +1,19 @@
+//! Transition logging over [defmt](https://defmt.ferrous-systems.com), for targets logging
+//! through a debug probe.
+
+use crate::TransitionObserver;
+
+/// Observer logging every step of a machine driven through its generated `next_observed`:
+/// transitions at the `debug` level, rejections at the `warn` level.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DefmtLogger;
+
+impl TransitionObserver for DefmtLogger {
+    fn on_transition(&mut self, from: &'static str, action: &'static str, to: &'static str) {
+        defmt::debug!("{=str} --{=str}--> {=str}", from, action, to);
+    }
+
+    fn on_rejection(&mut self, state: &'static str, action: &'static str) {
+        defmt::warn!("{=str} rejected {=str}", state, action);
+    }
+}
@@ -0,0 +1,4 @@
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(feature = "tcp")]
+pub mod tcp;
@@ -0,0 +1,243 @@
+//! HTTP/1.1 request head parser (request line and header fields), driven one byte at a time.
+//!
+//! Bytes are classified into [`HttpByte`] actions through `From<u8>`; the parser only checks the
+//! shape of the head, not the validity of methods, targets or header names. The message body,
+//! if any, is left to the caller once [`Complete`] is reached.
+//!
+//! Each field is kept as raw bytes until the byte ending it, and only decoded then: a field that
+//! is not valid UTF-8 has that byte rejected.
+
+use crate::{state_machine, Action, State};
+
+#[derive(Debug, Default)]
+pub struct Request {
+    pub method: String,
+    pub target: String,
+    pub version: String,
+    pub headers: Vec<(String, String)>,
+}
+
+// The states reading a field hold its raw bytes so far.
+#[derive(Debug, Default)]
+pub struct Method(Request, Vec<u8>);
+#[derive(Debug)]
+pub struct Target(Request, Vec<u8>);
+#[derive(Debug)]
+pub struct Version(Request, Vec<u8>);
+#[derive(Debug)]
+pub struct RequestLineEnd(Request);
+#[derive(Debug)]
+pub struct HeaderStart(Request);
+#[derive(Debug)]
+pub struct HeaderName(Request, Vec<u8>);
+#[derive(Debug)]
+pub struct HeaderValue(Request, Vec<u8>);
+#[derive(Debug)]
+pub struct HeaderLineEnd(Request);
+#[derive(Debug)]
+pub struct HeadersEnd(Request);
+#[derive(Debug)]
+pub struct Complete(pub Request);
+
+#[derive(Debug)]
+pub struct Token(pub u8);
+#[derive(Debug)]
+pub struct Sp;
+#[derive(Debug)]
+pub struct Colon;
+#[derive(Debug)]
+pub struct Cr;
+#[derive(Debug)]
+pub struct Lf;
+
+state_machine! {
+    pub HttpRequest,
+    pub HttpByte,
+    initial Method,
+    terminal Complete,
+    Method {
+        Token => Method,
+        Sp if |s: &Method, _: &Sp| is_utf8(&s.1) => Target
+    },
+    Target {
+        Token | Colon => Target,
+        Sp if |s: &Target, _: &Sp| is_utf8(&s.1) => Version
+    },
+    Version {
+        Token => Version,
+        Cr if |s: &Version, _: &Cr| is_utf8(&s.1) => RequestLineEnd
+    },
+    RequestLineEnd { Lf => HeaderStart },
+    HeaderStart {
+        Token => HeaderName,
+        Cr => HeadersEnd
+    },
+    HeaderName {
+        Token => HeaderName,
+        Colon if |s: &HeaderName, _: &Colon| is_utf8(&s.1) => HeaderValue
+    },
+    HeaderValue {
+        Token | Sp | Colon => HeaderValue,
+        Cr if |s: &HeaderValue, _: &Cr| is_utf8(&s.1) => HeaderLineEnd
+    },
+    HeaderLineEnd { Lf => HeaderStart },
+    HeadersEnd { Lf => Complete },
+}
+
+impl From<u8> for HttpByte {
+    fn from(b: u8) -> HttpByte {
+        match b {
+            b' ' => Sp.into(),
+            b':' => Colon.into(),
+            b'\r' => Cr.into(),
+            b'\n' => Lf.into(),
+            b => Token(b).into(),
+        }
+    }
+}
+
+/// Runs `input` through the parser, returning the request once its head has been fully read.
+pub fn parse(input: &[u8]) -> Option<Request> {
+    let mut state = HttpRequest::new();
+    for &b in input {
+        state = state.next(b.into()).ok()?;
+    }
+
+    state.into_terminal().ok().map(|Complete(request)| request)
+}
+
+fn is_utf8(field: &[u8]) -> bool {
+    core::str::from_utf8(field).is_ok()
+}
+
+/// Field checked by the guard of the transition ending it.
+fn decode(field: Vec<u8>) -> String {
+    String::from_utf8(field).expect("field checked by the transition guard")
+}
+
+impl State<HttpRequest, Token> for Method {
+    fn next(mut self, action: Token) -> HttpRequest {
+        self.1.push(action.0);
+        self.into()
+    }
+}
+
+impl State<HttpRequest, Sp> for Method {
+    fn next(mut self, _action: Sp) -> HttpRequest {
+        self.0.method = decode(self.1);
+        Target(self.0, Vec::new()).into()
+    }
+}
+
+impl State<HttpRequest, Token> for Target {
+    fn next(mut self, action: Token) -> HttpRequest {
+        self.1.push(action.0);
+        self.into()
+    }
+}
+
+impl State<HttpRequest, Colon> for Target {
+    fn next(mut self, _action: Colon) -> HttpRequest {
+        self.1.push(b':');
+        self.into()
+    }
+}
+
+impl State<HttpRequest, Sp> for Target {
+    fn next(mut self, _action: Sp) -> HttpRequest {
+        self.0.target = decode(self.1);
+        Version(self.0, Vec::new()).into()
+    }
+}
+
+impl State<HttpRequest, Token> for Version {
+    fn next(mut self, action: Token) -> HttpRequest {
+        self.1.push(action.0);
+        self.into()
+    }
+}
+
+impl State<HttpRequest, Cr> for Version {
+    fn next(mut self, _action: Cr) -> HttpRequest {
+        self.0.version = decode(self.1);
+        RequestLineEnd(self.0).into()
+    }
+}
+
+impl State<HttpRequest, Lf> for RequestLineEnd {
+    fn next(self, _action: Lf) -> HttpRequest {
+        HeaderStart(self.0).into()
+    }
+}
+
+impl State<HttpRequest, Token> for HeaderStart {
+    fn next(self, action: Token) -> HttpRequest {
+        HeaderName(self.0, vec![action.0]).into()
+    }
+}
+
+impl State<HttpRequest, Cr> for HeaderStart {
+    fn next(self, _action: Cr) -> HttpRequest {
+        HeadersEnd(self.0).into()
+    }
+}
+
+impl State<HttpRequest, Token> for HeaderName {
+    fn next(mut self, action: Token) -> HttpRequest {
+        self.1.push(action.0);
+        self.into()
+    }
+}
+
+impl State<HttpRequest, Colon> for HeaderName {
+    fn next(mut self, _action: Colon) -> HttpRequest {
+        self.0.headers.push((decode(self.1), String::new()));
+        HeaderValue(self.0, Vec::new()).into()
+    }
+}
+
+impl State<HttpRequest, Token> for HeaderValue {
+    fn next(mut self, action: Token) -> HttpRequest {
+        self.1.push(action.0);
+        self.into()
+    }
+}
+
+impl State<HttpRequest, Sp> for HeaderValue {
+    fn next(mut self, _action: Sp) -> HttpRequest {
+        // Leading whitespace is not part of the field value.
+        if !self.1.is_empty() {
+            self.1.push(b' ');
+        }
+        self.into()
+    }
+}
+
+impl State<HttpRequest, Colon> for HeaderValue {
+    fn next(mut self, _action: Colon) -> HttpRequest {
+        self.1.push(b':');
+        self.into()
+    }
+}
+
+impl State<HttpRequest, Cr> for HeaderValue {
+    fn next(mut self, _action: Cr) -> HttpRequest {
+        // Neither is trailing whitespace.
+        let trimmed_len = self.1.trim_ascii_end().len();
+        self.1.truncate(trimmed_len);
+        self.0.headers.last_mut().unwrap().1 = decode(self.1);
+        HeaderLineEnd(self.0).into()
+    }
+}
+
+impl State<HttpRequest, Lf> for HeaderLineEnd {
+    fn next(self, _action: Lf) -> HttpRequest {
+        HeaderStart(self.0).into()
+    }
+}
+
+impl State<HttpRequest, Lf> for HeadersEnd {
+    fn next(self, _action: Lf) -> HttpRequest {
+        Complete(self.0).into()
+    }
+}
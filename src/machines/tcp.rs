@@ -0,0 +1,115 @@
+//! TCP connection lifecycle, following the state diagram of RFC 793.
+//!
+//! Neither states nor events carry data: the machine only tracks where a connection is in its
+//! lifecycle, sending and acknowledging segments is left to the caller.
+
+use crate::{state_machine, Action, State};
+
+#[derive(Debug, Default)]
+pub struct Closed;
+#[derive(Debug)]
+pub struct Listen;
+#[derive(Debug)]
+pub struct SynSent;
+#[derive(Debug)]
+pub struct SynReceived;
+#[derive(Debug)]
+pub struct Established;
+#[derive(Debug)]
+pub struct FinWait1;
+#[derive(Debug)]
+pub struct FinWait2;
+#[derive(Debug)]
+pub struct CloseWait;
+#[derive(Debug)]
+pub struct Closing;
+#[derive(Debug)]
+pub struct LastAck;
+#[derive(Debug)]
+pub struct TimeWait;
+
+#[derive(Debug)]
+pub struct PassiveOpen;
+#[derive(Debug)]
+pub struct ActiveOpen;
+#[derive(Debug)]
+pub struct Close;
+#[derive(Debug)]
+pub struct RecvSyn;
+#[derive(Debug)]
+pub struct RecvSynAck;
+#[derive(Debug)]
+pub struct RecvAck;
+#[derive(Debug)]
+pub struct RecvFin;
+#[derive(Debug)]
+pub struct Timeout;
+
+state_machine! {
+    pub TcpConnection,
+    pub TcpEvent,
+    initial Closed,
+    Closed {
+        PassiveOpen => Listen,
+        ActiveOpen => SynSent
+    },
+    Listen {
+        RecvSyn => SynReceived,
+        Close => Closed
+    },
+    SynSent {
+        RecvSynAck => Established,
+        RecvSyn => SynReceived,
+        Close => Closed
+    },
+    SynReceived {
+        RecvAck => Established,
+        Close => FinWait1
+    },
+    Established {
+        Close => FinWait1,
+        RecvFin => CloseWait
+    },
+    FinWait1 {
+        RecvAck => FinWait2,
+        RecvFin => Closing
+    },
+    FinWait2 { RecvFin => TimeWait },
+    CloseWait { Close => LastAck },
+    Closing { RecvAck => TimeWait },
+    LastAck { RecvAck => Closed },
+    TimeWait { Timeout => Closed },
+}
+
+macro_rules! transitions {
+    ($($from:ident + $event:ident => $to:ident,)*) => {
+        $(
+            impl State<TcpConnection, $event> for $from {
+                fn next(self, _event: $event) -> TcpConnection {
+                    $to.into()
+                }
+            }
+        )*
+    };
+}
+
+transitions! {
+    Closed + PassiveOpen => Listen,
+    Closed + ActiveOpen => SynSent,
+    Listen + RecvSyn => SynReceived,
+    Listen + Close => Closed,
+    SynSent + RecvSynAck => Established,
+    SynSent + RecvSyn => SynReceived,
+    SynSent + Close => Closed,
+    SynReceived + RecvAck => Established,
+    SynReceived + Close => FinWait1,
+    Established + Close => FinWait1,
+    Established + RecvFin => CloseWait,
+    FinWait1 + RecvAck => FinWait2,
+    FinWait1 + RecvFin => Closing,
+    FinWait2 + RecvFin => TimeWait,
+    CloseWait + Close => LastAck,
+    Closing + RecvAck => TimeWait,
+    LastAck + RecvAck => Closed,
+    TimeWait + Timeout => Closed,
+}
@@ -0,0 +1,177 @@
+use std::collections::VecDeque;
+
+use crate::Machine;
+
+/// Machine that can be frozen: actions sent while paused are buffered until it is resumed or
+/// advanced manually with [`step_once`](Pausable::step_once).
+pub struct Pausable<M: Machine> {
+    // Only ever `None` while an action is being applied.
+    state: Option<M>,
+    paused: bool,
+    pending: VecDeque<M::Action>,
+}
+
+impl<M: Machine> Pausable<M> {
+    pub fn new(state: M) -> Self {
+        Pausable {
+            state: Some(state),
+            paused: false,
+            pending: VecDeque::new(),
+        }
+    }
+
+    pub fn state(&self) -> &M {
+        self.state
+            .as_ref()
+            .expect("Pausable poisoned by a panicking handler")
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Actions received while paused, oldest first.
+    pub fn pending(&self) -> impl Iterator<Item = &M::Action> {
+        self.pending.iter()
+    }
+
+    /// Applies `action` right away, or buffers it if paused. Rejected actions are given back.
+    pub fn send(&mut self, action: M::Action) -> Result<(), M::Action> {
+        if self.paused {
+            self.pending.push_back(action);
+            Ok(())
+        } else {
+            self.apply(action)
+        }
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Applies the buffered actions in order and goes back to applying actions as they are sent.
+    /// Returns the actions that were rejected along the way.
+    pub fn resume(&mut self) -> Vec<M::Action> {
+        self.paused = false;
+        let mut rejected = Vec::new();
+        while let Some(result) = self.step_once() {
+            if let Err(action) = result {
+                rejected.push(action);
+            }
+        }
+        rejected
+    }
+
+    /// Applies the oldest buffered action, if any, without resuming.
+    pub fn step_once(&mut self) -> Option<Result<(), M::Action>> {
+        let action = self.pending.pop_front()?;
+        Some(self.apply(action))
+    }
+
+    pub fn into_inner(self) -> M {
+        self.state
+            .expect("Pausable poisoned by a panicking handler")
+    }
+
+    fn apply(&mut self, action: M::Action) -> Result<(), M::Action> {
+        let state = self
+            .state
+            .take()
+            .expect("Pausable poisoned by a panicking handler");
+        let (state, result) = match state.next(action).map_err(Into::into) {
+            Ok(state) => (state, Ok(())),
+            Err((state, action)) => (state, Err(action)),
+        };
+        self.state = Some(state);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{state_machine, Action, State};
+
+    #[derive(Debug)]
+    struct Count(u32);
+    #[derive(Debug)]
+    struct Stopped(u32);
+
+    #[derive(Debug)]
+    struct Add(u32);
+    #[derive(Debug)]
+    struct Stop;
+
+    state_machine! {
+        Counter,
+        CounterAction,
+        terminal Stopped,
+        Count {
+            Add => Count,
+            Stop => Stopped,
+        },
+        Stopped {},
+    }
+
+    impl State<Counter, Add> for Count {
+        fn next(self, action: Add) -> Counter {
+            Count(self.0 + action.0).into()
+        }
+    }
+
+    impl State<Counter, Stop> for Count {
+        fn next(self, _action: Stop) -> Counter {
+            Stopped(self.0).into()
+        }
+    }
+
+    fn count(pausable: &Pausable<Counter>) -> u32 {
+        match pausable.state() {
+            Counter::Count(Count(n)) | Counter::Stopped(Stopped(n)) => *n,
+        }
+    }
+
+    #[test]
+    fn buffers_actions_while_paused() {
+        let mut pausable = Pausable::new(Counter::from(Count(0)));
+        pausable.send(Add(1).into()).unwrap();
+        pausable.pause();
+        assert!(pausable.is_paused());
+        pausable.send(Add(2).into()).unwrap();
+        pausable.send(Add(3).into()).unwrap();
+        assert_eq!(count(&pausable), 1);
+        assert_eq!(pausable.pending().count(), 2);
+
+        assert!(matches!(pausable.step_once(), Some(Ok(()))));
+        assert_eq!(count(&pausable), 3);
+        assert!(pausable.is_paused());
+
+        assert!(pausable.resume().is_empty());
+        assert!(!pausable.is_paused());
+        assert_eq!(count(&pausable), 6);
+        assert!(pausable.step_once().is_none());
+    }
+
+    #[test]
+    fn gives_back_what_was_rejected_on_resume() {
+        let mut pausable = Pausable::new(Counter::from(Count(0)));
+        pausable.pause();
+        pausable.send(Add(2).into()).unwrap();
+        pausable.send(Stop.into()).unwrap();
+        pausable.send(Add(3).into()).unwrap();
+
+        let rejected = pausable.resume();
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(rejected[0].name(), "Add");
+        assert_eq!(pausable.into_inner().name(), "Stopped");
+    }
+
+    #[test]
+    fn applies_actions_right_away_when_not_paused() {
+        let mut pausable = Pausable::new(Counter::from(Count(0)));
+        pausable.send(Stop.into()).unwrap();
+        assert_eq!(pausable.send(Add(1).into()).unwrap_err().name(), "Add");
+        assert_eq!(pausable.pending().count(), 0);
+    }
+}
@@ -0,0 +1,351 @@
+//! Compact, checksummed records of a machine's state, encoded with postcard and written to
+//! flash or EEPROM.
+//!
+//! Records go to the slots of a [`Storage`] in turn, each one after the slot holding the latest
+//! record: the wear is spread over all the slots, and a write cut short by a power loss leaves
+//! the previous record to be restored. A record is laid out as:
+//!
+//! | bytes | content                                                |
+//! |-------|--------------------------------------------------------|
+//! | 2     | magic, `b"SM"`                                         |
+//! | 4     | sequence number, one more than the previous record's   |
+//! | 8     | hash of the machine definition                         |
+//! | 2     | length of the payload                                  |
+//! | n     | postcard encoding of the state wrapper                 |
+//! | 4     | CRC-32 of all the above                                |
+//!
+//! Integers are little endian.
+
+use core::fmt;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::snapshot::IncompatibleSnapshot;
+
+const MAGIC: [u8; 2] = *b"SM";
+const HEADER_LEN: usize = 16;
+const CRC_LEN: usize = 4;
+
+/// Flash or EEPROM divided into slots, each able to hold one record.
+pub trait Storage {
+    type Error;
+
+    /// Number of slots records are written to in turn.
+    fn slot_count(&self) -> usize;
+
+    /// Contents of `slot`, as last written or erased.
+    fn read(&mut self, slot: usize) -> Result<&[u8], Self::Error>;
+
+    /// Calls `fill` with a buffer the size of a slot. If it returns the length of the record it
+    /// put there, erases `slot` and writes those bytes to it; if it returns `None`, leaves `slot`
+    /// as it is.
+    fn write(
+        &mut self,
+        slot: usize,
+        fill: &mut dyn FnMut(&mut [u8]) -> Option<usize>,
+    ) -> Result<(), Self::Error>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PersistError<E> {
+    Storage(E),
+    /// The encoded state does not fit in a slot.
+    TooLarge,
+    /// No slot holds a record.
+    NotFound,
+    /// The latest record was written by a different definition of the machine.
+    Incompatible(IncompatibleSnapshot),
+    /// The latest record passed its checksum but does not decode to a state of the machine.
+    Undecodable,
+}
+
+impl<E: fmt::Display> fmt::Display for PersistError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PersistError::Storage(e) => write!(f, "Storage failed: {}", e),
+            PersistError::TooLarge => write!(f, "State too large for a storage slot"),
+            PersistError::NotFound => write!(f, "No state persisted"),
+            PersistError::Incompatible(e) => e.fmt(f),
+            PersistError::Undecodable => write!(f, "Persisted state cannot be decoded"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Debug + fmt::Display> std::error::Error for PersistError<E> {}
+
+/// Implemented by the generated state wrappers with the `persist` feature.
+pub trait Persistent: Sized {
+    const MACHINE_HASH: u64;
+
+    /// Writes the state to the slot following the one holding the latest record.
+    fn persist<S: Storage>(&self, storage: &mut S) -> Result<(), PersistError<S::Error>>
+    where
+        Self: Serialize,
+    {
+        let (slot, sequence) = match latest(storage)? {
+            Some((slot, header)) => ((slot + 1) % storage.slot_count(), header.sequence + 1),
+            None => (0, 0),
+        };
+
+        let mut fits = true;
+        storage
+            .write(slot, &mut |buf| {
+                let len = encode(buf, sequence, Self::MACHINE_HASH, self);
+                fits = len.is_some();
+                len
+            })
+            .map_err(PersistError::Storage)?;
+        if fits {
+            Ok(())
+        } else {
+            Err(PersistError::TooLarge)
+        }
+    }
+
+    /// Reads the state back from the latest record. Records cut short or corrupted are skipped.
+    ///
+    /// The generated wrappers have an inherent `restore` taking a snapshot, so this one is called
+    /// as `Persistent::restore(&mut storage)`.
+    fn restore<S: Storage>(storage: &mut S) -> Result<Self, PersistError<S::Error>>
+    where
+        Self: DeserializeOwned,
+    {
+        let (slot, header) = latest(storage)?.ok_or(PersistError::NotFound)?;
+        if header.machine_hash != Self::MACHINE_HASH {
+            return Err(PersistError::Incompatible(IncompatibleSnapshot {
+                expected_hash: Self::MACHINE_HASH,
+                found_hash: header.machine_hash,
+            }));
+        }
+        let record = storage.read(slot).map_err(PersistError::Storage)?;
+        let payload = &record[HEADER_LEN..HEADER_LEN + header.len];
+        postcard::from_bytes(payload).map_err(|_| PersistError::Undecodable)
+    }
+}
+
+struct Header {
+    sequence: u32,
+    machine_hash: u64,
+    len: usize,
+}
+
+/// Slot holding the valid record with the highest sequence number.
+fn latest<S: Storage>(storage: &mut S) -> Result<Option<(usize, Header)>, PersistError<S::Error>> {
+    let mut latest: Option<(usize, Header)> = None;
+    for slot in 0..storage.slot_count() {
+        let record = storage.read(slot).map_err(PersistError::Storage)?;
+        if let Some(header) = decode_header(record) {
+            if latest
+                .as_ref()
+                .is_none_or(|(_, l)| header.sequence > l.sequence)
+            {
+                latest = Some((slot, header));
+            }
+        }
+    }
+    Ok(latest)
+}
+
+fn decode_header(record: &[u8]) -> Option<Header> {
+    if record.len() < HEADER_LEN + CRC_LEN || record[..2] != MAGIC {
+        return None;
+    }
+    let len = usize::from(u16::from_le_bytes(record[14..16].try_into().unwrap()));
+    let end = HEADER_LEN + len;
+    if record.len() < end + CRC_LEN {
+        return None;
+    }
+    let crc = u32::from_le_bytes(record[end..end + CRC_LEN].try_into().unwrap());
+    if crc32(&record[..end]) != crc {
+        return None;
+    }
+    Some(Header {
+        sequence: u32::from_le_bytes(record[2..6].try_into().unwrap()),
+        machine_hash: u64::from_le_bytes(record[6..14].try_into().unwrap()),
+        len,
+    })
+}
+
+/// Lays the record out in `buf`, returning its length, or `None` if it does not fit.
+fn encode(
+    buf: &mut [u8],
+    sequence: u32,
+    machine_hash: u64,
+    state: &impl Serialize,
+) -> Option<usize> {
+    if buf.len() < HEADER_LEN + CRC_LEN {
+        return None;
+    }
+    let payload_end = (buf.len() - CRC_LEN).min(HEADER_LEN + usize::from(u16::MAX));
+    let len = postcard::to_slice(state, &mut buf[HEADER_LEN..payload_end])
+        .ok()?
+        .len();
+    buf[..2].copy_from_slice(&MAGIC);
+    buf[2..6].copy_from_slice(&sequence.to_le_bytes());
+    buf[6..14].copy_from_slice(&machine_hash.to_le_bytes());
+    buf[14..16].copy_from_slice(&(len as u16).to_le_bytes());
+    let end = HEADER_LEN + len;
+    let crc = crc32(&buf[..end]);
+    buf[end..end + CRC_LEN].copy_from_slice(&crc.to_le_bytes());
+    Some(end + CRC_LEN)
+}
+
+/// CRC-32 as used by Ethernet and zlib, computed bit by bit to spare the table.
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |crc, &byte| {
+        (0..8).fold(crc ^ u32::from(byte), |crc, _| {
+            (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg())
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+
+    use std::vec;
+    use std::vec::Vec;
+
+    use serde::Deserialize;
+
+    use crate::{state_machine, Action, State};
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Idle;
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Brewing(u16);
+
+    #[derive(Debug)]
+    struct Brew(u16);
+    #[derive(Debug)]
+    struct Done;
+
+    state_machine! {
+        #[derive(Debug)] Coffee,
+        #[derive(Debug)] CoffeeAction,
+        Idle { Brew => Brewing },
+        Brewing { Done => Idle },
+    }
+
+    impl State<Coffee, Brew> for Idle {
+        fn next(self, action: Brew) -> Coffee {
+            Brewing(action.0).into()
+        }
+    }
+
+    impl State<Coffee, Done> for Brewing {
+        fn next(self, _action: Done) -> Coffee {
+            Idle.into()
+        }
+    }
+
+    // Erased flash reads as all ones.
+    struct Flash {
+        slots: Vec<Vec<u8>>,
+        writes: Vec<usize>,
+    }
+
+    impl Flash {
+        fn new(slot_count: usize, slot_len: usize) -> Self {
+            Flash {
+                slots: vec![vec![0xff; slot_len]; slot_count],
+                writes: vec![0; slot_count],
+            }
+        }
+    }
+
+    impl Storage for Flash {
+        type Error = ();
+
+        fn slot_count(&self) -> usize {
+            self.slots.len()
+        }
+
+        fn read(&mut self, slot: usize) -> Result<&[u8], ()> {
+            Ok(&self.slots[slot])
+        }
+
+        fn write(
+            &mut self,
+            slot: usize,
+            fill: &mut dyn FnMut(&mut [u8]) -> Option<usize>,
+        ) -> Result<(), ()> {
+            let mut buf = vec![0; self.slots[slot].len()];
+            if let Some(len) = fill(&mut buf) {
+                self.slots[slot].fill(0xff);
+                self.slots[slot][..len].copy_from_slice(&buf[..len]);
+                self.writes[slot] += 1;
+            }
+            Ok(())
+        }
+    }
+
+    fn brewing(coffee: &Coffee) -> u16 {
+        match coffee {
+            Coffee::Brewing(Brewing(cups)) => *cups,
+            Coffee::Idle(_) => panic!("not brewing"),
+        }
+    }
+
+    #[test]
+    fn checksums_match_the_usual_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn restores_the_latest_record() {
+        let mut flash = Flash::new(4, 32);
+        assert_eq!(
+            <Coffee as Persistent>::restore(&mut flash).unwrap_err(),
+            PersistError::NotFound
+        );
+
+        for cups in 1..=10 {
+            Coffee::from(Brewing(cups)).persist(&mut flash).unwrap();
+        }
+        let coffee: Coffee = Persistent::restore(&mut flash).unwrap();
+        assert_eq!(brewing(&coffee), 10);
+        assert_eq!(flash.writes, [3, 3, 2, 2]);
+    }
+
+    #[test]
+    fn skips_records_cut_short() {
+        let mut flash = Flash::new(2, 32);
+        Coffee::from(Brewing(1)).persist(&mut flash).unwrap();
+        Coffee::from(Brewing(2)).persist(&mut flash).unwrap();
+        flash.slots[1][HEADER_LEN] ^= 0x01;
+
+        let coffee: Coffee = Persistent::restore(&mut flash).unwrap();
+        assert_eq!(brewing(&coffee), 1);
+
+        Coffee::from(Idle).persist(&mut flash).unwrap();
+        assert_eq!(flash.writes, [1, 2]);
+        let coffee: Coffee = Persistent::restore(&mut flash).unwrap();
+        assert!(matches!(coffee, Coffee::Idle(_)));
+    }
+
+    #[test]
+    fn rejects_states_of_other_machines_and_too_large_ones() {
+        let mut flash = Flash::new(2, 32);
+        Coffee::from(Idle).persist(&mut flash).unwrap();
+        flash.slots[0][6] ^= 0x01;
+        let end = usize::from(flash.slots[0][14]) + HEADER_LEN;
+        let crc = crc32(&flash.slots[0][..end]);
+        flash.slots[0][end..end + CRC_LEN].copy_from_slice(&crc.to_le_bytes());
+        assert!(matches!(
+            <Coffee as Persistent>::restore(&mut flash),
+            Err(PersistError::Incompatible(_))
+        ));
+
+        let mut tiny = Flash::new(2, HEADER_LEN + CRC_LEN);
+        assert_eq!(
+            Coffee::from(Brewing(1)).persist(&mut tiny),
+            Err(PersistError::TooLarge)
+        );
+        assert_eq!(tiny.writes, [0, 0]);
+    }
+}
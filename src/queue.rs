@@ -0,0 +1,314 @@
+//! Fixed-capacity, lock-free single-producer single-consumer queue of actions.
+//!
+//! Meant to hand actions over from an interrupt handler to the main loop: the [`Producer`] never
+//! blocks nor allocates, and the [`Consumer`] drains the queued actions into a machine.
+
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::Machine;
+
+pub struct ActionQueue<A, const N: usize> {
+    buffer: [UnsafeCell<MaybeUninit<A>>; N],
+    // Both indices run over `0..2 * N`, the slot being `index % N`: this tells a full queue
+    // (indices `N` apart) from an empty one (equal indices) without wasting a slot.
+    head: AtomicUsize,
+    tail: AtomicUsize,
+}
+
+// The producer only ever writes to free slots, the consumer only reads queued ones.
+unsafe impl<A: Send, const N: usize> Sync for ActionQueue<A, N> {}
+
+impl<A, const N: usize> ActionQueue<A, N> {
+    fn increment(index: usize) -> usize {
+        (index + 1) % (2 * N)
+    }
+
+    fn len(head: usize, tail: usize) -> usize {
+        (tail + 2 * N - head) % (2 * N)
+    }
+
+    pub const fn new() -> Self {
+        const { assert!(N > 0, "ActionQueue needs a capacity of at least one action") };
+        ActionQueue {
+            buffer: [const { UnsafeCell::new(MaybeUninit::uninit()) }; N],
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    /// Splits the queue into its two ends. Taking `&mut self` guarantees there is only one of
+    /// each at a time.
+    pub fn split(&mut self) -> (Producer<'_, A, N>, Consumer<'_, A, N>) {
+        (Producer { queue: self }, Consumer { queue: self })
+    }
+}
+
+impl<A, const N: usize> Default for ActionQueue<A, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<A, const N: usize> Drop for ActionQueue<A, N> {
+    fn drop(&mut self) {
+        let head = *self.head.get_mut();
+        let tail = *self.tail.get_mut();
+        let mut index = head;
+        while index != tail {
+            // SAFETY: slots from `head` up to `tail` hold initialized actions.
+            unsafe { self.buffer[index % N].get_mut().assume_init_drop() };
+            index = Self::increment(index);
+        }
+    }
+}
+
+pub struct Producer<'a, A, const N: usize> {
+    queue: &'a ActionQueue<A, N>,
+}
+
+impl<A, const N: usize> Producer<'_, A, N> {
+    /// Queues `action`, giving it back if the queue is full.
+    pub fn enqueue(&mut self, action: A) -> Result<(), A> {
+        let tail = self.queue.tail.load(Ordering::Relaxed);
+        let head = self.queue.head.load(Ordering::Acquire);
+        if ActionQueue::<A, N>::len(head, tail) == N {
+            return Err(action);
+        }
+
+        // SAFETY: the queue is not full so the slot is free, the consumer will not read it until
+        // `tail` is published below.
+        unsafe { (*self.queue.buffer[tail % N].get()).write(action) };
+        self.queue
+            .tail
+            .store(ActionQueue::<A, N>::increment(tail), Ordering::Release);
+        Ok(())
+    }
+}
+
+pub struct Consumer<'a, A, const N: usize> {
+    queue: &'a ActionQueue<A, N>,
+}
+
+impl<A, const N: usize> Consumer<'_, A, N> {
+    pub fn dequeue(&mut self) -> Option<A> {
+        let head = self.queue.head.load(Ordering::Relaxed);
+        let tail = self.queue.tail.load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+
+        // SAFETY: the queue is not empty so the slot was initialized by the producer, which will
+        // not write to it again until `head` is published below.
+        let action = unsafe { (*self.queue.buffer[head % N].get()).assume_init_read() };
+        self.queue
+            .head
+            .store(ActionQueue::<A, N>::increment(head), Ordering::Release);
+        Some(action)
+    }
+
+    /// Steps `state` with every queued action, handing rejected ones to `on_rejected`.
+    pub fn drain_into<M>(&mut self, mut state: M, mut on_rejected: impl FnMut(&M, A)) -> M
+    where
+        M: Machine<Action = A>,
+    {
+        while let Some(action) = self.dequeue() {
+            state = match state.next(action).map_err(Into::into) {
+                Ok(state) => state,
+                Err((state, action)) => {
+                    on_rejected(&state, action);
+                    state
+                }
+            };
+        }
+        state
+    }
+}
+
+// Also meant to pass under `cargo +nightly miri test --lib queue`.
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+
+    use std::boxed::Box;
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::vec::Vec;
+
+    #[test]
+    fn dequeues_in_order() {
+        let mut queue = ActionQueue::<u32, 4>::new();
+        let (mut producer, mut consumer) = queue.split();
+        assert_eq!(consumer.dequeue(), None);
+        producer.enqueue(1).unwrap();
+        producer.enqueue(2).unwrap();
+        assert_eq!(consumer.dequeue(), Some(1));
+        producer.enqueue(3).unwrap();
+        assert_eq!(consumer.dequeue(), Some(2));
+        assert_eq!(consumer.dequeue(), Some(3));
+        assert_eq!(consumer.dequeue(), None);
+    }
+
+    #[test]
+    fn gives_back_actions_when_full() {
+        let mut queue = ActionQueue::<u32, 3>::new();
+        let (mut producer, mut consumer) = queue.split();
+        for i in 0..3 {
+            producer.enqueue(i).unwrap();
+        }
+        assert_eq!(producer.enqueue(3), Err(3));
+        assert_eq!(consumer.dequeue(), Some(0));
+        producer.enqueue(3).unwrap();
+        assert_eq!(producer.enqueue(4), Err(4));
+        for i in 1..4 {
+            assert_eq!(consumer.dequeue(), Some(i));
+        }
+        assert_eq!(consumer.dequeue(), None);
+    }
+
+    #[test]
+    fn wraps_around() {
+        let mut queue = ActionQueue::<usize, 3>::new();
+        let (mut producer, mut consumer) = queue.split();
+        // Goes several times over both the slots and the `0..2 * N` indices, at every fill level.
+        for round in 0..20 {
+            let fill = round % 3 + 1;
+            for i in 0..fill {
+                producer.enqueue(round * 10 + i).unwrap();
+            }
+            for i in 0..fill {
+                assert_eq!(consumer.dequeue(), Some(round * 10 + i));
+            }
+            assert_eq!(consumer.dequeue(), None);
+        }
+    }
+
+    #[test]
+    fn single_slot() {
+        let mut queue = ActionQueue::<u8, 1>::new();
+        let (mut producer, mut consumer) = queue.split();
+        for i in 0..5 {
+            producer.enqueue(i).unwrap();
+            assert_eq!(producer.enqueue(i), Err(i));
+            assert_eq!(consumer.dequeue(), Some(i));
+            assert_eq!(consumer.dequeue(), None);
+        }
+    }
+
+    struct Counted(Rc<Cell<usize>>);
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn drops_the_queued_actions_once() {
+        let drops = Rc::new(Cell::new(0));
+        let mut queue = ActionQueue::<Counted, 4>::new();
+        {
+            let (mut producer, mut consumer) = queue.split();
+            for _ in 0..6 {
+                producer.enqueue(Counted(drops.clone())).ok();
+                producer.enqueue(Counted(drops.clone())).ok();
+                drop(consumer.dequeue());
+            }
+        }
+        // 12 sent, 3 given back and dropped as the queue was full, 6 dequeued, 3 left queued.
+        assert_eq!(drops.get(), 9);
+        drop(queue);
+        assert_eq!(drops.get(), 12);
+    }
+
+    #[test]
+    fn drains_into_a_machine() {
+        use crate::{state_machine, Action, State};
+
+        #[derive(Debug)]
+        struct Count(u32);
+        #[derive(Debug)]
+        struct Add(u32);
+        #[derive(Debug)]
+        struct Stop;
+        #[derive(Debug)]
+        struct Stopped(u32);
+        #[derive(Debug)]
+        struct Resume;
+
+        state_machine! {
+            Counter,
+            CounterAction,
+            Count {
+                Add => Count,
+                Stop => Stopped,
+            },
+            Stopped { Resume => Count },
+        }
+
+        impl State<Counter, Add> for Count {
+            fn next(self, action: Add) -> Counter {
+                Count(self.0 + action.0).into()
+            }
+        }
+
+        impl State<Counter, Stop> for Count {
+            fn next(self, _action: Stop) -> Counter {
+                Stopped(self.0).into()
+            }
+        }
+
+        impl State<Counter, Resume> for Stopped {
+            fn next(self, _action: Resume) -> Counter {
+                Count(self.0).into()
+            }
+        }
+
+        let mut queue = ActionQueue::<CounterAction, 4>::new();
+        let (mut producer, mut consumer) = queue.split();
+        producer.enqueue(Add(2).into()).unwrap();
+        producer.enqueue(Stop.into()).unwrap();
+        producer.enqueue(Add(3).into()).unwrap();
+        let mut rejected = Vec::new();
+        let counter = consumer.drain_into(Counter::from(Count(1)), |state, action| {
+            rejected.push((state.name(), action.name()))
+        });
+        assert!(matches!(counter, Counter::Stopped(Stopped(3))));
+        assert_eq!(rejected, [("Stopped", "Add")]);
+    }
+
+    #[test]
+    fn hands_actions_over_between_threads() {
+        const COUNT: usize = if cfg!(miri) { 500 } else { 200_000 };
+
+        let mut queue = ActionQueue::<Box<usize>, 8>::new();
+        let (mut producer, mut consumer) = queue.split();
+        std::thread::scope(|scope| {
+            scope.spawn(move || {
+                for i in 0..COUNT {
+                    let mut action = Box::new(i);
+                    while let Err(back) = producer.enqueue(action) {
+                        action = back;
+                        std::thread::yield_now();
+                    }
+                }
+            });
+            scope.spawn(move || {
+                let mut expected = 0;
+                while expected < COUNT {
+                    match consumer.dequeue() {
+                        Some(action) => {
+                            assert_eq!(*action, expected);
+                            expected += 1;
+                        }
+                        None => std::thread::yield_now(),
+                    }
+                }
+                assert_eq!(consumer.dequeue(), None);
+            });
+        });
+    }
+}
@@ -0,0 +1,151 @@
+//! Speculative drivers running several machines over the same actions, e.g. to parse a prefix
+//! that is ambiguous between two formats.
+
+use either::Either;
+
+use crate::Machine;
+
+/// Sends every action to both machines and returns whichever reaches a terminal state first, the
+/// left one winning ties. A machine rejecting an action is dropped from the race; `None` is
+/// returned if both are, or if the actions run out before either finishes.
+pub fn race<L, R, A, I>(left: L, right: R, actions: I) -> Option<Either<L, R>>
+where
+    L: Machine,
+    R: Machine,
+    L::Action: From<A>,
+    R::Action: From<A>,
+    A: Clone,
+    I: IntoIterator<Item = A>,
+{
+    let (mut left, mut right) = (Some(left), Some(right));
+    let mut actions = actions.into_iter();
+    loop {
+        match (left, right) {
+            (Some(l), _) if l.is_finished() => return Some(Either::Left(l)),
+            (_, Some(r)) if r.is_finished() => return Some(Either::Right(r)),
+            (None, None) => return None,
+            (l, r) => {
+                let action = actions.next()?;
+                left = l.and_then(|l| l.next(action.clone().into()).ok());
+                right = r.and_then(|r| r.next(action.into()).ok());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{state_machine, Action, State};
+
+    #[derive(Debug)]
+    struct Start;
+    #[derive(Debug)]
+    struct SawA;
+    #[derive(Debug)]
+    struct Done;
+
+    #[derive(Debug)]
+    struct A;
+    #[derive(Debug)]
+    struct B;
+    #[derive(Debug)]
+    struct X;
+    #[derive(Debug)]
+    struct Y;
+
+    state_machine! {
+        AThenB,
+        AThenBAction,
+        terminal Done,
+        Start { A => SawA },
+        SawA { B => Done },
+        Done {},
+    }
+
+    state_machine! {
+        ATwice,
+        ATwiceAction,
+        terminal Done,
+        Start { X => SawA, reject Y },
+        SawA { X => Done, reject Y },
+        Done {},
+    }
+
+    macro_rules! transitions {
+        ($($machine:ident: $from:ident, $action:ident => $to:ident;)*) => {$(
+            impl State<$machine, $action> for $from {
+                fn next(self, _action: $action) -> $machine {
+                    $to.into()
+                }
+            }
+        )*};
+    }
+
+    transitions! {
+        AThenB: Start, A => SawA;
+        AThenB: SawA, B => Done;
+        ATwice: Start, X => SawA;
+        ATwice: SawA, X => Done;
+    }
+
+    #[derive(Clone, Copy)]
+    enum Letter {
+        A,
+        B,
+    }
+
+    impl From<Letter> for AThenBAction {
+        fn from(letter: Letter) -> Self {
+            match letter {
+                Letter::A => A.into(),
+                Letter::B => B.into(),
+            }
+        }
+    }
+
+    impl From<Letter> for ATwiceAction {
+        fn from(letter: Letter) -> Self {
+            match letter {
+                Letter::A => X.into(),
+                Letter::B => Y.into(),
+            }
+        }
+    }
+
+    fn start() -> (AThenB, ATwice) {
+        (Start.into(), Start.into())
+    }
+
+    #[test]
+    fn returns_the_machine_finishing_first() {
+        let (a_then_b, a_twice) = start();
+        assert!(matches!(
+            race(a_then_b, a_twice, [Letter::A, Letter::B]),
+            Some(Either::Left(AThenB::Done(Done)))
+        ));
+        let (a_then_b, a_twice) = start();
+        assert!(matches!(
+            race(a_then_b, a_twice, [Letter::A, Letter::A]),
+            Some(Either::Right(ATwice::Done(Done)))
+        ));
+    }
+
+    #[test]
+    fn left_wins_ties() {
+        let a_then_b = || AThenB::from(Start);
+        assert!(matches!(
+            race(a_then_b(), a_then_b(), [Letter::A, Letter::B]),
+            Some(Either::Left(_))
+        ));
+    }
+
+    #[test]
+    fn gives_up_when_both_reject_or_the_actions_run_out() {
+        let (a_then_b, a_twice) = start();
+        assert!(race(a_then_b, a_twice, [Letter::B]).is_none());
+        let (a_then_b, a_twice) = start();
+        assert!(race(a_then_b, a_twice, [Letter::A]).is_none());
+    }
+}
@@ -0,0 +1,213 @@
+use crate::Machine;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Violation {
+    /// The sequence number was already used by an earlier action.
+    Replayed,
+    /// Some sequence numbers were skipped.
+    Gap,
+    /// The current state does not accept this action.
+    IllegalTransition,
+}
+
+/// Why an action received from a peer was not applied.
+#[derive(Debug)]
+pub struct Rejection<A> {
+    pub violation: Violation,
+    pub sequence: u64,
+    pub expected_sequence: u64,
+    pub action: A,
+}
+
+#[cfg(feature = "std")]
+impl<A> Rejection<A> {
+    /// The sequence number problem, unless the action was rejected by the machine itself.
+    pub fn replay_error(&self) -> Option<crate::errors::ReplayError> {
+        (self.violation != Violation::IllegalTransition).then_some(crate::errors::ReplayError {
+            sequence: self.sequence,
+            expected_sequence: self.expected_sequence,
+        })
+    }
+}
+
+impl<A> core::fmt::Display for Rejection<A> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.violation {
+            Violation::Replayed | Violation::Gap => write!(
+                f,
+                "Action with sequence number {} {}, expected {}",
+                self.sequence,
+                if self.violation == Violation::Replayed {
+                    "replayed"
+                } else {
+                    "out of order"
+                },
+                self.expected_sequence
+            ),
+            Violation::IllegalTransition => write!(
+                f,
+                "Action with sequence number {} rejected by the current state",
+                self.sequence
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<A: core::fmt::Debug> std::error::Error for Rejection<A> {}
+
+/// Drives a machine with actions coming from an untrusted peer.
+///
+/// Each action has to come with the next sequence number and be accepted by the current state,
+/// otherwise it is rejected without touching the machine.
+pub struct RemoteDriver<M: Machine> {
+    // Only ever `None` while an action is being applied.
+    state: Option<M>,
+    next_sequence: u64,
+}
+
+impl<M: Machine> RemoteDriver<M> {
+    pub fn new(state: M) -> Self {
+        RemoteDriver {
+            state: Some(state),
+            next_sequence: 0,
+        }
+    }
+
+    pub fn state(&self) -> &M {
+        self.state
+            .as_ref()
+            .expect("RemoteDriver poisoned by a panicking handler")
+    }
+
+    pub fn into_inner(self) -> M {
+        self.state
+            .expect("RemoteDriver poisoned by a panicking handler")
+    }
+
+    pub fn next_sequence(&self) -> u64 {
+        self.next_sequence
+    }
+
+    pub fn apply(&mut self, sequence: u64, action: M::Action) -> Result<(), Rejection<M::Action>> {
+        let reject = |violation, action| Rejection {
+            violation,
+            sequence,
+            expected_sequence: self.next_sequence,
+            action,
+        };
+
+        if sequence < self.next_sequence {
+            return Err(reject(Violation::Replayed, action));
+        }
+        if sequence > self.next_sequence {
+            return Err(reject(Violation::Gap, action));
+        }
+
+        let state = self
+            .state
+            .take()
+            .expect("RemoteDriver poisoned by a panicking handler");
+        match state.next(action).map_err(Into::into) {
+            Ok(state) => {
+                self.state = Some(state);
+                self.next_sequence += 1;
+                Ok(())
+            }
+            Err((state, action)) => {
+                self.state = Some(state);
+                Err(reject(Violation::IllegalTransition, action))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+
+    use std::string::ToString;
+
+    use crate::{state_machine, Action, State};
+
+    #[derive(Debug)]
+    struct Count(u32);
+    #[derive(Debug)]
+    struct Stopped;
+
+    #[derive(Debug)]
+    struct Add(u32);
+    #[derive(Debug)]
+    struct Stop;
+
+    state_machine! {
+        Counter,
+        CounterAction,
+        terminal Stopped,
+        Count {
+            Add => Count,
+            Stop => Stopped,
+        },
+        Stopped {},
+    }
+
+    impl State<Counter, Add> for Count {
+        fn next(self, action: Add) -> Counter {
+            Count(self.0 + action.0).into()
+        }
+    }
+
+    impl State<Counter, Stop> for Count {
+        fn next(self, _action: Stop) -> Counter {
+            Stopped.into()
+        }
+    }
+
+    #[test]
+    fn applies_actions_in_sequence() {
+        let mut driver = RemoteDriver::new(Counter::from(Count(0)));
+        driver.apply(0, Add(2).into()).unwrap();
+        driver.apply(1, Add(3).into()).unwrap();
+        assert_eq!(driver.next_sequence(), 2);
+        assert!(matches!(driver.into_inner(), Counter::Count(Count(5))));
+    }
+
+    #[test]
+    fn rejects_replayed_and_skipped_sequence_numbers() {
+        let mut driver = RemoteDriver::new(Counter::from(Count(0)));
+        driver.apply(0, Add(2).into()).unwrap();
+
+        let replayed = driver.apply(0, Add(2).into()).unwrap_err();
+        assert_eq!(replayed.violation, Violation::Replayed);
+        assert_eq!(
+            replayed.to_string(),
+            "Action with sequence number 0 replayed, expected 1"
+        );
+
+        let gap = driver.apply(5, Add(2).into()).unwrap_err();
+        assert_eq!(gap.violation, Violation::Gap);
+        assert_eq!((gap.sequence, gap.expected_sequence), (5, 1));
+        assert_eq!(gap.action.name(), "Add");
+
+        assert_eq!(driver.next_sequence(), 1);
+        assert!(matches!(driver.state(), Counter::Count(Count(2))));
+    }
+
+    #[test]
+    fn rejects_illegal_transitions_without_using_the_sequence_number() {
+        let mut driver = RemoteDriver::new(Counter::from(Count(0)));
+        driver.apply(0, Stop.into()).unwrap();
+        let rejection = driver.apply(1, Add(1).into()).unwrap_err();
+        assert_eq!(rejection.violation, Violation::IllegalTransition);
+        assert_eq!(driver.next_sequence(), 1);
+        #[cfg(feature = "std")]
+        assert_eq!(rejection.replay_error(), None);
+
+        let gap = driver.apply(2, Add(1).into()).unwrap_err();
+        assert_eq!(gap.violation, Violation::Gap);
+        #[cfg(feature = "std")]
+        assert!(gap.replay_error().is_some_and(|e| !e.is_replayed()));
+    }
+}
@@ -0,0 +1,60 @@
+use std::fmt::Debug;
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+
+use crate::Machine;
+
+/// Steps `state` with actions read line by line from stdin, printing each new state and the
+/// actions it accepts, until the end of input. Returns the machine in its final state.
+pub fn repl<M>(state: M) -> io::Result<M>
+where
+    M: Machine + Debug,
+    M::Action: FromStr + Debug,
+    <M::Action as FromStr>::Err: Debug,
+{
+    run(state, io::stdin().lock(), io::stdout().lock())
+}
+
+/// Same as [`repl`], reading from `input` and writing to `output`.
+pub fn run<M, R, W>(mut state: M, input: R, mut output: W) -> io::Result<M>
+where
+    M: Machine + Debug,
+    M::Action: FromStr + Debug,
+    <M::Action as FromStr>::Err: Debug,
+    R: BufRead,
+    W: Write,
+{
+    writeln!(output, "{:#?}", state)?;
+    writeln!(output, "Allowed actions: {:?}", state.allowed_actions())?;
+    write!(output, "> ")?;
+    output.flush()?;
+
+    for line in input.lines() {
+        let line = line?;
+        let line = line.trim();
+        if !line.is_empty() {
+            match line.parse::<M::Action>() {
+                Err(e) => writeln!(output, "Could not parse action {:?}: {:?}", line, e)?,
+                Ok(action) => {
+                    state = match state.next(action).map_err(Into::into) {
+                        Ok(state) => {
+                            writeln!(output, "{:#?}", state)?;
+                            state
+                        }
+                        Err((state, action)) => {
+                            writeln!(output, "Action {:?} rejected in state {:?}", action, state)?;
+                            state
+                        }
+                    };
+                    writeln!(output, "Allowed actions: {:?}", state.allowed_actions())?;
+                }
+            }
+        }
+
+        write!(output, "> ")?;
+        output.flush()?;
+    }
+
+    writeln!(output)?;
+    Ok(state)
+}
@@ -0,0 +1,375 @@
+//! Rollback of a machine to an earlier tick, for rollback netcode: the state after every tick of
+//! a window is kept encoded with postcard, as the bytes changed since the tick before unless it
+//! is a keyframe, along with the action applied at each tick so that the ticks following a late
+//! or corrected input can be simulated again.
+
+use std::collections::VecDeque;
+use std::fmt;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::Machine;
+
+/// The tick is not in the window kept by the [`Rollback`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutOfHistory {
+    pub tick: u64,
+    pub oldest: u64,
+    pub latest: u64,
+}
+
+impl fmt::Display for OutOfHistory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Tick {} is out of the history, which goes from tick {} to tick {}",
+            self.tick, self.oldest, self.latest
+        )
+    }
+}
+
+impl std::error::Error for OutOfHistory {}
+
+// Bytes of the encoded state differing from the ones of the tick before.
+struct Delta {
+    len: usize,
+    runs: Vec<(usize, Vec<u8>)>,
+}
+
+impl Delta {
+    fn between(old: &[u8], new: &[u8]) -> Delta {
+        let mut runs = Vec::new();
+        let mut start = None;
+        for (index, byte) in new.iter().enumerate() {
+            match (old.get(index) == Some(byte), start) {
+                (false, None) => start = Some(index),
+                (true, Some(run_start)) => {
+                    runs.push((run_start, new[run_start..index].to_vec()));
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        if let Some(run_start) = start {
+            runs.push((run_start, new[run_start..].to_vec()));
+        }
+        Delta {
+            len: new.len(),
+            runs,
+        }
+    }
+
+    fn apply(&self, bytes: &mut Vec<u8>) {
+        bytes.resize(self.len, 0);
+        for (offset, run) in &self.runs {
+            bytes[*offset..offset + run.len()].copy_from_slice(run);
+        }
+    }
+}
+
+enum Frame {
+    Key(Vec<u8>),
+    Delta(Delta),
+}
+
+impl Frame {
+    fn len(&self) -> usize {
+        match self {
+            Frame::Key(bytes) => bytes.len(),
+            Frame::Delta(delta) => delta.runs.iter().map(|(_, run)| run.len()).sum(),
+        }
+    }
+}
+
+/// Owns a machine stepped once per tick, and can bring it back to any tick of the last `window`
+/// ones.
+///
+/// States are encoded with postcard, a state failing to serialize making the stepping panic.
+pub struct Rollback<M: Machine> {
+    // Only ever `None` while an action is being applied.
+    state: Option<M>,
+    // State after each tick of the window, oldest first. The oldest one is always a keyframe.
+    frames: VecDeque<Frame>,
+    // Action applied at each tick, `actions[i]` leading from `frames[i]` to `frames[i + 1]`.
+    actions: VecDeque<M::Action>,
+    // Tick of `frames[0]`.
+    oldest: u64,
+    // Encoding of the current state, which the next delta is taken against.
+    latest: Vec<u8>,
+    window: usize,
+    keyframe_interval: u64,
+}
+
+impl<M> Rollback<M>
+where
+    M: Machine + Serialize + DeserializeOwned,
+    M::Action: Clone,
+{
+    /// Starts at tick 0 with `state`, keeping the states of the last `window` ticks.
+    pub fn new(state: M, window: usize) -> Self {
+        let latest = encode(&state);
+        Rollback {
+            state: Some(state),
+            frames: VecDeque::from([Frame::Key(latest.clone())]),
+            actions: VecDeque::new(),
+            oldest: 0,
+            latest,
+            window,
+            keyframe_interval: 16,
+        }
+    }
+
+    /// Stores every `interval`th tick in full rather than as a delta, 16 by default: rolling back
+    /// decodes at most that many deltas.
+    pub fn with_keyframe_interval(mut self, interval: u64) -> Self {
+        self.keyframe_interval = interval.max(1);
+        self
+    }
+
+    pub fn state(&self) -> &M {
+        self.state
+            .as_ref()
+            .expect("Rollback poisoned by a panicking handler")
+    }
+
+    pub fn into_inner(self) -> M {
+        self.state
+            .expect("Rollback poisoned by a panicking handler")
+    }
+
+    /// Number of ticks simulated so far.
+    pub fn tick(&self) -> u64 {
+        self.oldest + self.actions.len() as u64
+    }
+
+    /// Oldest tick the machine can be rolled back to.
+    pub fn oldest_tick(&self) -> u64 {
+        self.oldest
+    }
+
+    /// Size in bytes of the encoded states kept.
+    pub fn history_len(&self) -> usize {
+        self.frames.iter().map(Frame::len).sum()
+    }
+
+    /// Simulates the next tick with `action`. A rejected action is given back, the state staying
+    /// as it was, but still counts as the input of the tick: it is rejected again when the tick
+    /// is simulated again.
+    pub fn advance(&mut self, action: M::Action) -> Result<(), M::Action> {
+        let state = self
+            .state
+            .take()
+            .expect("Rollback poisoned by a panicking handler");
+        let (state, rejected) = match state.next(action.clone()).map_err(Into::into) {
+            Ok(state) => (state, None),
+            Err((state, action)) => (state, Some(action)),
+        };
+
+        let encoded = encode(&state);
+        let frame = if (self.tick() + 1).is_multiple_of(self.keyframe_interval) {
+            Frame::Key(encoded.clone())
+        } else {
+            Frame::Delta(Delta::between(&self.latest, &encoded))
+        };
+        self.frames.push_back(frame);
+        self.actions.push_back(action);
+        self.latest = encoded;
+        self.state = Some(state);
+
+        if self.actions.len() > self.window {
+            let second = self.decode_frame(1);
+            self.frames.pop_front();
+            self.actions.pop_front();
+            self.frames[0] = Frame::Key(second);
+            self.oldest += 1;
+        }
+
+        match rejected {
+            None => Ok(()),
+            Some(action) => Err(action),
+        }
+    }
+
+    /// Brings the machine back to the state it was in at `tick`, forgetting the ticks after it.
+    /// Returns the actions of the forgotten ticks, oldest first, to simulate them again once
+    /// corrected.
+    pub fn rollback_to(&mut self, tick: u64) -> Result<Vec<M::Action>, OutOfHistory> {
+        if tick < self.oldest || tick > self.tick() {
+            return Err(OutOfHistory {
+                tick,
+                oldest: self.oldest,
+                latest: self.tick(),
+            });
+        }
+
+        let index = (tick - self.oldest) as usize;
+        let bytes = self.decode_frame(index);
+        self.state = Some(decode(&bytes));
+        self.latest = bytes;
+        self.frames.truncate(index + 1);
+        Ok(self.actions.drain(index..).collect())
+    }
+
+    /// Replaces the action of the tick going from `tick` to `tick + 1`, then simulates again the
+    /// ticks up to the current one with their recorded actions.
+    pub fn correct(&mut self, tick: u64, action: M::Action) -> Result<(), OutOfHistory> {
+        if tick >= self.tick() {
+            return Err(OutOfHistory {
+                tick,
+                oldest: self.oldest,
+                latest: self.tick(),
+            });
+        }
+
+        let mut actions = self.rollback_to(tick)?.into_iter();
+        actions.next();
+        let _ = self.advance(action);
+        for action in actions {
+            let _ = self.advance(action);
+        }
+        Ok(())
+    }
+
+    // Encoding of `frames[index]`, applying the deltas since the last keyframe before it.
+    fn decode_frame(&self, index: usize) -> Vec<u8> {
+        let key = (0..=index)
+            .rev()
+            .find(|&i| matches!(self.frames[i], Frame::Key(_)))
+            .expect("the oldest frame is a keyframe");
+        let mut bytes = match &self.frames[key] {
+            Frame::Key(bytes) => bytes.clone(),
+            Frame::Delta(_) => unreachable!(),
+        };
+        for frame in self.frames.range(key + 1..=index) {
+            if let Frame::Delta(delta) = frame {
+                delta.apply(&mut bytes);
+            }
+        }
+        bytes
+    }
+}
+
+fn encode<M: Serialize>(state: &M) -> Vec<u8> {
+    postcard::to_allocvec(state).expect("Rollback could not encode the state")
+}
+
+fn decode<M: DeserializeOwned>(bytes: &[u8]) -> M {
+    postcard::from_bytes(bytes).expect("Rollback could not decode a state it encoded")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde::Deserialize;
+
+    use crate::{state_machine, Action, State};
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Alive([u32; 8]);
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Dead;
+
+    #[derive(Debug, Clone)]
+    struct Move(usize);
+    #[derive(Debug, Clone)]
+    struct Hit;
+
+    state_machine! {
+        #[derive(Debug)] Player,
+        #[derive(Debug, Clone)] PlayerAction,
+        terminal Dead,
+        Alive {
+            Move => Alive,
+            Hit => Dead,
+        },
+        Dead {},
+    }
+
+    impl State<Player, Move> for Alive {
+        fn next(mut self, action: Move) -> Player {
+            self.0[action.0] += 1;
+            self.into()
+        }
+    }
+
+    impl State<Player, Hit> for Alive {
+        fn next(self, _action: Hit) -> Player {
+            Dead.into()
+        }
+    }
+
+    fn position(player: &Player) -> [u32; 8] {
+        match player {
+            Player::Alive(Alive(position)) => *position,
+            Player::Dead(_) => panic!("Player is dead"),
+        }
+    }
+
+    fn rollback(window: usize) -> Rollback<Player> {
+        Rollback::new(Player::from(Alive([0; 8])), window)
+    }
+
+    #[test]
+    fn rolls_back_to_earlier_ticks() {
+        let mut rollback = rollback(32).with_keyframe_interval(4);
+        for tick in 0..10 {
+            rollback.advance(Move(tick % 8).into()).unwrap();
+        }
+        assert_eq!(rollback.tick(), 10);
+
+        let undone = rollback.rollback_to(5).unwrap();
+        assert_eq!(undone.len(), 5);
+        assert_eq!(rollback.tick(), 5);
+        assert_eq!(position(rollback.state()), [1, 1, 1, 1, 1, 0, 0, 0]);
+
+        rollback.rollback_to(0).unwrap();
+        assert_eq!(position(rollback.state()), [0; 8]);
+    }
+
+    #[test]
+    fn simulates_again_after_a_correction() {
+        let mut rollback = rollback(32);
+        rollback.advance(Move(0).into()).unwrap();
+        rollback.advance(Move(1).into()).unwrap();
+        rollback.advance(Move(1).into()).unwrap();
+
+        rollback.correct(1, Move(2).into()).unwrap();
+        assert_eq!(rollback.tick(), 3);
+        assert_eq!(position(rollback.state()), [1, 1, 1, 0, 0, 0, 0, 0]);
+
+        rollback.correct(0, Hit.into()).unwrap();
+        assert!(rollback.state().is_finished());
+        assert_eq!(rollback.tick(), 3);
+    }
+
+    #[test]
+    fn keeps_a_window_of_ticks() {
+        let mut rollback = rollback(4);
+        for _ in 0..10 {
+            rollback.advance(Move(3).into()).unwrap();
+        }
+        assert_eq!(rollback.oldest_tick(), 6);
+        assert_eq!(
+            rollback.rollback_to(5).unwrap_err(),
+            OutOfHistory {
+                tick: 5,
+                oldest: 6,
+                latest: 10
+            }
+        );
+        rollback.rollback_to(6).unwrap();
+        assert_eq!(position(rollback.state())[3], 6);
+        assert!(rollback.correct(6, Hit.into()).is_err());
+    }
+
+    #[test]
+    fn stores_deltas_between_keyframes() {
+        let mut rollback = rollback(16).with_keyframe_interval(100);
+        let key = rollback.history_len();
+        for _ in 0..16 {
+            rollback.advance(Move(7).into()).unwrap();
+        }
+        assert!(rollback.history_len() < key + 16 * 2);
+    }
+}
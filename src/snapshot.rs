@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+/// State of a machine saved along with the hash of its definition, so that it is only ever
+/// restored into the same machine. Built by the generated `snapshot`, turned back into a machine
+/// by `restore`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snapshot<W> {
+    pub machine_hash: u64,
+    pub state: W,
+}
+
+/// The snapshot was taken from a different definition of the machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IncompatibleSnapshot {
+    pub expected_hash: u64,
+    pub found_hash: u64,
+}
+
+impl core::fmt::Display for IncompatibleSnapshot {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "Snapshot taken from machine {:#018x}, expected {:#018x}",
+            self.found_hash, self.expected_hash
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for IncompatibleSnapshot {}
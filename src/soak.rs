@@ -0,0 +1,346 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::Machine;
+
+type Generator<A> = Box<dyn FnMut() -> A>;
+type Invariant<M> = Box<dyn Fn(&M) -> bool>;
+
+/// Long-running test harness sending randomly picked actions to a machine, and checking
+/// registered invariants along the way.
+///
+/// The last actions sent are kept in a ring buffer, handed over with the first violation. Actions
+/// are picked by a seeded generator, so a failing run can be replayed with the same seed.
+pub struct Soak<M: Machine> {
+    // Only ever `None` while an action is being applied.
+    state: Option<M>,
+    actions: Vec<(&'static str, u32, Generator<M::Action>)>,
+    invariants: Vec<(&'static str, Invariant<M>)>,
+    memory: Option<(Box<dyn FnMut() -> usize>, usize)>,
+    check_every: u64,
+    trace: VecDeque<TraceEntry>,
+    trace_len: usize,
+    rng: u64,
+}
+
+/// An action sent during a soak run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub step: u64,
+    pub action: &'static str,
+    pub accepted: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SoakReport {
+    pub steps: u64,
+    pub rejected: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ViolationKind {
+    Invariant(&'static str),
+    MemoryGrowth { baseline: usize, current: usize },
+}
+
+/// First check that failed during a soak run, with the actions leading to it, oldest first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub step: u64,
+    pub kind: ViolationKind,
+    pub trace: Vec<TraceEntry>,
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            ViolationKind::Invariant(name) => {
+                write!(f, "Invariant {} violated after step {}", name, self.step)
+            }
+            ViolationKind::MemoryGrowth { baseline, current } => write!(
+                f,
+                "Memory grew from {} to {} after step {}",
+                baseline, current, self.step
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Violation {}
+
+impl<M: Machine> Soak<M> {
+    pub fn new(state: M, seed: u64) -> Self {
+        Soak {
+            state: Some(state),
+            actions: Vec::new(),
+            invariants: Vec::new(),
+            memory: None,
+            check_every: 1000,
+            trace: VecDeque::new(),
+            trace_len: 64,
+            rng: seed,
+        }
+    }
+
+    /// Adds an action to pick from, `weight` times as likely as an action of weight 1, built by
+    /// `make_action` each time it is sent.
+    pub fn action(
+        mut self,
+        name: &'static str,
+        weight: u32,
+        make_action: impl FnMut() -> M::Action + 'static,
+    ) -> Self {
+        self.actions.push((name, weight, Box::new(make_action)));
+        self
+    }
+
+    /// Adds a check the machine must pass every time the invariants are checked.
+    pub fn invariant(mut self, name: &'static str, check: impl Fn(&M) -> bool + 'static) -> Self {
+        self.invariants.push((name, Box::new(check)));
+        self
+    }
+
+    /// Checks that the memory use reported by `probe`, e.g. read from the allocator or the OS,
+    /// never grows more than `max_growth` above what it was when the run started.
+    pub fn memory(mut self, probe: impl FnMut() -> usize + 'static, max_growth: usize) -> Self {
+        self.memory = Some((Box::new(probe), max_growth));
+        self
+    }
+
+    /// Number of actions sent between two checks, 1000 by default.
+    pub fn check_every(mut self, steps: u64) -> Self {
+        self.check_every = steps.max(1);
+        self
+    }
+
+    /// Number of actions kept for the trace, 64 by default.
+    pub fn trace_len(mut self, len: usize) -> Self {
+        self.trace_len = len;
+        self
+    }
+
+    pub fn state(&self) -> &M {
+        self.state
+            .as_ref()
+            .expect("Soak poisoned by a panicking handler")
+    }
+
+    pub fn into_inner(self) -> M {
+        self.state.expect("Soak poisoned by a panicking handler")
+    }
+
+    /// Sends actions for `duration`, checking the invariants every few steps and once more at the
+    /// end. Stops at the first violation.
+    pub fn run(&mut self, duration: Duration) -> Result<SoakReport, Violation> {
+        let total_weight = self
+            .actions
+            .iter()
+            .map(|(_, w, _)| u64::from(*w))
+            .sum::<u64>();
+        assert!(
+            total_weight > 0,
+            "Soak needs an action with a non-zero weight"
+        );
+
+        let baseline = self.memory.as_mut().map(|(probe, _)| probe());
+        let start = Instant::now();
+        let mut report = SoakReport {
+            steps: 0,
+            rejected: 0,
+        };
+        self.trace.clear();
+        while start.elapsed() < duration {
+            let mut pick = self.next_u64() % total_weight;
+            let index = self
+                .actions
+                .iter()
+                .position(|(_, w, _)| match pick.checked_sub(u64::from(*w)) {
+                    Some(rest) => {
+                        pick = rest;
+                        false
+                    }
+                    None => true,
+                })
+                .expect("pick is below the total weight");
+
+            let (name, _, make_action) = &mut self.actions[index];
+            let name = *name;
+            let action = make_action();
+            let accepted = self.apply(action);
+            report.steps += 1;
+            if !accepted {
+                report.rejected += 1;
+            }
+            if self.trace.len() == self.trace_len {
+                self.trace.pop_front();
+            }
+            if self.trace_len > 0 {
+                self.trace.push_back(TraceEntry {
+                    step: report.steps,
+                    action: name,
+                    accepted,
+                });
+            }
+
+            if report.steps.is_multiple_of(self.check_every) {
+                self.check(report.steps, baseline)?;
+            }
+        }
+        self.check(report.steps, baseline)?;
+        Ok(report)
+    }
+
+    fn check(&mut self, step: u64, baseline: Option<usize>) -> Result<(), Violation> {
+        let state = self.state();
+        let broken = self
+            .invariants
+            .iter()
+            .find(|(_, check)| !check(state))
+            .map(|(name, _)| ViolationKind::Invariant(name));
+        let grown = match (&mut self.memory, baseline) {
+            (Some((probe, max_growth)), Some(baseline)) => {
+                let current = probe();
+                (current > baseline.saturating_add(*max_growth))
+                    .then_some(ViolationKind::MemoryGrowth { baseline, current })
+            }
+            _ => None,
+        };
+
+        match broken.or(grown) {
+            Some(kind) => Err(Violation {
+                step,
+                kind,
+                trace: self.trace.iter().copied().collect(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn apply(&mut self, action: M::Action) -> bool {
+        let state = self
+            .state
+            .take()
+            .expect("Soak poisoned by a panicking handler");
+        let (state, accepted) = match state.next(action).map_err(Into::into) {
+            Ok(state) => (state, true),
+            Err((state, _)) => (state, false),
+        };
+        self.state = Some(state);
+        accepted
+    }
+
+    // splitmix64
+    fn next_u64(&mut self) -> u64 {
+        self.rng = self.rng.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use crate::{state_machine, Action, State};
+
+    #[derive(Debug)]
+    struct Count(u32);
+    #[derive(Debug)]
+    struct Stopped;
+
+    #[derive(Debug)]
+    struct Add;
+    #[derive(Debug)]
+    struct Stop;
+
+    state_machine! {
+        Counter,
+        CounterAction,
+        terminal Stopped,
+        Count {
+            Add => Count,
+            Stop => Stopped,
+        },
+        Stopped {},
+    }
+
+    impl State<Counter, Add> for Count {
+        fn next(self, _action: Add) -> Counter {
+            Count(self.0 + 1).into()
+        }
+    }
+
+    impl State<Counter, Stop> for Count {
+        fn next(self, _action: Stop) -> Counter {
+            Stopped.into()
+        }
+    }
+
+    #[test]
+    fn stops_at_the_first_violation_with_the_last_actions() {
+        let mut soak = Soak::new(Counter::from(Count(0)), 3)
+            .action("add", 1, || Add.into())
+            .invariant(
+                "below ten",
+                |c| matches!(c, Counter::Count(Count(n)) if *n < 10),
+            )
+            .check_every(1)
+            .trace_len(4);
+        let violation = soak.run(Duration::from_secs(60)).unwrap_err();
+        assert_eq!(violation.step, 10);
+        assert_eq!(violation.kind, ViolationKind::Invariant("below ten"));
+        assert_eq!(
+            violation.trace.iter().map(|e| e.step).collect::<Vec<_>>(),
+            [7, 8, 9, 10]
+        );
+        assert!(violation
+            .trace
+            .iter()
+            .all(|e| e.action == "add" && e.accepted));
+        assert_eq!(
+            violation.to_string(),
+            "Invariant below ten violated after step 10"
+        );
+        assert!(matches!(soak.state(), Counter::Count(Count(10))));
+    }
+
+    #[test]
+    fn counts_the_rejected_actions() {
+        let mut soak = Soak::new(Counter::from(Count(0)), 3).action("stop", 1, || Stop.into());
+        let report = soak.run(Duration::from_millis(10)).unwrap();
+        assert!(report.steps > 1);
+        assert_eq!(report.rejected, report.steps - 1);
+        assert!(matches!(soak.into_inner(), Counter::Stopped(Stopped)));
+    }
+
+    #[test]
+    fn reports_memory_growing_past_the_limit() {
+        let used = Rc::new(Cell::new(0));
+        let probe = Rc::clone(&used);
+        let mut soak = Soak::new(Counter::from(Count(0)), 3)
+            .action("add", 1, || Add.into())
+            .memory(
+                move || {
+                    probe.set(probe.get() + 1);
+                    probe.get()
+                },
+                5,
+            )
+            .check_every(2);
+        let violation = soak.run(Duration::from_secs(60)).unwrap_err();
+        assert_eq!(
+            violation.kind,
+            ViolationKind::MemoryGrowth {
+                baseline: 1,
+                current: 7
+            }
+        );
+        assert_eq!(violation.step, 12);
+        assert_eq!(used.get(), 7);
+    }
+}
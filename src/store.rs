@@ -0,0 +1,229 @@
+use crate::Machine;
+
+/// Hooks run by a [`Store`] around every dispatched action.
+pub trait Middleware<M: Machine> {
+    /// Sees the action before the machine does, and returns the action to dispatch in its place,
+    /// or `None` to swallow it. Middlewares are run in the order they were added, each one
+    /// receiving what the previous one returned.
+    fn before_dispatch(&mut self, _state: &M, action: M::Action) -> Option<M::Action> {
+        Some(action)
+    }
+
+    /// `accepted` is false when the machine rejected the action, `state` is then unchanged.
+    fn after_dispatch(&mut self, _state: &M, _accepted: bool) {}
+}
+
+type Subscriber<M> = Box<dyn FnMut(&M)>;
+
+/// Owns a machine and notifies subscribers of every transition it goes through.
+pub struct Store<M: Machine> {
+    // Only ever `None` while an action is being dispatched.
+    state: Option<M>,
+    subscribers: Vec<Subscriber<M>>,
+    middlewares: Vec<Box<dyn Middleware<M>>>,
+    experimental: bool,
+}
+
+impl<M: Machine> Store<M> {
+    pub fn new(state: M) -> Self {
+        Store {
+            state: Some(state),
+            subscribers: Vec::new(),
+            middlewares: Vec::new(),
+            experimental: false,
+        }
+    }
+
+    pub fn state(&self) -> &M {
+        self.state
+            .as_ref()
+            .expect("Store poisoned by a panicking dispatch")
+    }
+
+    pub fn into_inner(self) -> M {
+        self.state.expect("Store poisoned by a panicking dispatch")
+    }
+
+    /// Registers a callback called with the new state after each accepted action.
+    pub fn subscribe(&mut self, subscriber: impl FnMut(&M) + 'static) {
+        self.subscribers.push(Box::new(subscriber));
+    }
+
+    pub fn add_middleware(&mut self, middleware: impl Middleware<M> + 'static) {
+        self.middlewares.push(Box::new(middleware));
+    }
+
+    /// Enables or disables the machine's experimental transitions for the next dispatches.
+    pub fn set_experimental(&mut self, enabled: bool) {
+        self.experimental = enabled;
+    }
+
+    /// Steps the machine with `action`, giving the action back if the current state rejected it.
+    ///
+    /// An action swallowed by a middleware counts as dispatched, but neither reaches the machine
+    /// nor the subscribers.
+    pub fn dispatch(&mut self, mut action: M::Action) -> Result<(), M::Action> {
+        let state = self
+            .state
+            .take()
+            .expect("Store poisoned by a panicking dispatch");
+        for middleware in &mut self.middlewares {
+            action = match middleware.before_dispatch(&state, action) {
+                Some(action) => action,
+                None => {
+                    self.state = Some(state);
+                    return Ok(());
+                }
+            };
+        }
+
+        let next = if self.experimental {
+            state.next_experimental(action)
+        } else {
+            state.next(action)
+        };
+        let (state, rejected) = match next.map_err(Into::into) {
+            Ok(state) => (state, None),
+            Err((state, action)) => (state, Some(action)),
+        };
+
+        for middleware in &mut self.middlewares {
+            middleware.after_dispatch(&state, rejected.is_none());
+        }
+        if rejected.is_none() {
+            for subscriber in &mut self.subscribers {
+                subscriber(&state);
+            }
+        }
+
+        self.state = Some(state);
+        match rejected {
+            None => Ok(()),
+            Some(action) => Err(action),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::{state_machine, Action, State};
+
+    #[derive(Debug)]
+    struct Count(u32);
+    #[derive(Debug)]
+    struct Stopped(u32);
+
+    #[derive(Debug)]
+    struct Add(u32);
+    #[derive(Debug)]
+    struct Stop;
+    #[derive(Debug)]
+    struct Boost;
+
+    state_machine! {
+        Counter,
+        CounterAction,
+        terminal Stopped,
+        Count {
+            Add => Count,
+            Stop => Stopped,
+            experimental Boost => Count,
+        },
+        Stopped {},
+    }
+
+    impl State<Counter, Add> for Count {
+        fn next(self, action: Add) -> Counter {
+            Count(self.0 + action.0).into()
+        }
+    }
+
+    impl State<Counter, Stop> for Count {
+        fn next(self, _action: Stop) -> Counter {
+            Stopped(self.0).into()
+        }
+    }
+
+    impl State<Counter, Boost> for Count {
+        fn next(self, _action: Boost) -> Counter {
+            Count(self.0 * 10).into()
+        }
+    }
+
+    fn count(counter: &Counter) -> u32 {
+        match counter {
+            Counter::Count(Count(n)) | Counter::Stopped(Stopped(n)) => *n,
+        }
+    }
+
+    #[test]
+    fn notifies_subscribers_of_accepted_actions() {
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let mut store = Store::new(Counter::from(Count(0)));
+        let subscriber_seen = seen.clone();
+        store.subscribe(move |state| subscriber_seen.borrow_mut().push(count(state)));
+
+        store.dispatch(Add(2).into()).unwrap();
+        store.dispatch(Stop.into()).unwrap();
+        let rejected = store.dispatch(Add(3).into()).unwrap_err();
+        assert_eq!(rejected.name(), "Add");
+        assert_eq!(*seen.borrow(), [2, 2]);
+        assert_eq!(store.state().name(), "Stopped");
+    }
+
+    struct Doubler {
+        accepted: Rc<RefCell<Vec<bool>>>,
+    }
+
+    impl Middleware<Counter> for Doubler {
+        fn before_dispatch(
+            &mut self,
+            state: &Counter,
+            action: CounterAction,
+        ) -> Option<CounterAction> {
+            match action {
+                // Swallowed once the count gets too high.
+                CounterAction::Add(_) if count(state) >= 10 => None,
+                CounterAction::Add(Add(n)) => Some(Add(n * 2).into()),
+                action => Some(action),
+            }
+        }
+
+        fn after_dispatch(&mut self, _state: &Counter, accepted: bool) {
+            self.accepted.borrow_mut().push(accepted);
+        }
+    }
+
+    #[test]
+    fn runs_the_middlewares_around_the_machine() {
+        let accepted = Rc::new(RefCell::new(Vec::new()));
+        let mut store = Store::new(Counter::from(Count(0)));
+        store.add_middleware(Doubler {
+            accepted: accepted.clone(),
+        });
+
+        store.dispatch(Add(3).into()).unwrap();
+        assert_eq!(count(store.state()), 6);
+        store.dispatch(Add(3).into()).unwrap();
+        assert_eq!(count(store.state()), 12);
+        // Swallowed, without reaching the machine nor `after_dispatch`.
+        store.dispatch(Add(3).into()).unwrap();
+        assert_eq!(count(store.state()), 12);
+        store.dispatch(Boost.into()).unwrap_err();
+        assert_eq!(*accepted.borrow(), [true, true, false]);
+    }
+
+    #[test]
+    fn takes_experimental_transitions_once_enabled() {
+        let mut store = Store::new(Counter::from(Count(2)));
+        assert!(store.dispatch(Boost.into()).is_err());
+        store.set_experimental(true);
+        store.dispatch(Boost.into()).unwrap();
+        assert_eq!(count(&store.into_inner()), 20);
+    }
+}
@@ -0,0 +1,307 @@
+use std::time::Instant;
+
+use crate::Machine;
+
+struct Timer<A> {
+    // Active states from the top machine down to the one the timer is armed for.
+    scope: Vec<&'static str>,
+    deadline: Instant,
+    action: A,
+}
+
+/// Owns a machine and one timer per level of its submachines, each firing its action unless the
+/// machine it was armed for left its state first.
+///
+/// A handshake machine nested in a connection machine arms its own timeout at its own depth: the
+/// timeout is dropped as soon as the handshake moves on, or the connection leaves the state
+/// holding it, while the connection's own timer keeps running. A transition back into the same
+/// state keeps the timers armed.
+pub struct Timers<M: Machine> {
+    // Only ever `None` while an action is being applied.
+    state: Option<M>,
+    timers: Vec<Timer<M::Action>>,
+}
+
+impl<M: Machine> Timers<M> {
+    pub fn new(state: M) -> Self {
+        Timers {
+            state: Some(state),
+            timers: Vec::new(),
+        }
+    }
+
+    pub fn state(&self) -> &M {
+        self.state
+            .as_ref()
+            .expect("Timers poisoned by a panicking handler")
+    }
+
+    pub fn into_inner(self) -> M {
+        self.state.expect("Timers poisoned by a panicking handler")
+    }
+
+    /// Arms the timer of the machine `depth` levels down the submachines, 0 being the top one,
+    /// replacing the one armed there before. `action` is given to the top machine, which routes
+    /// it to the submachine listing it.
+    ///
+    /// Returns false, arming nothing, if there is no machine that deep.
+    pub fn arm(&mut self, depth: usize, deadline: Instant, action: impl Into<M::Action>) -> bool {
+        let scope = match self.active_scope(depth) {
+            Some(scope) => scope,
+            None => return false,
+        };
+        self.cancel(depth);
+        self.timers.push(Timer {
+            scope,
+            deadline,
+            action: action.into(),
+        });
+        true
+    }
+
+    /// Disarms the timer of the machine `depth` levels down. Returns whether one was armed.
+    pub fn cancel(&mut self, depth: usize) -> bool {
+        let armed = self.timers.len();
+        self.timers.retain(|timer| timer.scope.len() != depth + 1);
+        self.timers.len() != armed
+    }
+
+    /// Earliest deadline of the armed timers, to sleep until.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.timers.iter().map(|timer| timer.deadline).min()
+    }
+
+    /// Whether the timer of the machine `depth` levels down is armed.
+    pub fn is_armed(&self, depth: usize) -> bool {
+        self.timers
+            .iter()
+            .any(|timer| timer.scope.len() == depth + 1)
+    }
+
+    /// Steps the machine with `action`, giving the action back if the current state rejected it.
+    /// Disarms the timers of the machines that left the state they were armed in.
+    pub fn dispatch(&mut self, action: M::Action) -> Result<(), M::Action> {
+        let state = self
+            .state
+            .take()
+            .expect("Timers poisoned by a panicking handler");
+        match state.next(action).map_err(Into::into) {
+            Ok(state) => {
+                let state = self.state.insert(state);
+                self.timers.retain(|timer| {
+                    timer
+                        .scope
+                        .iter()
+                        .enumerate()
+                        .all(|(depth, name)| state.active_state(depth) == Some(name))
+                });
+                Ok(())
+            }
+            Err((state, action)) => {
+                self.state = Some(state);
+                Err(action)
+            }
+        }
+    }
+
+    /// Fires the timers whose deadline is past `now`, earliest first, each one disarmed before
+    /// its action is dispatched. Returns the timeout actions the machine rejected.
+    pub fn fire_due(&mut self, now: Instant) -> Vec<M::Action> {
+        let mut rejected = Vec::new();
+        while let Some(index) = self
+            .timers
+            .iter()
+            .enumerate()
+            .filter(|(_, timer)| timer.deadline <= now)
+            .min_by_key(|(_, timer)| timer.deadline)
+            .map(|(index, _)| index)
+        {
+            let timer = self.timers.remove(index);
+            if let Err(action) = self.dispatch(timer.action) {
+                rejected.push(action);
+            }
+        }
+        rejected
+    }
+
+    fn active_scope(&self, depth: usize) -> Option<Vec<&'static str>> {
+        let state = self.state();
+        (0..=depth).map(|depth| state.active_state(depth)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    use crate::{state_machine, Action, Done, State, Submachine};
+
+    mod handshake {
+        use crate::{state_machine, Action, State};
+
+        #[derive(Debug, Default)]
+        pub struct SentHello;
+        #[derive(Debug)]
+        pub struct SentKey;
+        #[derive(Debug)]
+        pub struct Established;
+        #[derive(Debug)]
+        pub struct Failed;
+
+        #[derive(Debug)]
+        pub struct Hello;
+        #[derive(Debug)]
+        pub struct Key;
+        #[derive(Debug)]
+        pub struct HandshakeTimeout;
+
+        state_machine! {
+            #[derive(Debug)] pub Handshake,
+            #[derive(Debug)] pub HandshakeAction,
+            initial SentHello,
+            terminal Established,
+            terminal Failed,
+            SentHello {
+                Hello => SentKey,
+                HandshakeTimeout => Failed,
+            },
+            SentKey {
+                Key => Established,
+                HandshakeTimeout => Failed,
+            },
+        }
+
+        impl State<Handshake, Hello> for SentHello {
+            fn next(self, _action: Hello) -> Handshake {
+                SentKey.into()
+            }
+        }
+
+        impl State<Handshake, HandshakeTimeout> for SentHello {
+            fn next(self, _action: HandshakeTimeout) -> Handshake {
+                Failed.into()
+            }
+        }
+
+        impl State<Handshake, Key> for SentKey {
+            fn next(self, _action: Key) -> Handshake {
+                Established.into()
+            }
+        }
+
+        impl State<Handshake, HandshakeTimeout> for SentKey {
+            fn next(self, _action: HandshakeTimeout) -> Handshake {
+                Failed.into()
+            }
+        }
+    }
+
+    use handshake::{Handshake, HandshakeTimeout, Hello, Key};
+
+    #[derive(Debug)]
+    struct Connecting {
+        handshake: Handshake,
+    }
+    #[derive(Debug)]
+    struct Open;
+    #[derive(Debug)]
+    struct Closed;
+
+    #[derive(Debug)]
+    struct ConnectTimeout;
+
+    state_machine! {
+        #[derive(Debug)] Connection,
+        #[derive(Debug)] ConnectionAction,
+        terminal Open,
+        terminal Closed,
+        Connecting {
+            submachine Handshake: Hello | Key | HandshakeTimeout => Open | Closed,
+            ConnectTimeout => Closed,
+        },
+    }
+
+    impl Submachine<Handshake> for Connecting {
+        fn child(&self) -> &Handshake {
+            &self.handshake
+        }
+
+        fn child_mut(&mut self) -> &mut Handshake {
+            &mut self.handshake
+        }
+    }
+
+    impl State<Connection, Done<Handshake>> for Connecting {
+        fn next(self, done: Done<Handshake>) -> Connection {
+            match done.0 {
+                Handshake::Established(_) => Open.into(),
+                _ => Closed.into(),
+            }
+        }
+    }
+
+    impl State<Connection, ConnectTimeout> for Connecting {
+        fn next(self, _action: ConnectTimeout) -> Connection {
+            Closed.into()
+        }
+    }
+
+    fn connecting() -> Timers<Connection> {
+        Timers::new(Connection::from(Connecting {
+            handshake: Handshake::new(),
+        }))
+    }
+
+    #[test]
+    fn reports_the_active_states_down_the_submachines() {
+        let timers = connecting();
+        assert_eq!(timers.state().active_state(0), Some("Connecting"));
+        assert_eq!(timers.state().active_state(1), Some("SentHello"));
+        assert_eq!(timers.state().active_state(2), None);
+    }
+
+    #[test]
+    fn drops_the_timer_of_a_submachine_moving_on() {
+        let start = Instant::now();
+        let mut timers = connecting();
+        assert!(timers.arm(0, start + Duration::from_secs(10), ConnectTimeout));
+        assert!(timers.arm(1, start + Duration::from_secs(1), HandshakeTimeout));
+        assert!(!timers.arm(2, start, HandshakeTimeout));
+        assert_eq!(timers.next_deadline(), Some(start + Duration::from_secs(1)));
+
+        timers.dispatch(Hello.into()).unwrap();
+        assert!(!timers.is_armed(1));
+        assert!(timers.is_armed(0));
+        assert!(timers.fire_due(start + Duration::from_secs(5)).is_empty());
+        assert_eq!(timers.state().active_state(1), Some("SentKey"));
+
+        assert!(timers.arm(1, start + Duration::from_secs(6), HandshakeTimeout));
+        assert!(timers.fire_due(start + Duration::from_secs(6)).is_empty());
+        assert!(matches!(timers.state(), Connection::Closed(_)));
+        assert!(!timers.is_armed(0));
+    }
+
+    #[test]
+    fn fires_each_level_on_its_own_deadline() {
+        let start = Instant::now();
+        let mut timers = connecting();
+        timers.arm(0, start + Duration::from_secs(2), ConnectTimeout);
+        timers.arm(1, start + Duration::from_secs(3), HandshakeTimeout);
+
+        timers.dispatch(Hello.into()).unwrap();
+        timers.arm(1, start + Duration::from_secs(1), HandshakeTimeout);
+        timers.dispatch(Key.into()).unwrap();
+        assert!(matches!(timers.state(), Connection::Open(_)));
+        assert_eq!(timers.next_deadline(), None);
+
+        let mut timers = connecting();
+        timers.arm(0, start + Duration::from_secs(2), ConnectTimeout);
+        timers.arm(1, start + Duration::from_secs(3), HandshakeTimeout);
+        assert!(timers.cancel(1));
+        assert!(!timers.cancel(1));
+        assert!(timers.fire_due(start + Duration::from_secs(4)).is_empty());
+        assert!(matches!(timers.state(), Connection::Closed(_)));
+    }
+}
@@ -0,0 +1,213 @@
+//! Terminal dashboard of running machines, as a [ratatui](https://ratatui.rs) widget.
+
+use std::collections::VecDeque;
+
+use ratatui::buffer::Buffer;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::Stylize;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph, Widget};
+
+use crate::{Machine, TransitionObserver};
+
+enum Step {
+    Transition {
+        from: &'static str,
+        action: &'static str,
+        to: &'static str,
+    },
+    Rejection {
+        state: &'static str,
+        action: &'static str,
+    },
+}
+
+/// What one machine is in, the actions it accepts there and its last steps, as shown by
+/// [`Dashboard`]. Given to the generated `next_observed` to record the steps, and told about the
+/// machine after each one with [`observe`](MachineMonitor::observe).
+pub struct MachineMonitor {
+    title: String,
+    state: &'static str,
+    allowed: &'static [&'static str],
+    trace: VecDeque<Step>,
+    trace_len: usize,
+}
+
+impl MachineMonitor {
+    /// Monitors `machine`, keeping its last `trace_len` steps.
+    pub fn new(title: impl Into<String>, machine: &impl Machine, trace_len: usize) -> Self {
+        MachineMonitor {
+            title: title.into(),
+            state: machine.name(),
+            allowed: machine.allowed_actions(),
+            trace: VecDeque::with_capacity(trace_len),
+            trace_len,
+        }
+    }
+
+    /// Refreshes the state and the allowed actions shown from `machine`.
+    pub fn observe(&mut self, machine: &impl Machine) {
+        self.state = machine.name();
+        self.allowed = machine.allowed_actions();
+    }
+
+    fn record(&mut self, step: Step) {
+        if self.trace.len() == self.trace_len {
+            self.trace.pop_front();
+        }
+        if self.trace_len > 0 {
+            self.trace.push_back(step);
+        }
+    }
+
+    fn lines(&self, height: u16) -> Vec<Line<'static>> {
+        let allowed = if self.allowed.is_empty() {
+            Span::raw("none").italic()
+        } else {
+            Span::raw(self.allowed.join(", "))
+        };
+        let mut lines = vec![
+            Line::from(vec![Span::raw("State: ").bold(), Span::raw(self.state)]),
+            Line::from(vec![Span::raw("Allowed: ").bold(), allowed]),
+            Line::from(Span::raw("Recent steps:").bold()),
+        ];
+        // The most recent steps that fit, oldest first.
+        let shown = usize::from(height).saturating_sub(lines.len());
+        let skipped = self.trace.len().saturating_sub(shown);
+        lines.extend(self.trace.iter().skip(skipped).map(|step| match step {
+            Step::Transition { from, action, to } => {
+                Line::from(format!("{} --{}--> {}", from, action, to))
+            }
+            Step::Rejection { state, action } => {
+                Line::from(format!("{} rejected {}", state, action)).red()
+            }
+        }));
+        lines
+    }
+}
+
+impl TransitionObserver for MachineMonitor {
+    fn on_transition(&mut self, from: &'static str, action: &'static str, to: &'static str) {
+        self.state = to;
+        self.record(Step::Transition { from, action, to });
+    }
+
+    fn on_rejection(&mut self, state: &'static str, action: &'static str) {
+        self.record(Step::Rejection { state, action });
+    }
+}
+
+impl Widget for &MachineMonitor {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let block = Block::bordered().title(self.title.as_str());
+        let lines = self.lines(block.inner(area).height);
+        Paragraph::new(lines).block(block).render(area, buf);
+    }
+}
+
+/// Monitored machines side by side, each in its own bordered column.
+pub struct Dashboard<'a> {
+    monitors: &'a [MachineMonitor],
+}
+
+impl<'a> Dashboard<'a> {
+    pub fn new(monitors: &'a [MachineMonitor]) -> Self {
+        Dashboard { monitors }
+    }
+}
+
+impl Widget for Dashboard<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let columns = Layout::horizontal(self.monitors.iter().map(|_| Constraint::Fill(1)));
+        for (monitor, column) in self.monitors.iter().zip(columns.split(area).iter()) {
+            monitor.render(*column, buf);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{state_machine, Action, State};
+
+    #[derive(Debug)]
+    struct Locked;
+    #[derive(Debug)]
+    struct Unlocked;
+
+    #[derive(Debug)]
+    struct Coin;
+    #[derive(Debug)]
+    struct Push;
+
+    state_machine! {
+        Turnstile,
+        TurnstileAction,
+        Locked { Coin => Unlocked },
+        Unlocked { Push => Locked },
+    }
+
+    impl State<Turnstile, Coin> for Locked {
+        fn next(self, _action: Coin) -> Turnstile {
+            Unlocked.into()
+        }
+    }
+
+    impl State<Turnstile, Push> for Unlocked {
+        fn next(self, _action: Push) -> Turnstile {
+            Locked.into()
+        }
+    }
+
+    fn rows(buf: &Buffer) -> Vec<String> {
+        (0..buf.area.height)
+            .map(|y| {
+                (0..buf.area.width)
+                    .map(|x| buf[(x, y)].symbol())
+                    .collect::<String>()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn shows_the_state_allowed_actions_and_last_steps() {
+        let turnstile = Turnstile::from(Locked);
+        let mut monitor = MachineMonitor::new("gate", &turnstile, 2);
+        let turnstile = turnstile.next_observed(&mut monitor, Coin.into()).unwrap();
+        let e = turnstile
+            .next_observed(&mut monitor, Coin.into())
+            .unwrap_err();
+        let turnstile = e.state.next_observed(&mut monitor, Push.into()).unwrap();
+        monitor.observe(&turnstile);
+
+        let mut buf = Buffer::empty(Rect::new(0, 0, 30, 7));
+        (&monitor).render(buf.area, &mut buf);
+        assert_eq!(
+            rows(&buf),
+            [
+                "┌gate────────────────────────┐",
+                "│State: Locked               │",
+                "│Allowed: Coin               │",
+                "│Recent steps:               │",
+                "│Unlocked rejected Coin      │",
+                "│Unlocked --Push--> Locked   │",
+                "└────────────────────────────┘",
+            ]
+        );
+    }
+
+    #[test]
+    fn lays_machines_out_side_by_side() {
+        let monitors = [
+            MachineMonitor::new("a", &Turnstile::from(Locked), 4),
+            MachineMonitor::new("b", &Turnstile::from(Unlocked), 4),
+        ];
+        let mut buf = Buffer::empty(Rect::new(0, 0, 40, 5));
+        Dashboard::new(&monitors).render(buf.area, &mut buf);
+        let rows = rows(&buf);
+        assert_eq!(rows[0], "┌a─────────────────┐┌b─────────────────┐");
+        assert_eq!(rows[1], "│State: Locked     ││State: Unlocked   │");
+        assert_eq!(rows[2], "│Allowed: Coin     ││Allowed: Push     │");
+    }
+}
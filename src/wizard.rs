@@ -0,0 +1,196 @@
+use crate::Machine;
+
+/// Multi-step form driven by a machine, each state being a step and each accepted action a way to
+/// move on. The states gone through are kept, so the form can go back and show breadcrumbs.
+///
+/// Meant for machines whose transitions form a DAG: a step coming back to an earlier state shows
+/// it again in the breadcrumbs, going back still undoing one step at a time.
+pub struct Wizard<M> {
+    current: M,
+    // States left, oldest first.
+    previous: Vec<M>,
+}
+
+impl<M: Machine + Clone> Wizard<M> {
+    pub fn new(state: M) -> Self {
+        Wizard {
+            current: state,
+            previous: Vec::new(),
+        }
+    }
+
+    pub fn state(&self) -> &M {
+        &self.current
+    }
+
+    pub fn into_inner(self) -> M {
+        self.current
+    }
+
+    /// Name of the state the form is at.
+    pub fn current_step(&self) -> &'static str {
+        self.current.name()
+    }
+
+    /// Number of steps taken to get to the current one.
+    pub fn depth(&self) -> usize {
+        self.previous.len()
+    }
+
+    pub fn can_go_back(&self) -> bool {
+        !self.previous.is_empty()
+    }
+
+    /// Names of the actions moving on from the current step.
+    pub fn next_options(&self) -> &'static [&'static str] {
+        self.current.allowed_actions()
+    }
+
+    /// Names of the steps gone through, the current one last.
+    pub fn breadcrumbs(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.previous
+            .iter()
+            .chain([&self.current])
+            .map(Machine::name)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.current.is_finished()
+    }
+
+    /// Moves on to the step `action` leads to. A rejected action is given back, the form staying
+    /// at the current step.
+    pub fn advance(&mut self, action: M::Action) -> Result<(), M::Action> {
+        match self.current.clone().next(action).map_err(Into::into) {
+            Ok(next) => {
+                let left = core::mem::replace(&mut self.current, next);
+                self.previous.push(left);
+                Ok(())
+            }
+            Err((_, action)) => Err(action),
+        }
+    }
+
+    /// Goes back to the previous step, as it was when it was left. Returns whether there was one.
+    pub fn back(&mut self) -> bool {
+        match self.previous.pop() {
+            Some(previous) => {
+                self.current = previous;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{state_machine, Action, State};
+
+    #[derive(Debug, Clone)]
+    struct Account;
+    #[derive(Debug, Clone)]
+    struct Address(&'static str);
+    #[derive(Debug, Clone)]
+    struct Review(&'static str);
+    #[derive(Debug, Clone)]
+    struct Submitted;
+
+    #[derive(Debug, Clone)]
+    struct Continue(&'static str);
+    #[derive(Debug, Clone)]
+    struct SkipAddress;
+    #[derive(Debug, Clone)]
+    struct Submit;
+
+    state_machine! {
+        #[derive(Debug, Clone)] Signup,
+        #[derive(Debug, Clone)] SignupAction,
+        terminal Submitted,
+        Account {
+            Continue => Address,
+            SkipAddress => Review,
+        },
+        Address { Continue => Review },
+        Review { Submit => Submitted },
+        Submitted {},
+    }
+
+    impl State<Signup, Continue> for Account {
+        fn next(self, action: Continue) -> Signup {
+            Address(action.0).into()
+        }
+    }
+
+    impl State<Signup, SkipAddress> for Account {
+        fn next(self, _action: SkipAddress) -> Signup {
+            Review("no address").into()
+        }
+    }
+
+    impl State<Signup, Continue> for Address {
+        fn next(self, action: Continue) -> Signup {
+            Review(action.0).into()
+        }
+    }
+
+    impl State<Signup, Submit> for Review {
+        fn next(self, _action: Submit) -> Signup {
+            Submitted.into()
+        }
+    }
+
+    #[test]
+    fn walks_through_the_steps() {
+        let mut wizard = Wizard::new(Signup::from(Account));
+        assert_eq!(wizard.current_step(), "Account");
+        assert_eq!(wizard.next_options(), ["Continue", "SkipAddress"]);
+        assert!(!wizard.can_go_back());
+
+        wizard.advance(Continue("me").into()).unwrap();
+        wizard.advance(Continue("12 main st").into()).unwrap();
+        assert_eq!(wizard.depth(), 2);
+        assert!(matches!(
+            wizard.state(),
+            Signup::Review(Review("12 main st"))
+        ));
+        assert_eq!(
+            wizard.breadcrumbs().collect::<Vec<_>>(),
+            ["Account", "Address", "Review"]
+        );
+
+        wizard.advance(Submit.into()).unwrap();
+        assert!(wizard.is_finished());
+        assert!(wizard.next_options().is_empty());
+    }
+
+    #[test]
+    fn goes_back_to_the_step_as_it_was_left() {
+        let mut wizard = Wizard::new(Signup::from(Account));
+        wizard.advance(Continue("me").into()).unwrap();
+        wizard.advance(Continue("12 main st").into()).unwrap();
+
+        assert!(wizard.back());
+        assert!(matches!(wizard.state(), Signup::Address(Address("me"))));
+        assert!(wizard.back());
+        assert!(!wizard.back());
+        assert_eq!(wizard.current_step(), "Account");
+
+        wizard.advance(SkipAddress.into()).unwrap();
+        assert_eq!(
+            wizard.breadcrumbs().collect::<Vec<_>>(),
+            ["Account", "Review"]
+        );
+    }
+
+    #[test]
+    fn stays_at_the_step_rejecting_an_action() {
+        let mut wizard = Wizard::new(Signup::from(Account));
+        let rejected = wizard.advance(Submit.into()).unwrap_err();
+        assert_eq!(rejected.name(), "Submit");
+        assert_eq!(wizard.current_step(), "Account");
+        assert!(!wizard.can_go_back());
+    }
+}
@@ -0,0 +1,68 @@
+use state_machine::{state_machine, Action, InvalidAction, State};
+
+#[derive(Debug)]
+pub struct Idle;
+#[derive(Debug)]
+pub struct Heating(pub u8);
+
+#[derive(Debug)]
+pub struct SetPoint(u8);
+#[derive(Debug)]
+pub struct TurnOn;
+#[derive(Debug)]
+pub struct TurnOff;
+
+state_machine! {
+    Oven,
+    OvenAction,
+    unit TurnOn | TurnOff,
+    validate SetPoint(u8) where 50..=250,
+    Idle { TurnOn => Heating },
+    Heating {
+        SetPoint => Heating,
+        TurnOff => Idle,
+    },
+}
+
+impl State<Oven, TurnOn> for Idle {
+    fn next(self, _action: TurnOn) -> Oven {
+        Heating(180).into()
+    }
+}
+
+impl State<Oven, SetPoint> for Heating {
+    fn next(self, action: SetPoint) -> Oven {
+        Heating(action.0).into()
+    }
+}
+
+impl State<Oven, TurnOff> for Heating {
+    fn next(self, _action: TurnOff) -> Oven {
+        Idle.into()
+    }
+}
+
+#[test]
+fn unit_actions_have_constants() {
+    let oven = Oven::from(Idle).next(OvenAction::TURN_ON).unwrap();
+    assert!(matches!(oven, Oven::Heating(Heating(180))));
+
+    let oven = oven.next(OvenAction::TURN_OFF).unwrap();
+    assert_eq!(oven.name(), "Idle");
+}
+
+#[test]
+fn validated_actions_only_carry_accepted_payloads() {
+    assert_eq!(
+        SetPoint::new(20).unwrap_err(),
+        InvalidAction { action: "SetPoint" }
+    );
+    assert_eq!(
+        SetPoint::new(251).unwrap_err().to_string(),
+        "Invalid payload for action SetPoint"
+    );
+
+    let oven = Oven::from(Idle).next(OvenAction::TURN_ON).unwrap();
+    let oven = oven.next(SetPoint::new(220).unwrap().into()).unwrap();
+    assert!(matches!(oven, Oven::Heating(Heating(220))));
+}
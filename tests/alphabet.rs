@@ -0,0 +1,122 @@
+use state_machine::{state_machine, Action, State};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Num(u32),
+    Plus,
+    Op { symbol: char },
+    Ident(String),
+    Eof,
+}
+
+#[derive(Debug)]
+pub struct Start;
+#[derive(Debug)]
+pub struct Number(u32);
+#[derive(Debug)]
+pub struct Operator;
+#[derive(Debug)]
+pub struct Finished;
+
+state_machine! {
+    #[derive(Debug)] Parser,
+    #[derive(Debug, Clone)] ParserAction,
+    alphabet Token,
+    terminal Finished,
+    Start {
+        Token::Num(0) => Start,
+        Token::Num(n) if |_: &Start, _: &Num| *n < 1000 => Number,
+        Eof => Finished,
+    },
+    Number {
+        Plus | Token::Op { symbol: '-' } => Operator,
+        Eof => Finished,
+    },
+    Operator { Token::Num(_) => Number },
+}
+
+impl State<Parser, Num> for Start {
+    fn next(self, Num(token): Num) -> Parser {
+        match token {
+            Token::Num(0) => Start.into(),
+            Token::Num(n) => Number(n).into(),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl State<Parser, Eof> for Start {
+    fn next(self, _action: Eof) -> Parser {
+        Finished.into()
+    }
+}
+
+impl State<Parser, Plus> for Number {
+    fn next(self, _action: Plus) -> Parser {
+        Operator.into()
+    }
+}
+
+impl State<Parser, Op> for Number {
+    fn next(self, _action: Op) -> Parser {
+        Operator.into()
+    }
+}
+
+impl State<Parser, Eof> for Number {
+    fn next(self, _action: Eof) -> Parser {
+        Finished.into()
+    }
+}
+
+impl State<Parser, Num> for Operator {
+    fn next(self, Num(token): Num) -> Parser {
+        match token {
+            Token::Num(n) => Number(n).into(),
+            _ => unreachable!(),
+        }
+    }
+}
+
+fn action(token: Token) -> ParserAction {
+    token.try_into().unwrap()
+}
+
+#[test]
+fn patterns_pick_the_transition() {
+    let parser = Parser::from(Start).next(action(Token::Num(0))).unwrap();
+    assert!(matches!(parser, Parser::Start(_)));
+    let parser = parser.next(action(Token::Num(42))).unwrap();
+    assert!(matches!(parser, Parser::Number(Number(42))));
+
+    let e = parser.next(action(Token::Op { symbol: '*' })).unwrap_err();
+    assert_eq!(Token::from(e.action), Token::Op { symbol: '*' });
+    let parser = e.state.next(action(Token::Op { symbol: '-' })).unwrap();
+    let parser = parser.next(action(Token::Num(0))).unwrap();
+    assert!(parser.next(action(Token::Eof)).unwrap().is_finished());
+}
+
+#[test]
+fn bindings_reach_the_guard() {
+    let e = Parser::from(Start)
+        .next(action(Token::Num(1000)))
+        .unwrap_err();
+    assert!(matches!(e.action, ParserAction::Num(Num(Token::Num(1000)))));
+    assert_eq!(Parser::from(Start).allowed_actions(), ["Num", "Eof"]);
+}
+
+#[test]
+fn gives_back_the_variants_the_machine_does_not_name() {
+    assert_eq!(
+        ParserAction::try_from(Token::Ident("x".into())).unwrap_err(),
+        Token::Ident("x".into())
+    );
+    assert_eq!(ParserAction::try_from(Token::Plus).unwrap().name(), "Plus");
+}
+
+#[test]
+fn diagrams_show_the_patterns() {
+    let dot = Parser::dot();
+    assert!(dot.contains(r#"Start -> Start [label="Num matching Token :: Num(0)"];"#));
+    assert!(dot.contains(r#"Operator -> Number [label="Num"];"#));
+}
@@ -0,0 +1,48 @@
+use std::future::Future;
+use std::pin::pin;
+use std::task::{Context, Poll, Waker};
+
+use state_machine::{state_machine, Action, AsyncState};
+
+// The handlers below never wait on anything, so polling once is enough.
+fn block_on<F: Future>(future: F) -> F::Output {
+    match pin!(future).poll(&mut Context::from_waker(Waker::noop())) {
+        Poll::Ready(output) => output,
+        Poll::Pending => panic!("future was not ready"),
+    }
+}
+
+async fn fetch(len: usize) -> usize {
+    len
+}
+
+#[derive(Debug)]
+pub struct Idle;
+#[derive(Debug)]
+pub struct Received(pub usize);
+
+#[derive(Debug)]
+pub struct Request(pub &'static str);
+
+state_machine! {
+    async Client,
+    ClientAction,
+    terminal Received,
+    Idle { Request => Received },
+    Received {},
+}
+
+impl AsyncState<Client, Request> for Idle {
+    async fn next(self, action: Request) -> Client {
+        Received(fetch(action.0.len()).await).into()
+    }
+}
+
+#[test]
+fn awaits_the_handler() {
+    let client = block_on(Client::from(Idle).next(Request("hello").into())).unwrap();
+    assert!(matches!(client, Client::Received(Received(5))));
+
+    let e = block_on(client.next(Request("again").into())).unwrap_err();
+    assert_eq!(e.state.name(), "Received");
+}
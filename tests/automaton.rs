@@ -0,0 +1,174 @@
+#![cfg(feature = "std")]
+
+use state_machine::{state_machine, Action, State};
+
+#[derive(Debug, Default)]
+pub struct Empty;
+#[derive(Debug)]
+pub struct Filling;
+#[derive(Debug)]
+pub struct Full;
+
+#[derive(Debug)]
+pub struct Pour;
+#[derive(Debug)]
+pub struct Drain;
+#[derive(Debug)]
+pub struct Spill;
+#[derive(Debug)]
+pub struct Tip;
+
+state_machine! {
+    Tank,
+    TankAction,
+    initial Empty,
+    terminal Full,
+    deprecated Tip => Spill,
+    Empty { Pour => Filling },
+    Filling {
+        Pour if |_: &Filling, _: &Pour| true => Full,
+        Pour => Filling,
+        Drain => Empty,
+        Spill => Empty,
+    },
+    Full { Drain => Empty },
+}
+
+impl State<Tank, Pour> for Empty {
+    fn next(self, _action: Pour) -> Tank {
+        Filling.into()
+    }
+}
+
+impl State<Tank, Pour> for Filling {
+    fn next(self, _action: Pour) -> Tank {
+        Full.into()
+    }
+}
+
+impl State<Tank, Drain> for Filling {
+    fn next(self, _action: Drain) -> Tank {
+        Empty.into()
+    }
+}
+
+impl State<Tank, Spill> for Filling {
+    fn next(self, _action: Spill) -> Tank {
+        Empty.into()
+    }
+}
+
+impl State<Tank, Drain> for Full {
+    fn next(self, _action: Drain) -> Tank {
+        Empty.into()
+    }
+}
+
+impl From<Tip> for Spill {
+    fn from(_: Tip) -> Spill {
+        Spill
+    }
+}
+
+#[test]
+fn validates_sequences_against_every_possible_state() {
+    let validator = TankValidator::validate(&[TankActionKind::Pour, TankActionKind::Pour]).unwrap();
+    assert_eq!(
+        validator.possible_states().collect::<Vec<_>>(),
+        [TankKind::Filling, TankKind::Full]
+    );
+    assert!(validator.is_accepting());
+    assert_eq!(TankValidator::validate(&[TankActionKind::Drain]), Err(0));
+}
+
+#[test]
+fn accepts_sequences_ending_in_a_terminal_state() {
+    use TankActionKind::*;
+    assert!(Tank::accepts(&[Pour, Pour]));
+    assert!(!Tank::accepts(&[Pour]));
+    assert!(!Tank::accepts(&[Drain, Pour, Pour]));
+    assert_eq!(
+        Tank::accepted_sequences(4),
+        [
+            vec![Pour, Pour],
+            vec![Pour, Pour, Pour],
+            vec![Pour, Pour, Pour, Pour],
+            vec![Pour, Drain, Pour, Pour],
+            vec![Pour, Spill, Pour, Pour],
+        ]
+    );
+}
+
+#[test]
+fn finds_the_shortest_path_to_a_state() {
+    use TankActionKind::*;
+    assert_eq!(Tank::reachability_witness(TankKind::Empty), Some(vec![]));
+    assert_eq!(
+        Tank::reachability_witness(TankKind::Full),
+        Some(vec![Pour, Pour])
+    );
+}
+
+#[test]
+fn accepts_actions_from_the_same_table() {
+    let filling = Tank::from(Filling);
+    assert!(filling.accepts_action(&Pour.into()));
+    assert!(filling.accepts_action(&Spill.into()));
+    #[allow(deprecated)]
+    let tip = TankAction::Tip(Tip);
+    assert!(filling.accepts_action(&tip));
+    assert!(!Tank::new().accepts_action(&Drain.into()));
+    for (action, _) in TankKind::Filling.edges() {
+        assert_ne!(*action, TankActionKind::Tip);
+    }
+}
+
+#[test]
+fn tables_leave_deprecated_actions_out() {
+    assert_eq!(
+        Tank::from(Filling).allowed_actions(),
+        ["Pour", "Drain", "Spill"]
+    );
+    assert!(Tank::TRANSITIONS
+        .iter()
+        .all(|(_, action, _)| *action != "Tip"));
+    assert!(Tank::ACTIONS.contains(&"Tip"));
+}
+
+#[test]
+fn compares_the_accepted_sequences_with_a_specification() {
+    use state_machine::automaton::Language;
+    use TankActionKind::*;
+
+    let tank = Tank::language();
+    assert!(tank.accepts(&[Pour, Spill, Pour, Pour]));
+    assert!(!tank.accepts(&[Tip]));
+
+    // Anything ending with a pour.
+    let ends_with_pour = Language::from_nfa(
+        vec![Pour, Drain, Spill],
+        0,
+        &[1],
+        vec![
+            vec![(Pour, vec![0, 1]), (Drain, vec![0]), (Spill, vec![0])],
+            vec![],
+        ],
+    );
+    assert!(tank.difference(&ends_with_pour).is_empty());
+    assert!(!ends_with_pour.difference(&tank).is_empty());
+
+    // Spill belongs to the alphabet, so that the complement has the sequences spilling.
+    let never_spills = Language::from_nfa(
+        vec![Pour, Drain, Spill],
+        0,
+        &[0],
+        vec![vec![(Pour, vec![0]), (Drain, vec![0])]],
+    );
+    assert_eq!(
+        tank.difference(&never_spills).shortest_word(),
+        Some(vec![Pour, Spill, Pour, Pour])
+    );
+    assert!(tank
+        .intersection(&never_spills.complement())
+        .accepts(&[Pour, Spill, Pour, Pour]));
+}
@@ -0,0 +1,106 @@
+use std::time::Duration;
+
+use state_machine::blend::{Blend, BlendState, Easing};
+use state_machine::{state_machine, Action, State};
+
+#[derive(Debug)]
+pub struct Idle;
+#[derive(Debug)]
+pub struct Walking;
+#[derive(Debug)]
+pub struct Running;
+
+#[derive(Debug)]
+pub struct Go;
+#[derive(Debug)]
+pub struct Faster;
+#[derive(Debug)]
+pub struct Stop;
+
+state_machine! {
+    Gait,
+    GaitAction,
+    * { blend(300, EaseOut) Stop => Idle },
+    Idle { blend(200, EaseIn) Go => Walking },
+    Walking { blend(100, Linear) Faster => Running },
+    Running { Go => Walking },
+}
+
+impl State<Gait, Go> for Idle {
+    fn next(self, _action: Go) -> Gait {
+        Walking.into()
+    }
+}
+
+impl State<Gait, Faster> for Walking {
+    fn next(self, _action: Faster) -> Gait {
+        Running.into()
+    }
+}
+
+impl State<Gait, Go> for Running {
+    fn next(self, _action: Go) -> Gait {
+        Walking.into()
+    }
+}
+
+impl State<Gait, Stop> for Gait {
+    fn next(self, _action: Stop) -> Gait {
+        Idle.into()
+    }
+}
+
+/// Speed the animation plays at in each state.
+fn speed(gait: &Gait) -> f32 {
+    match gait {
+        Gait::Idle(_) => 0.0,
+        Gait::Walking(_) => 1.0,
+        Gait::Running(_) => 3.0,
+    }
+}
+
+#[test]
+fn transitions_tell_their_blend() {
+    let idle = Gait::from(Idle);
+    assert_eq!(
+        idle.blend(&Go.into()),
+        Some(Blend {
+            duration: Duration::from_millis(200),
+            easing: Easing::EaseIn,
+        })
+    );
+    assert_eq!(idle.blend(&Faster.into()), None);
+    assert_eq!(
+        Gait::from(Running).blend(&Stop.into()),
+        Some(Blend {
+            duration: Duration::from_millis(300),
+            easing: Easing::EaseOut,
+        })
+    );
+    assert_eq!(Gait::from(Running).blend(&Go.into()), None);
+}
+
+#[test]
+fn blends_the_outputs_of_the_states() {
+    let walking = Gait::from(Walking);
+    let action = Faster.into();
+    let blend = walking.blend(&action).unwrap();
+    let from = speed(&walking);
+    let running = walking.next(action).unwrap();
+
+    let mut speed = BlendState::new(from, speed(&running), blend);
+    speed.advance(Duration::from_millis(50));
+    assert_eq!(speed.value(), 2.0);
+    speed.advance(Duration::from_millis(50));
+    assert!(speed.is_done());
+    assert_eq!(speed.value(), 3.0);
+}
+
+#[test]
+fn introspection_lists_the_blends() {
+    let json = Gait::introspection();
+    assert!(json.contains(
+        r#"{"state": "Idle", "action": "Go", "next": ["Walking"], "blend": {"millis": 200, "easing": "EaseIn"}}"#
+    ));
+    assert!(json.contains(r#"{"state": "Running", "action": "Go", "next": ["Walking"]}"#));
+}
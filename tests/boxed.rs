@@ -0,0 +1,64 @@
+use std::mem::size_of;
+
+use state_machine::{state_machine, Action, State};
+
+#[derive(Debug)]
+pub struct Empty;
+#[derive(Debug)]
+pub struct Full(pub [u8; 1024]);
+
+#[derive(Debug)]
+pub struct Fill(pub u8);
+#[derive(Debug)]
+pub struct Drain;
+
+state_machine! {
+    Buffer,
+    BufferAction,
+    boxed Full,
+    max_size 16,
+    Empty { Fill => Full },
+    Full {
+        Fill => Full,
+        Drain => Empty,
+    },
+}
+
+impl State<Buffer, Fill> for Empty {
+    fn next(self, action: Fill) -> Buffer {
+        Full([action.0; 1024]).into()
+    }
+}
+
+impl State<Buffer, Fill> for Full {
+    fn next(mut self, action: Fill) -> Buffer {
+        self.0[0] = action.0;
+        self.into()
+    }
+}
+
+impl State<Buffer, Drain> for Full {
+    fn next(self, _action: Drain) -> Buffer {
+        Empty.into()
+    }
+}
+
+#[test]
+fn boxed_states_keep_the_wrapper_small() {
+    assert!(size_of::<Buffer>() <= 16);
+    assert_eq!(Buffer::VARIANT_SIZES, [("Empty", 0), ("Full", 1024)]);
+}
+
+#[test]
+fn boxed_states_take_and_give_back_actions() {
+    let buffer = Buffer::from(Empty).next(Fill(7).into()).unwrap();
+    let buffer = buffer.next(Fill(9).into()).unwrap();
+    match &buffer {
+        Buffer::Full(full) => assert_eq!((full.0[0], full.0[1]), (9, 7)),
+        other => panic!("expected Full, got {:?}", other),
+    }
+
+    let buffer = buffer.next(Drain.into()).unwrap();
+    let e = buffer.next(Drain.into()).unwrap_err();
+    assert_eq!(e.state.name(), "Empty");
+}
@@ -0,0 +1,79 @@
+use state_machine::{state_machine, Action, ContextState};
+
+#[derive(Debug, Default)]
+pub struct Ledger {
+    pub balance: i64,
+    pub entries: usize,
+}
+
+#[derive(Debug)]
+pub struct Open;
+#[derive(Debug)]
+pub struct Closed;
+
+#[derive(Debug)]
+pub struct Deposit(pub i64);
+#[derive(Debug)]
+pub struct Withdraw(pub i64);
+#[derive(Debug)]
+pub struct Close;
+
+state_machine! {
+    Account,
+    AccountAction,
+    ctx Ledger,
+    terminal Closed,
+    Open {
+        Deposit => Open,
+        Withdraw => Open,
+        Close => Closed,
+    },
+    Closed {},
+}
+
+impl ContextState<Account, Deposit, Ledger> for Open {
+    fn next(self, action: Deposit, ledger: &mut Ledger) -> Account {
+        ledger.balance += action.0;
+        ledger.entries += 1;
+        self.into()
+    }
+}
+
+impl ContextState<Account, Withdraw, Ledger> for Open {
+    fn next(self, action: Withdraw, ledger: &mut Ledger) -> Account {
+        ledger.balance -= action.0;
+        ledger.entries += 1;
+        self.into()
+    }
+}
+
+impl ContextState<Account, Close, Ledger> for Open {
+    fn next(self, _action: Close, _ledger: &mut Ledger) -> Account {
+        Closed.into()
+    }
+}
+
+#[test]
+fn handlers_share_the_context() {
+    let mut ledger = Ledger::default();
+    let account = Account::from(Open)
+        .next(Deposit(50).into(), &mut ledger)
+        .unwrap()
+        .next(Withdraw(20).into(), &mut ledger)
+        .unwrap()
+        .next(Close.into(), &mut ledger)
+        .unwrap();
+    assert_eq!(account.name(), "Closed");
+    assert_eq!(ledger.balance, 30);
+    assert_eq!(ledger.entries, 2);
+}
+
+#[test]
+fn rejected_actions_leave_the_context_alone() {
+    let mut ledger = Ledger::default();
+    let e = Account::from(Closed)
+        .next(Deposit(50).into(), &mut ledger)
+        .unwrap_err();
+    assert_eq!(e.state.name(), "Closed");
+    assert_eq!(ledger.entries, 0);
+}
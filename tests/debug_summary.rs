@@ -0,0 +1,70 @@
+use state_machine::{state_machine, Action, State};
+
+#[derive(Debug)]
+pub struct Idle;
+#[derive(Debug)]
+pub struct Busy {
+    pub job: u32,
+}
+#[derive(Debug)]
+pub struct Off;
+
+#[derive(Debug)]
+pub struct Start(pub u32);
+#[derive(Debug)]
+pub struct Stop;
+#[derive(Debug)]
+pub struct PowerOff;
+
+state_machine! {
+    Worker,
+    WorkerAction,
+    debug_summary,
+    terminal Off,
+    Idle {
+        Start => Busy,
+        PowerOff => Off,
+    },
+    Busy { Stop => Idle },
+    Off {},
+}
+
+impl State<Worker, Start> for Idle {
+    fn next(self, action: Start) -> Worker {
+        Busy { job: action.0 }.into()
+    }
+}
+
+impl State<Worker, PowerOff> for Idle {
+    fn next(self, _action: PowerOff) -> Worker {
+        Off.into()
+    }
+}
+
+impl State<Worker, Stop> for Busy {
+    fn next(self, _action: Stop) -> Worker {
+        Idle.into()
+    }
+}
+
+#[test]
+fn debug_lists_the_accepted_actions() {
+    let worker = Worker::from(Idle);
+    assert_eq!(
+        format!("{:?}", worker),
+        "Worker::Idle { Idle } accepts [Start, PowerOff]"
+    );
+
+    let worker = worker.next(Start(4).into()).unwrap();
+    assert_eq!(
+        format!("{:?}", worker),
+        "Worker::Busy { Busy { job: 4 } } accepts [Stop]"
+    );
+
+    let worker = worker
+        .next(Stop.into())
+        .unwrap()
+        .next(PowerOff.into())
+        .unwrap();
+    assert_eq!(format!("{:?}", worker), "Worker::Off { Off } accepts []");
+}
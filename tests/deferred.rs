@@ -0,0 +1,76 @@
+#![cfg(feature = "std")]
+
+use state_machine::deferred::Deferring;
+use state_machine::{state_machine, Action, State};
+
+#[derive(Debug)]
+pub struct S0;
+#[derive(Debug)]
+pub struct S1;
+#[derive(Debug)]
+pub struct S2;
+#[derive(Debug)]
+pub struct S3;
+
+#[derive(Debug)]
+pub struct P;
+#[derive(Debug)]
+pub struct Q;
+#[derive(Debug)]
+pub struct Go;
+
+state_machine! {
+    Machine,
+    MachineAction,
+    terminal S3,
+    S0 {
+        defer P | Q,
+        Go => S1,
+    },
+    S1 { Q => S2 },
+    S2 { P => S3 },
+}
+
+impl State<Machine, Go> for S0 {
+    fn next(self, _action: Go) -> Machine {
+        S1.into()
+    }
+}
+
+impl State<Machine, Q> for S1 {
+    fn next(self, _action: Q) -> Machine {
+        S2.into()
+    }
+}
+
+impl State<Machine, P> for S2 {
+    fn next(self, _action: P) -> Machine {
+        S3.into()
+    }
+}
+
+#[test]
+fn deferred_actions_wait_for_the_transition() {
+    let mut runner = Deferring::new(Machine::from(S0));
+    runner.send(P.into()).unwrap();
+    runner.send(Q.into()).unwrap();
+    assert_eq!(runner.deferred().count(), 2);
+    assert_eq!(runner.state().name(), "S0");
+}
+
+#[test]
+fn replay_goes_on_while_actions_are_accepted() {
+    let mut runner = Deferring::new(Machine::from(S0));
+    runner.send(P.into()).unwrap();
+    runner.send(Q.into()).unwrap();
+    runner.send(Go.into()).unwrap();
+    assert_eq!(runner.deferred().count(), 0);
+    assert_eq!(runner.into_inner().name(), "S3");
+}
+
+#[test]
+fn rejected_actions_are_given_back() {
+    let mut runner = Deferring::new(Machine::from(S1));
+    assert!(matches!(runner.send(Go.into()), Err(MachineAction::Go(_))));
+    assert_eq!(runner.state().name(), "S1");
+}
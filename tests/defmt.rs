@@ -0,0 +1,89 @@
+#![cfg(feature = "defmt")]
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use state_machine::defmt;
+use state_machine::logging::DefmtLogger;
+use state_machine::{state_machine, Action, State};
+
+#[derive(Debug)]
+pub struct Off;
+#[derive(Debug)]
+pub struct On(pub u8);
+
+#[derive(Debug)]
+pub struct Toggle;
+#[derive(Debug)]
+pub struct Dim(pub u8);
+
+state_machine! {
+    Lamp,
+    LampAction,
+    Off { Toggle => On },
+    On {
+        Toggle => Off,
+        Dim => On,
+    },
+}
+
+impl State<Lamp, Toggle> for Off {
+    fn next(self, _action: Toggle) -> Lamp {
+        On(255).into()
+    }
+}
+
+impl State<Lamp, Toggle> for On {
+    fn next(self, _action: Toggle) -> Lamp {
+        Off.into()
+    }
+}
+
+impl State<Lamp, Dim> for On {
+    fn next(self, action: Dim) -> Lamp {
+        On(action.0).into()
+    }
+}
+
+static TAKEN: AtomicBool = AtomicBool::new(false);
+static WRITTEN: AtomicUsize = AtomicUsize::new(0);
+
+defmt::timestamp!("");
+
+#[defmt::global_logger]
+struct CountingLogger;
+
+unsafe impl defmt::Logger for CountingLogger {
+    fn acquire() {
+        while TAKEN.swap(true, Ordering::Acquire) {}
+    }
+
+    unsafe fn flush() {}
+
+    unsafe fn release() {
+        TAKEN.store(false, Ordering::Release);
+    }
+
+    unsafe fn write(bytes: &[u8]) {
+        WRITTEN.fetch_add(bytes.len(), Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn wrappers_and_kinds_are_formatted_as_names() {
+    let lamp = Lamp::from(On(3));
+    let action = LampAction::from(Dim(1));
+    let before = WRITTEN.load(Ordering::Relaxed);
+    defmt::println!("{} {} {} {}", lamp, lamp.kind(), action, action.kind());
+    assert!(WRITTEN.load(Ordering::Relaxed) > before);
+}
+
+// Transitions and rejections are logged at levels filtered out unless `DEFMT_LOG` enables them.
+#[test]
+fn logs_through_the_observer() {
+    let mut logger = DefmtLogger;
+    let lamp = Lamp::from(Off);
+    let lamp = lamp.next_observed(&mut logger, Toggle.into()).unwrap();
+    let lamp = lamp.next_observed(&mut logger, Toggle.into()).unwrap();
+    let e = lamp.next_observed(&mut logger, Dim(1).into()).unwrap_err();
+    assert_eq!(e.state.name(), "Off");
+}
@@ -0,0 +1,103 @@
+use state_machine::{state_machine, Action, State};
+
+#[derive(Debug, Default)]
+pub struct Idle;
+#[derive(Debug)]
+pub struct Busy;
+#[derive(Debug)]
+pub struct Off;
+
+#[derive(Debug)]
+pub struct Start;
+#[derive(Debug)]
+pub struct Stop;
+#[derive(Debug)]
+pub struct PowerOff;
+
+state_machine! {
+    Worker,
+    WorkerAction,
+    initial Idle,
+    terminal Off,
+    Idle {
+        Start => Busy,
+        PowerOff => Off,
+    },
+    Busy { Stop => Idle | Off },
+    Off {},
+}
+
+impl State<Worker, Start> for Idle {
+    fn next(self, _action: Start) -> Worker {
+        Busy.into()
+    }
+}
+
+impl State<Worker, PowerOff> for Idle {
+    fn next(self, _action: PowerOff) -> Worker {
+        Off.into()
+    }
+}
+
+impl State<Worker, Stop> for Busy {
+    fn next(self, _action: Stop) -> Worker {
+        Idle.into()
+    }
+}
+
+#[test]
+fn renders_dot() {
+    assert_eq!(
+        Worker::dot(),
+        r#"digraph Worker {
+    __start [shape=point];
+    __start -> Idle;
+    Off [shape=doublecircle];
+    Idle -> Busy [label="Start"];
+    Idle -> Off [label="PowerOff"];
+    Busy -> Idle [label="Stop"];
+    Busy -> Off [label="Stop"];
+}
+"#
+    );
+}
+
+#[test]
+fn renders_plantuml() {
+    assert_eq!(
+        Worker::plantuml(),
+        "@startuml
+[*] --> Idle
+Idle --> Busy : Start
+Idle --> Off : PowerOff
+Busy --> Idle : Stop
+Busy --> Off : Stop
+Off --> [*]
+@enduml
+"
+    );
+}
+
+#[test]
+fn describes_the_machine_in_json() {
+    let expected = r#"{
+  "schema": 1,
+  "name": "Worker",
+  "hash": "HASH",
+  "initial": "Idle",
+  "terminal": ["Off"],
+  "states": ["Idle", "Busy", "Off"],
+  "actions": ["Start", "PowerOff", "Stop"],
+  "deprecated": [],
+  "transitions": [
+    {"state": "Idle", "action": "Start", "next": ["Busy"]},
+    {"state": "Idle", "action": "PowerOff", "next": ["Off"]},
+    {"state": "Busy", "action": "Stop", "next": ["Idle", "Off"]}
+  ]
+}
+"#;
+    assert_eq!(
+        Worker::introspection(),
+        expected.replace("HASH", &format!("{:016x}", Worker::MACHINE_HASH))
+    );
+}
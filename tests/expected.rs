@@ -0,0 +1,95 @@
+use state_machine::{state_machine, Action, State};
+
+#[derive(Debug)]
+pub struct Idle;
+#[derive(Debug)]
+pub struct Busy;
+
+#[derive(Debug)]
+pub struct Start;
+#[derive(Debug)]
+pub struct Beta;
+#[derive(Debug)]
+pub struct Turbo;
+#[derive(Debug)]
+pub struct Noise(pub u8);
+#[derive(Debug)]
+pub struct Reset;
+#[derive(Debug)]
+pub struct Old;
+
+state_machine! {
+    Worker,
+    WorkerAction,
+    terminal Busy,
+    deprecated Old => Start,
+    Idle {
+        reject Reset,
+        Start => Busy,
+        experimental Beta => Busy,
+        optional(Fast) Turbo => Busy,
+        Noise if |_: &Idle, n: &Noise| n.0 > 3 => Busy,
+    },
+}
+
+impl State<Worker, Start> for Idle {
+    fn next(self, _action: Start) -> Worker {
+        Busy.into()
+    }
+}
+
+impl State<Worker, Beta> for Idle {
+    fn next(self, _action: Beta) -> Worker {
+        Busy.into()
+    }
+}
+
+impl State<Worker, Turbo> for Idle {
+    fn next(self, _action: Turbo) -> Worker {
+        Busy.into()
+    }
+}
+
+impl State<Worker, Noise> for Idle {
+    fn next(self, _action: Noise) -> Worker {
+        Busy.into()
+    }
+}
+
+impl From<Old> for Start {
+    fn from(_: Old) -> Start {
+        Start
+    }
+}
+
+#[test]
+fn lists_the_actions_enabled_for_the_call() {
+    let e = Worker::from(Idle).next(Beta.into()).unwrap_err();
+    assert_eq!(e.expected, ["Start", "Noise"]);
+    assert_eq!(
+        e.to_string(),
+        r#"State Idle rejected action Beta, expected one of ["Start", "Noise"]"#
+    );
+
+    let e = Worker::from(Idle)
+        .next_with(Reset.into(), true)
+        .unwrap_err();
+    assert_eq!(e.expected, ["Start", "Beta", "Noise"]);
+}
+
+#[test]
+fn leaves_the_rejected_action_out() {
+    let e = Worker::from(Idle).next(Noise(1).into()).unwrap_err();
+    assert_eq!(e.expected, ["Start"]);
+    assert!(!e.expected.contains("Noise"));
+}
+
+#[test]
+fn lists_the_optional_transitions_of_the_enabled_flags() {
+    let flagged = WorkerFlagged::new(Idle.into(), WorkerFlagged::FAST);
+    let e = flagged.next(Reset.into()).unwrap_err();
+    assert_eq!(e.expected, ["Start", "Turbo", "Noise"]);
+
+    let e = Worker::from(Idle).next(Turbo.into()).unwrap_err();
+    assert_eq!(e.expected, ["Start", "Noise"]);
+}
@@ -0,0 +1,98 @@
+use state_machine::{state_machine, Action, Machine, State};
+
+#[derive(Debug)]
+pub struct Idle;
+#[derive(Debug)]
+pub struct Busy;
+#[derive(Debug)]
+pub struct Fast;
+
+#[derive(Debug)]
+pub struct Start;
+#[derive(Debug)]
+pub struct Beta;
+#[derive(Debug)]
+pub struct Turbo;
+#[derive(Debug)]
+pub struct Nitro;
+
+state_machine! {
+    Worker,
+    WorkerAction,
+    terminal Busy,
+    terminal Fast,
+    Idle {
+        Start => Busy,
+        experimental Beta => Busy,
+        optional(Turbo) Turbo => Fast,
+        optional(Nitro) Nitro => Fast,
+    },
+}
+
+impl State<Worker, Start> for Idle {
+    fn next(self, _action: Start) -> Worker {
+        Busy.into()
+    }
+}
+
+impl State<Worker, Beta> for Idle {
+    fn next(self, _action: Beta) -> Worker {
+        Busy.into()
+    }
+}
+
+impl State<Worker, Turbo> for Idle {
+    fn next(self, _action: Turbo) -> Worker {
+        Fast.into()
+    }
+}
+
+impl State<Worker, Nitro> for Idle {
+    fn next(self, _action: Nitro) -> Worker {
+        Fast.into()
+    }
+}
+
+#[test]
+fn experimental_transitions_are_only_taken_when_asked() {
+    let e = Worker::from(Idle).next(Beta.into()).unwrap_err();
+    assert_eq!(e.state.name(), "Idle");
+
+    let busy = Worker::from(Idle).next_with(Beta.into(), true).unwrap();
+    assert_eq!(busy.name(), "Busy");
+
+    let busy = Machine::next_experimental(Worker::from(Idle), Beta.into()).unwrap();
+    assert_eq!(busy.name(), "Busy");
+
+    let busy = Worker::from(Idle).next_with(Start.into(), false).unwrap();
+    assert_eq!(busy.name(), "Busy");
+}
+
+#[test]
+fn optional_transitions_follow_the_enabled_flags() {
+    assert_ne!(WorkerFlagged::TURBO, WorkerFlagged::NITRO);
+
+    let flagged = WorkerFlagged::new(Idle.into(), WorkerFlagged::TURBO);
+    let fast = flagged.next(Turbo.into()).unwrap();
+    assert_eq!(fast.state().name(), "Fast");
+    assert_eq!(fast.enabled(), WorkerFlagged::TURBO);
+
+    let flagged = WorkerFlagged::new(Idle.into(), WorkerFlagged::TURBO);
+    let e = flagged.next(Nitro.into()).unwrap_err();
+    assert_eq!(e.state.enabled(), WorkerFlagged::TURBO);
+    assert_eq!(e.state.into_inner().name(), "Idle");
+
+    let e = Worker::from(Idle).next(Turbo.into()).unwrap_err();
+    assert_eq!(e.state.name(), "Idle");
+}
+
+#[test]
+fn flagged_machines_take_the_other_transitions_as_usual() {
+    let flagged = WorkerFlagged::new(Idle.into(), 0);
+    let busy = flagged.next(Start.into()).unwrap();
+    assert!(busy.is_finished());
+    assert_eq!(
+        format!("{:?}", busy),
+        "WorkerFlagged { state: Busy(Busy), enabled: 0 }"
+    );
+}
@@ -0,0 +1,92 @@
+use state_machine::{state_machine_fragment, Action, State};
+
+state_machine_fragment! {
+    shutdown {
+        terminal Halted,
+        * { Halt => Halted },
+        Halted {},
+    }
+}
+
+state_machine_fragment! {
+    pausing {
+        Paused { Resume => Busy },
+    }
+}
+
+#[derive(Debug)]
+pub struct Idle;
+#[derive(Debug)]
+pub struct Busy;
+#[derive(Debug)]
+pub struct Paused;
+#[derive(Debug)]
+pub struct Halted;
+
+#[derive(Debug)]
+pub struct Start;
+#[derive(Debug)]
+pub struct Pause;
+#[derive(Debug)]
+pub struct Resume;
+#[derive(Debug)]
+pub struct Halt;
+
+shutdown! {
+    => pausing;
+    Worker,
+    WorkerAction,
+    Idle { Start => Busy },
+    Busy { Pause => Paused },
+}
+
+impl State<Worker, Start> for Idle {
+    fn next(self, _action: Start) -> Worker {
+        Busy.into()
+    }
+}
+
+impl State<Worker, Pause> for Busy {
+    fn next(self, _action: Pause) -> Worker {
+        Paused.into()
+    }
+}
+
+impl State<Worker, Resume> for Paused {
+    fn next(self, _action: Resume) -> Worker {
+        Busy.into()
+    }
+}
+
+impl State<Worker, Halt> for Worker {
+    fn next(self, _action: Halt) -> Worker {
+        Halted.into()
+    }
+}
+
+#[test]
+fn machines_get_the_fragment_items() {
+    assert_eq!(
+        Worker::TRANSITIONS,
+        [
+            ("Idle", "Start", &["Busy"][..]),
+            ("Idle", "Halt", &["Halted"][..]),
+            ("Busy", "Pause", &["Paused"][..]),
+            ("Busy", "Halt", &["Halted"][..]),
+            ("Paused", "Resume", &["Busy"][..]),
+            ("Paused", "Halt", &["Halted"][..]),
+        ]
+    );
+
+    let worker = Worker::from(Idle)
+        .next(Start.into())
+        .unwrap()
+        .next(Pause.into())
+        .unwrap()
+        .next(Resume.into())
+        .unwrap()
+        .next(Halt.into())
+        .unwrap();
+    assert!(worker.is_finished());
+    assert!(worker.next(Start.into()).is_err());
+}
@@ -0,0 +1,86 @@
+use state_machine::{state_machine, Action, State};
+
+#[derive(Debug, Default)]
+pub struct Idle;
+#[derive(Debug)]
+pub struct Busy;
+#[derive(Debug)]
+pub struct Paused;
+#[derive(Debug)]
+pub struct Off;
+
+#[derive(Debug)]
+pub struct Start;
+#[derive(Debug)]
+pub struct Pause;
+#[derive(Debug)]
+pub struct Stop;
+#[derive(Debug)]
+pub struct Reset;
+
+state_machine! {
+    Worker,
+    WorkerAction,
+    initial Idle,
+    terminal Off,
+    * { Reset => Idle },
+    Idle { Start => Busy },
+    Busy { Pause => Paused, Stop => Off },
+    Paused { Reset => Busy },
+}
+
+impl State<Worker, Start> for Idle {
+    fn next(self, _action: Start) -> Worker {
+        Busy.into()
+    }
+}
+
+impl State<Worker, Pause> for Busy {
+    fn next(self, _action: Pause) -> Worker {
+        Paused.into()
+    }
+}
+
+impl State<Worker, Stop> for Busy {
+    fn next(self, _action: Stop) -> Worker {
+        Off.into()
+    }
+}
+
+impl State<Worker, Reset> for Paused {
+    fn next(self, _action: Reset) -> Worker {
+        Busy.into()
+    }
+}
+
+impl State<Worker, Reset> for Worker {
+    fn next(self, _action: Reset) -> Worker {
+        Idle.into()
+    }
+}
+
+#[test]
+fn global_transitions_apply_in_every_state() {
+    let busy = Worker::new().next(Start.into()).unwrap();
+    let idle = busy.next(Reset.into()).unwrap();
+    assert_eq!(idle.name(), "Idle");
+    assert_eq!(idle.next(Reset.into()).unwrap().name(), "Idle");
+}
+
+#[test]
+fn states_handling_the_action_override_them() {
+    let paused = Worker::from(Paused);
+    assert_eq!(paused.next(Reset.into()).unwrap().name(), "Busy");
+}
+
+#[test]
+fn terminal_states_stay_finished() {
+    let off = Worker::from(Off);
+    assert!(off.is_finished());
+    assert_eq!(off.next(Reset.into()).unwrap().name(), "Off");
+    for (_, next_states) in WorkerKind::Off {
+        assert_eq!(next_states, [WorkerKind::Off]);
+    }
+    assert!(!Worker::dot().contains("Off -> Idle"));
+    assert!(Worker::dot().contains("Busy -> Idle"));
+}
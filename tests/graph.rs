@@ -0,0 +1,59 @@
+#![cfg(feature = "petgraph")]
+
+use state_machine::{state_machine, Action, State};
+
+#[derive(Debug)]
+pub struct Idle;
+#[derive(Debug)]
+pub struct Busy;
+#[derive(Debug)]
+pub struct Off;
+
+#[derive(Debug)]
+pub struct Start;
+#[derive(Debug)]
+pub struct Stop;
+
+state_machine! {
+    Worker,
+    WorkerAction,
+    terminal Off,
+    Idle { Start => Busy },
+    Busy { Stop => Idle | Off },
+    Off {},
+}
+
+impl State<Worker, Start> for Idle {
+    fn next(self, _action: Start) -> Worker {
+        Busy.into()
+    }
+}
+
+impl State<Worker, Stop> for Busy {
+    fn next(self, _action: Stop) -> Worker {
+        Off.into()
+    }
+}
+
+#[test]
+fn builds_the_transition_graph() {
+    let graph = Worker::to_graph();
+    assert_eq!(
+        graph.node_weights().copied().collect::<Vec<_>>(),
+        [WorkerKind::Idle, WorkerKind::Busy, WorkerKind::Off]
+    );
+
+    let edges = graph
+        .raw_edges()
+        .iter()
+        .map(|e| (graph[e.source()], e.weight, graph[e.target()]))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        edges,
+        [
+            (WorkerKind::Idle, WorkerActionKind::Start, WorkerKind::Busy),
+            (WorkerKind::Busy, WorkerActionKind::Stop, WorkerKind::Idle),
+            (WorkerKind::Busy, WorkerActionKind::Stop, WorkerKind::Off),
+        ]
+    );
+}
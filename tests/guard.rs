@@ -0,0 +1,48 @@
+use state_machine::{state_machine, Action, State};
+
+#[derive(Debug)]
+pub struct Idle;
+#[derive(Debug)]
+pub struct Low;
+#[derive(Debug)]
+pub struct High;
+
+#[derive(Debug)]
+pub struct Level(pub u8);
+
+state_machine! {
+    Gauge,
+    GaugeAction,
+    terminal Low,
+    terminal High,
+    Idle {
+        Level if |_: &Idle, l: &Level| l.0 < 10 => Low,
+        Level if |_: &Idle, l: &Level| l.0 >= 20 => High,
+    },
+}
+
+impl State<Gauge, Level> for Idle {
+    fn next(self, action: Level) -> Gauge {
+        if action.0 < 10 {
+            Low.into()
+        } else {
+            High.into()
+        }
+    }
+}
+
+#[test]
+fn guards_route_the_action() {
+    let low = Gauge::from(Idle).next(Level(3).into()).unwrap();
+    assert_eq!(low.name(), "Low");
+
+    let high = Gauge::from(Idle).next(Level(30).into()).unwrap();
+    assert_eq!(high.name(), "High");
+}
+
+#[test]
+fn rejects_what_no_guard_lets_through() {
+    let e = Gauge::from(Idle).next(Level(15).into()).unwrap_err();
+    assert_eq!(e.state.name(), "Idle");
+    assert!(matches!(e.action, GaugeAction::Level(Level(15))));
+}
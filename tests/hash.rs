@@ -0,0 +1,71 @@
+macro_rules! guarded_machine {
+    ($module:ident, $first:expr, $second:expr) => {
+        mod $module {
+            use state_machine::{state_machine, Action, State};
+
+            #[derive(Debug, Default)]
+            pub struct Idle;
+            #[derive(Debug)]
+            pub struct Low;
+            #[derive(Debug)]
+            pub struct High;
+            #[derive(Debug)]
+            pub struct Level(pub u8);
+
+            state_machine! {
+                pub Gauge,
+                pub GaugeAction,
+                initial Idle,
+                terminal Low,
+                terminal High,
+                Idle {
+                    Level if $first => Low,
+                    Level if $second => High,
+                },
+            }
+
+            impl State<Gauge, Level> for Idle {
+                fn next(self, action: Level) -> Gauge {
+                    if action.0 < 10 {
+                        Low.into()
+                    } else {
+                        High.into()
+                    }
+                }
+            }
+        }
+    };
+}
+
+guarded_machine!(
+    low_first,
+    |_: &Idle, l: &Level| l.0 < 10,
+    |_: &Idle, _: &Level| true
+);
+#[rustfmt::skip]
+guarded_machine!(
+    low_first_again,
+    |_:&Idle,l:&Level|l.0<10,
+    |_ : & Idle , _ : & Level | true
+);
+guarded_machine!(
+    catch_all_first,
+    |_: &Idle, _: &Level| true,
+    |_: &Idle, l: &Level| l.0 < 10
+);
+
+#[test]
+fn hash_ignores_guard_spacing() {
+    assert_eq!(
+        low_first::Gauge::MACHINE_HASH,
+        low_first_again::Gauge::MACHINE_HASH
+    );
+}
+
+#[test]
+fn hash_depends_on_guard_order() {
+    assert_ne!(
+        low_first::Gauge::MACHINE_HASH,
+        catch_all_first::Gauge::MACHINE_HASH
+    );
+}
@@ -0,0 +1,107 @@
+use std::cell::RefCell;
+
+use state_machine::{state_machine, Action, State, StateHooks};
+
+thread_local! {
+    static LOG: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+fn log(entry: &str) {
+    LOG.with(|log| log.borrow_mut().push(entry.to_string()));
+}
+
+fn take_log() -> Vec<String> {
+    LOG.with(|log| log.take())
+}
+
+#[derive(Debug, Default)]
+pub struct Idle;
+#[derive(Debug)]
+pub struct Busy(pub u32);
+
+#[derive(Debug)]
+pub struct Start;
+#[derive(Debug)]
+pub struct Tick;
+#[derive(Debug)]
+pub struct Stop;
+
+state_machine! {
+    Worker,
+    WorkerAction,
+    hooks,
+    initial Idle,
+    Idle { Start => Busy },
+    Busy {
+        Tick => @internal,
+        Stop => Idle,
+    },
+}
+
+impl StateHooks for Idle {
+    fn on_enter(&mut self) {
+        log("enter Idle");
+    }
+
+    fn on_exit(&mut self) {
+        log("exit Idle");
+    }
+}
+
+impl StateHooks for Busy {
+    fn on_enter(&mut self) {
+        log("enter Busy");
+    }
+
+    fn on_exit(&mut self) {
+        log("exit Busy");
+    }
+}
+
+impl State<Worker, Start> for Idle {
+    fn next(self, _action: Start) -> Worker {
+        log("start");
+        Busy(0).into()
+    }
+}
+
+impl State<Worker, Tick> for Busy {
+    fn next(self, _action: Tick) -> Worker {
+        log("tick");
+        Busy(self.0 + 1).into()
+    }
+}
+
+impl State<Worker, Stop> for Busy {
+    fn next(self, _action: Stop) -> Worker {
+        log("stop");
+        Idle.into()
+    }
+}
+
+#[test]
+fn hooks_surround_the_handler() {
+    let worker = Worker::new();
+    assert_eq!(take_log(), ["enter Idle"]);
+
+    let worker = worker.next(Start.into()).unwrap();
+    assert_eq!(take_log(), ["exit Idle", "start", "enter Busy"]);
+
+    let worker = worker.next(Stop.into()).unwrap();
+    assert_eq!(take_log(), ["exit Busy", "stop", "enter Idle"]);
+
+    let e = worker.next(Stop.into()).unwrap_err();
+    assert_eq!(e.state.name(), "Idle");
+    assert!(take_log().is_empty());
+}
+
+#[test]
+fn internal_transitions_skip_the_hooks() {
+    let worker = Worker::new().next(Start.into()).unwrap();
+    take_log();
+
+    let worker = worker.next(Tick.into()).unwrap();
+    let worker = worker.next(Tick.into()).unwrap();
+    assert_eq!(take_log(), ["tick", "tick"]);
+    assert!(matches!(worker, Worker::Busy(Busy(2))));
+}
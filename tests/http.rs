@@ -0,0 +1,76 @@
+#![cfg(feature = "http")]
+
+use state_machine::machines::http::{parse, HttpRequest};
+
+#[test]
+fn parses_a_request_head() {
+    let request = parse(
+        b"GET http://example.com:80/index.html HTTP/1.1\r\n\
+          Host:  example.com \r\n\
+          Accept: text/html\r\n\
+          \r\n",
+    )
+    .unwrap();
+    assert_eq!(request.method, "GET");
+    assert_eq!(request.target, "http://example.com:80/index.html");
+    assert_eq!(request.version, "HTTP/1.1");
+    assert_eq!(
+        request.headers,
+        [
+            ("Host".to_owned(), "example.com".to_owned()),
+            ("Accept".to_owned(), "text/html".to_owned()),
+        ]
+    );
+}
+
+#[test]
+fn parses_a_request_without_headers() {
+    let request = parse(b"DELETE /item HTTP/1.0\r\n\r\n").unwrap();
+    assert_eq!(request.method, "DELETE");
+    assert!(request.headers.is_empty());
+}
+
+#[test]
+fn rejects_malformed_heads() {
+    // Missing the version.
+    assert!(parse(b"GET /\r\n\r\n").is_none());
+    // Bare line feed.
+    assert!(parse(b"GET / HTTP/1.1\n\r\n").is_none());
+    // Header without a colon.
+    assert!(parse(b"GET / HTTP/1.1\r\nHost\r\n\r\n").is_none());
+    // Head not finished.
+    assert!(parse(b"GET / HTTP/1.1\r\nHost: example.com\r\n").is_none());
+}
+
+#[test]
+fn stops_at_the_rejected_byte() {
+    let mut state = HttpRequest::new();
+    for &b in b"GET " {
+        state = state.next(b.into()).unwrap();
+    }
+    let e = state.next(b'\r'.into()).unwrap_err();
+    assert_eq!(e.state.name(), "Target");
+    assert_eq!(e.action.name(), "Cr");
+    assert_eq!(e.expected, ["Token", "Sp", "Colon"]);
+}
+
+#[test]
+fn decodes_fields_as_utf8() {
+    let request = parse("GET /café HTTP/1.1\r\nX-Name: Zoë Ünal\r\n\r\n".as_bytes()).unwrap();
+    assert_eq!(request.target, "/café");
+    assert_eq!(
+        request.headers,
+        [("X-Name".to_owned(), "Zoë Ünal".to_owned())]
+    );
+}
+
+#[test]
+fn rejects_the_end_of_a_field_that_is_not_utf8() {
+    let mut state = HttpRequest::new();
+    for &b in b"GET / HTTP/1.1\r\nX-Name: \xff" {
+        state = state.next(b.into()).unwrap();
+    }
+    let e = state.next(b'\r'.into()).unwrap_err();
+    assert_eq!(e.state.name(), "HeaderValue");
+    assert_eq!(e.action.name(), "Cr");
+}
@@ -0,0 +1,95 @@
+#![cfg(feature = "std")]
+
+use state_machine::errors::MachineError;
+use state_machine::{state_machine, Action, InvalidTransition, State};
+
+#[derive(Debug)]
+pub struct Draft;
+#[derive(Debug)]
+pub struct Review;
+#[derive(Debug)]
+pub struct Published;
+
+#[derive(Debug, Clone)]
+pub struct Submit;
+#[derive(Debug, Clone)]
+pub struct Approve;
+
+state_machine! {
+    Post,
+    #[derive(Debug, Clone)]
+    PostAction,
+    on_invalid_transition Err,
+    Draft { Submit => Review },
+    // The handler goes back to `Draft` instead.
+    Review { Approve => Published },
+    // The wildcard handler goes back to `Draft` too.
+    Published { Submit => Published, _ => Review },
+}
+
+impl State<Post, Submit> for Draft {
+    fn next(self, _action: Submit) -> Post {
+        Review.into()
+    }
+}
+
+impl State<Post, Approve> for Review {
+    fn next(self, _action: Approve) -> Post {
+        Draft.into()
+    }
+}
+
+impl State<Post, Submit> for Published {
+    fn next(self, _action: Submit) -> Post {
+        self.into()
+    }
+}
+
+impl State<Post, PostAction> for Published {
+    fn next(self, _action: PostAction) -> Post {
+        Draft.into()
+    }
+}
+
+#[test]
+fn returns_invalid_transitions() {
+    let post = Post::from(Draft).next(Submit.into()).unwrap();
+    let e = post.next(Approve.into()).unwrap_err();
+    assert_eq!(e.state.name(), "Draft");
+    assert_eq!(e.action.name(), "Approve");
+    assert_eq!(
+        e.invalid,
+        Some(InvalidTransition {
+            state: "Review",
+            action: "Approve",
+            got: "Draft",
+            allowed: &["Published"],
+        })
+    );
+    assert!(matches!(
+        MachineError::from(e),
+        MachineError::InvalidTransition(_)
+    ));
+}
+
+#[test]
+fn rejections_are_not_invalid_transitions() {
+    let e = Post::from(Draft).next(Approve.into()).unwrap_err();
+    assert_eq!(e.invalid, None);
+    assert!(matches!(MachineError::from(e), MachineError::Rejected(_)));
+}
+
+#[test]
+fn wildcards_report_the_action_they_got() {
+    let e = Post::from(Published).next(Approve.into()).unwrap_err();
+    assert_eq!(e.action.name(), "Approve");
+    assert_eq!(
+        e.invalid,
+        Some(InvalidTransition {
+            state: "Published",
+            action: "Approve",
+            got: "Draft",
+            allowed: &["Review"],
+        })
+    );
+}
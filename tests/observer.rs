@@ -0,0 +1,65 @@
+use state_machine::{state_machine, Action, State, TransitionObserver};
+
+#[derive(Debug)]
+pub struct Idle;
+#[derive(Debug)]
+pub struct Busy;
+
+#[derive(Debug)]
+pub struct Start;
+#[derive(Debug)]
+pub struct Stop;
+
+state_machine! {
+    Worker,
+    WorkerAction,
+    Idle { Start => Busy },
+    Busy { Stop => Idle },
+}
+
+impl State<Worker, Start> for Idle {
+    fn next(self, _action: Start) -> Worker {
+        Busy.into()
+    }
+}
+
+impl State<Worker, Stop> for Busy {
+    fn next(self, _action: Stop) -> Worker {
+        Idle.into()
+    }
+}
+
+#[derive(Default)]
+struct Recorder(Vec<String>);
+
+impl TransitionObserver for Recorder {
+    fn on_transition(&mut self, from: &'static str, action: &'static str, to: &'static str) {
+        self.0.push(format!("{} --{}--> {}", from, action, to));
+    }
+
+    fn on_rejection(&mut self, state: &'static str, action: &'static str) {
+        self.0.push(format!("{} rejected {}", state, action));
+    }
+}
+
+#[test]
+fn reports_transitions_and_rejections() {
+    let mut recorder = Recorder::default();
+    let worker = Worker::from(Idle)
+        .next_observed(&mut recorder, Start.into())
+        .unwrap();
+    let worker = worker
+        .next_observed(&mut recorder, Start.into())
+        .unwrap_err()
+        .state;
+    let worker = worker.next_observed(&mut recorder, Stop.into()).unwrap();
+    assert_eq!(worker.name(), "Idle");
+    assert_eq!(
+        recorder.0,
+        [
+            "Idle --Start--> Busy",
+            "Busy rejected Start",
+            "Busy --Stop--> Idle"
+        ]
+    );
+}
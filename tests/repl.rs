@@ -0,0 +1,52 @@
+#![cfg(feature = "std")]
+
+use state_machine::{repl, state_machine, Action, State};
+
+#[derive(Debug)]
+pub struct Locked;
+#[derive(Debug)]
+pub struct Unlocked;
+
+#[derive(Debug, Default)]
+pub struct Coin;
+#[derive(Debug, Default)]
+pub struct Push;
+
+state_machine! {
+    Turnstile,
+    TurnstileAction,
+    Locked { Coin => Unlocked },
+    Unlocked { Push => Locked },
+}
+
+impl State<Turnstile, Coin> for Locked {
+    fn next(self, _action: Coin) -> Turnstile {
+        Unlocked.into()
+    }
+}
+
+impl State<Turnstile, Push> for Unlocked {
+    fn next(self, _action: Push) -> Turnstile {
+        Locked.into()
+    }
+}
+
+#[test]
+fn prints_the_allowed_actions_after_every_step() {
+    let mut output = Vec::new();
+    let state = repl::run(
+        Turnstile::from(Locked),
+        "Coin\nCoin\n".as_bytes(),
+        &mut output,
+    )
+    .unwrap();
+    assert_eq!(state.name(), "Unlocked");
+    let output = String::from_utf8(output).unwrap();
+    let allowed = output
+        .lines()
+        .filter_map(|line| line.split_once("Allowed actions: "))
+        .map(|(_, allowed)| allowed)
+        .collect::<Vec<_>>();
+    assert_eq!(allowed, ["[\"Coin\"]", "[\"Push\"]", "[\"Push\"]"]);
+    assert!(output.contains("Action Coin(Coin) rejected in state Unlocked(Unlocked)"));
+}
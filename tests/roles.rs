@@ -0,0 +1,112 @@
+use state_machine::{state_machine, Action, State};
+
+#[derive(Debug)]
+pub struct Draft;
+#[derive(Debug)]
+pub struct Review;
+#[derive(Debug)]
+pub struct Published;
+
+#[derive(Debug)]
+pub struct Edit;
+#[derive(Debug)]
+pub struct Submit;
+#[derive(Debug)]
+pub struct Approve;
+#[derive(Debug)]
+pub struct Reject;
+#[derive(Debug)]
+pub struct Archive;
+
+state_machine! {
+    Document,
+    DocumentAction,
+    * { requires(Admin) Archive => Draft },
+    Draft {
+        Edit => Draft,
+        requires(Author | Admin) Submit => Review,
+    },
+    Review {
+        requires(Editor) Approve | Reject => Published | Draft,
+    },
+    Published { Edit => Draft },
+}
+
+impl State<Document, Edit> for Draft {
+    fn next(self, _action: Edit) -> Document {
+        self.into()
+    }
+}
+
+impl State<Document, Submit> for Draft {
+    fn next(self, _action: Submit) -> Document {
+        Review.into()
+    }
+}
+
+impl State<Document, Approve> for Review {
+    fn next(self, _action: Approve) -> Document {
+        Published.into()
+    }
+}
+
+impl State<Document, Reject> for Review {
+    fn next(self, _action: Reject) -> Document {
+        Draft.into()
+    }
+}
+
+impl State<Document, Edit> for Published {
+    fn next(self, _action: Edit) -> Document {
+        Draft.into()
+    }
+}
+
+impl State<Document, Archive> for Document {
+    fn next(self, _action: Archive) -> Document {
+        Draft.into()
+    }
+}
+
+#[test]
+fn filters_the_allowed_actions_by_role() {
+    let draft = Document::from(Draft);
+    assert_eq!(draft.allowed_actions(), ["Edit", "Submit", "Archive"]);
+    assert_eq!(
+        draft.allowed_actions_for(DocumentRole::Admin),
+        ["Edit", "Submit", "Archive"]
+    );
+    assert_eq!(
+        draft.allowed_actions_for(DocumentRole::Author),
+        ["Edit", "Submit"]
+    );
+    assert_eq!(draft.allowed_actions_for(DocumentRole::Editor), ["Edit"]);
+
+    let review = Document::from(Review);
+    assert_eq!(
+        review.allowed_actions_for(DocumentRole::Editor),
+        ["Approve", "Reject"]
+    );
+    assert_eq!(
+        review.allowed_actions_for(DocumentRole::Author),
+        [] as [&str; 0]
+    );
+}
+
+#[test]
+fn dispatch_ignores_the_roles() {
+    let review = Document::from(Review);
+    assert!(matches!(
+        review.next(Approve.into()),
+        Ok(Document::Published(_))
+    ));
+}
+
+#[test]
+fn introspection_lists_the_required_roles() {
+    let json = Document::introspection();
+    assert!(json.contains(
+        r#"{"state": "Draft", "action": "Submit", "next": ["Review"], "requires": ["Author", "Admin"]}"#
+    ));
+    assert!(json.contains(r#"{"state": "Draft", "action": "Edit", "next": ["Draft"]}"#));
+}
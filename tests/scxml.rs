@@ -0,0 +1,140 @@
+#![cfg(feature = "std")]
+
+use state_machine::{state_machine_from_scxml, Action, State};
+
+#[derive(Debug, Default)]
+pub struct Closed;
+#[derive(Debug)]
+pub struct Opened;
+#[derive(Debug)]
+pub struct Locked;
+#[derive(Debug)]
+pub struct Broken;
+
+#[derive(Debug)]
+pub struct Open;
+#[derive(Debug)]
+pub struct Close;
+#[derive(Debug)]
+pub struct Lock;
+#[derive(Debug)]
+pub struct Unlock;
+#[derive(Debug)]
+pub struct Break;
+
+state_machine_from_scxml!("tests/scxml/door.scxml");
+
+impl State<Door, Open> for Closed {
+    fn next(self, _action: Open) -> Door {
+        Opened.into()
+    }
+}
+
+impl State<Door, Lock> for Closed {
+    fn next(self, _action: Lock) -> Door {
+        Locked.into()
+    }
+}
+
+impl State<Door, Close> for Opened {
+    fn next(self, _action: Close) -> Door {
+        Closed.into()
+    }
+}
+
+impl State<Door, Unlock> for Locked {
+    fn next(self, _action: Unlock) -> Door {
+        Closed.into()
+    }
+}
+
+impl State<Door, Break> for Locked {
+    fn next(self, _action: Break) -> Door {
+        Broken.into()
+    }
+}
+
+#[test]
+fn imports_the_states_and_transitions() {
+    let door = Door::new();
+    assert_eq!(door.name(), "Closed");
+
+    let door = door.next(Open.into()).unwrap();
+    let e = door.next(Lock.into()).unwrap_err();
+    assert_eq!(e.state.name(), "Opened");
+
+    let door = e
+        .state
+        .next(Close.into())
+        .unwrap()
+        .next(Lock.into())
+        .unwrap()
+        .next(Break.into())
+        .unwrap();
+    assert!(door.is_finished());
+}
+
+#[test]
+fn exports_the_document_it_was_imported_from() {
+    assert_eq!(
+        Door::to_scxml(),
+        r#"<scxml xmlns="http://www.w3.org/2005/07/scxml" xmlns:sm="urn:state_machine" version="1.0" name="Door" initial="Closed">
+  <state id="Closed">
+    <transition event="Open" target="Opened"/>
+    <transition event="Lock" target="Locked"/>
+  </state>
+  <state id="Opened">
+    <transition event="Close" target="Closed"/>
+  </state>
+  <state id="Locked">
+    <transition event="Unlock" target="Closed"/>
+    <transition event="Break" target="Broken"/>
+  </state>
+  <final id="Broken"/>
+</scxml>
+"#
+    );
+}
+
+mod gauge {
+    use state_machine::{state_machine, Action, State};
+
+    #[derive(Debug)]
+    pub struct Idle;
+    #[derive(Debug)]
+    pub struct Low;
+
+    #[derive(Debug)]
+    pub struct Level(pub u8);
+    #[derive(Debug)]
+    pub struct Reset;
+
+    state_machine! {
+        pub Gauge,
+        pub GaugeAction,
+        terminal Low,
+        Idle {
+            reject Reset,
+            Level if |_: &Idle, l: &Level| l.0 < 10 => Low,
+        },
+    }
+
+    impl State<Gauge, Level> for Idle {
+        fn next(self, _action: Level) -> Gauge {
+            Low.into()
+        }
+    }
+}
+
+#[test]
+fn exports_markers_and_escaped_guards() {
+    let scxml = gauge::Gauge::to_scxml();
+    assert!(scxml.contains(r#"<transition event="Reset" sm:kind="reject"/>"#));
+
+    // The spacing of the guard is up to the compiler.
+    let cond = scxml.split("cond=\"").nth(1).unwrap();
+    let cond = &cond[..cond.find('"').unwrap()];
+    assert!(cond.contains("&amp; Idle"));
+    assert!(cond.contains("&lt; 10"));
+    assert!(!cond.contains(['<', '>']));
+}
@@ -0,0 +1,78 @@
+#![cfg(feature = "serde")]
+
+use state_machine::serde::{de::DeserializeOwned, Deserialize, Serialize};
+use state_machine::snapshot::{IncompatibleSnapshot, Snapshot};
+use state_machine::{state_machine, Action, State};
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "state_machine::serde")]
+pub struct Idle;
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "state_machine::serde")]
+pub struct Busy(pub u32);
+
+#[derive(Debug)]
+pub struct Start(pub u32);
+#[derive(Debug)]
+pub struct Wait;
+
+state_machine! {
+    Worker,
+    WorkerAction,
+    terminal Busy,
+    Idle { Start => Busy },
+}
+
+state_machine! {
+    Other,
+    OtherAction,
+    Idle { Wait => Idle },
+}
+
+impl State<Worker, Start> for Idle {
+    fn next(self, action: Start) -> Worker {
+        Busy(action.0).into()
+    }
+}
+
+impl State<Other, Wait> for Idle {
+    fn next(self, _action: Wait) -> Other {
+        self.into()
+    }
+}
+
+fn serde_roundtrip<T: Serialize + DeserializeOwned>() {}
+
+#[test]
+fn snapshots_carry_the_machine_hash() {
+    serde_roundtrip::<Snapshot<Worker>>();
+
+    let worker = Worker::from(Idle).next(Start(3).into()).unwrap();
+    let snapshot = worker.snapshot();
+    assert_eq!(snapshot.machine_hash, Worker::MACHINE_HASH);
+    assert!(matches!(snapshot.state, Worker::Busy(Busy(3))));
+}
+
+#[test]
+fn restores_snapshots_of_the_same_machine_only() {
+    let snapshot = Snapshot {
+        machine_hash: Worker::MACHINE_HASH,
+        state: Worker::from(Busy(3)),
+    };
+    assert!(matches!(
+        Worker::restore(snapshot),
+        Ok(Worker::Busy(Busy(3)))
+    ));
+
+    let snapshot = Snapshot {
+        machine_hash: Other::MACHINE_HASH,
+        state: Worker::from(Idle),
+    };
+    assert_eq!(
+        Worker::restore(snapshot).unwrap_err(),
+        IncompatibleSnapshot {
+            expected_hash: Worker::MACHINE_HASH,
+            found_hash: Other::MACHINE_HASH,
+        }
+    );
+}
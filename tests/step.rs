@@ -0,0 +1,130 @@
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use state_machine::{state_machine, Action, State, StepError};
+
+#[derive(Debug, Default)]
+pub struct Idle;
+#[derive(Debug)]
+pub struct Running(Vec<u32>);
+#[derive(Debug)]
+pub struct Done;
+
+#[derive(Debug, Clone)]
+pub struct Start;
+#[derive(Debug, Clone)]
+pub struct Push(u32);
+#[derive(Debug, Clone)]
+pub struct Crash;
+#[derive(Debug, Clone)]
+pub struct Stray;
+
+state_machine! {
+    Job,
+    #[derive(Debug, Clone)] JobAction,
+    initial Idle,
+    terminal Done,
+    Idle { Start => Running },
+    Running {
+        Push => Running,
+        Crash => Running,
+        // The handler goes to `Idle` instead.
+        Stray => Done,
+    },
+}
+
+impl State<Job, Start> for Idle {
+    fn next(self, _action: Start) -> Job {
+        Running(Vec::new()).into()
+    }
+}
+
+impl State<Job, Push> for Running {
+    fn next(mut self, action: Push) -> Job {
+        self.0.push(action.0);
+        self.into()
+    }
+}
+
+impl State<Job, Crash> for Running {
+    fn next(self, _action: Crash) -> Job {
+        panic!("handler failed")
+    }
+}
+
+impl State<Job, Stray> for Running {
+    fn next(self, _action: Stray) -> Job {
+        Idle.into()
+    }
+}
+
+#[test]
+fn steps_in_place() {
+    let mut job = Job::new();
+    job.step(Start.into()).unwrap();
+    job.step(Push(1).into()).unwrap();
+    job.step(Push(2).into()).unwrap();
+    assert!(matches!(&job, Job::Running(Running(pushed)) if pushed == &[1, 2]));
+}
+
+#[test]
+fn rejected_actions_leave_the_machine_as_it_was() {
+    let mut job = Job::new();
+    job.step(Start.into()).unwrap();
+    job.step(Push(1).into()).unwrap();
+    assert!(matches!(job.step(Start.into()), Err(JobAction::Start(_))));
+    assert!(matches!(&job, Job::Running(Running(pushed)) if pushed == &[1]));
+}
+
+#[test]
+fn panicking_handlers_reset_the_machine() {
+    let mut job = Job::new();
+    job.step(Start.into()).unwrap();
+    job.step(Push(1).into()).unwrap();
+    let panic = catch_unwind(AssertUnwindSafe(|| job.step(Crash.into())));
+    assert!(panic.is_err());
+    assert_eq!(job.name(), "Idle");
+    job.step(Start.into()).unwrap();
+}
+
+#[test]
+fn invalid_transitions_unwind() {
+    let mut job = Job::new();
+    job.step(Start.into()).unwrap();
+    let panic = catch_unwind(AssertUnwindSafe(|| job.step(Stray.into())));
+    assert!(panic.is_err());
+    assert_eq!(job.name(), "Idle");
+}
+
+#[test]
+fn steps_through_slices() {
+    let mut job = Job::new();
+    let actions = [Start.into(), Push(1).into(), Push(2).into()];
+    assert_eq!(job.step_slice(&actions), Ok(3));
+    assert!(matches!(&job, Job::Running(Running(pushed)) if pushed == &[1, 2]));
+}
+
+#[test]
+fn slices_stop_at_the_first_rejection() {
+    let mut job = Job::new();
+    let actions = [Start.into(), Push(1).into(), Start.into(), Push(2).into()];
+    assert_eq!(
+        job.step_slice(&actions),
+        Err(StepError {
+            index: 2,
+            state: "Running",
+            action: "Start",
+            invalid: None,
+            position: None,
+        })
+    );
+    assert!(matches!(&job, Job::Running(Running(pushed)) if pushed == &[1]));
+}
+
+#[test]
+fn panicking_handlers_reset_the_machine_within_slices() {
+    let mut job = Job::new();
+    let actions = [Start.into(), Push(1).into(), Crash.into()];
+    let panic = catch_unwind(AssertUnwindSafe(|| job.step_slice(&actions)));
+    assert!(panic.is_err());
+    assert_eq!(job.name(), "Idle");
+}
@@ -0,0 +1,172 @@
+use state_machine::{state_machine, Action, Done, State, Submachine};
+
+mod auth {
+    use state_machine::{state_machine, Action, State};
+
+    #[derive(Debug, Default)]
+    pub struct Anonymous;
+    #[derive(Debug)]
+    pub struct Named(pub String);
+    #[derive(Debug)]
+    pub struct Authenticated(pub String);
+
+    #[derive(Debug)]
+    pub struct User(pub String);
+    #[derive(Debug)]
+    pub struct Password(pub String);
+
+    state_machine! {
+        pub Auth,
+        pub AuthAction,
+        initial Anonymous,
+        terminal Authenticated,
+        Anonymous { User => Named },
+        Named { Password => Authenticated | Anonymous },
+    }
+
+    impl State<Auth, User> for Anonymous {
+        fn next(self, action: User) -> Auth {
+            Named(action.0).into()
+        }
+    }
+
+    impl State<Auth, Password> for Named {
+        fn next(self, action: Password) -> Auth {
+            if action.0 == "secret" {
+                Authenticated(self.0).into()
+            } else {
+                Anonymous.into()
+            }
+        }
+    }
+}
+
+use auth::{Auth, Password, User};
+
+#[derive(Debug)]
+pub struct Disconnected;
+#[derive(Debug)]
+pub struct Connected {
+    auth: Auth,
+}
+#[derive(Debug)]
+pub struct Ready {
+    user: String,
+}
+
+#[derive(Debug)]
+pub struct Connect;
+#[derive(Debug)]
+pub struct Disconnect;
+
+state_machine! {
+    Session,
+    SessionAction,
+    Disconnected { Connect => Connected },
+    Connected {
+        submachine Auth: User | Password => Ready | Disconnected,
+        Disconnect => Disconnected,
+    },
+    Ready { Disconnect => Disconnected },
+}
+
+impl State<Session, Connect> for Disconnected {
+    fn next(self, _action: Connect) -> Session {
+        Connected { auth: Auth::new() }.into()
+    }
+}
+
+impl Submachine<Auth> for Connected {
+    fn child(&self) -> &Auth {
+        &self.auth
+    }
+
+    fn child_mut(&mut self) -> &mut Auth {
+        &mut self.auth
+    }
+}
+
+impl State<Session, Done<Auth>> for Connected {
+    fn next(self, done: Done<Auth>) -> Session {
+        match done.0 {
+            Auth::Authenticated(auth::Authenticated(user)) => Ready { user }.into(),
+            _ => Disconnected.into(),
+        }
+    }
+}
+
+impl State<Session, Disconnect> for Connected {
+    fn next(self, _action: Disconnect) -> Session {
+        Disconnected.into()
+    }
+}
+
+impl State<Session, Disconnect> for Ready {
+    fn next(self, _action: Disconnect) -> Session {
+        Disconnected.into()
+    }
+}
+
+fn child(session: &Session) -> &'static str {
+    match session {
+        Session::Connected(connected) => connected.auth.name(),
+        _ => panic!("not connected: {:?}", session),
+    }
+}
+
+#[test]
+fn hands_control_back_once_the_child_finishes() {
+    let session = Session::from(Disconnected).next(Connect.into()).unwrap();
+    assert_eq!(child(&session), "Anonymous");
+
+    let session = session.next(User("alice".into()).into()).unwrap();
+    assert_eq!(child(&session), "Named");
+    let session = session.next(Password("guess".into()).into()).unwrap();
+    assert_eq!(child(&session), "Anonymous");
+
+    let session = session.next(User("alice".into()).into()).unwrap();
+    let session = session.next(Password("secret".into()).into()).unwrap();
+    assert!(matches!(&session, Session::Ready(Ready { user }) if user == "alice"));
+}
+
+#[test]
+fn bubbles_up_what_the_child_rejects() {
+    let session = Session::from(Disconnected).next(Connect.into()).unwrap();
+    let e = session.next(Password("secret".into()).into()).unwrap_err();
+    assert_eq!(e.state.name(), "Connected");
+    assert!(matches!(e.action, SessionAction::Password(Password(p)) if p == "secret"));
+    assert_eq!(child(&e.state), "Anonymous");
+
+    let session = e.state.next(User("alice".into()).into()).unwrap();
+    let session = session.next(Disconnect.into()).unwrap();
+    assert_eq!(session.name(), "Disconnected");
+}
+
+#[test]
+fn child_actions_lead_to_the_completion_states() {
+    assert_eq!(
+        SessionKind::Connected.edges(),
+        [
+            (
+                SessionActionKind::Disconnect,
+                &[SessionKind::Disconnected][..]
+            ),
+            (
+                SessionActionKind::User,
+                &[
+                    SessionKind::Connected,
+                    SessionKind::Ready,
+                    SessionKind::Disconnected
+                ][..]
+            ),
+            (
+                SessionActionKind::Password,
+                &[
+                    SessionKind::Connected,
+                    SessionKind::Ready,
+                    SessionKind::Disconnected
+                ][..]
+            ),
+        ]
+    );
+}
@@ -0,0 +1,105 @@
+#![cfg(feature = "tcp")]
+
+use state_machine::machines::tcp::*;
+use state_machine::Machine;
+
+/// Names of the states the connection goes through, starting from `Closed`.
+fn walk(events: impl IntoIterator<Item = TcpEvent>) -> Vec<&'static str> {
+    let mut connection = TcpConnection::new();
+    let mut states = vec![connection.name()];
+    for event in events {
+        connection = connection.next(event).unwrap();
+        states.push(connection.name());
+    }
+    states
+}
+
+#[test]
+fn active_open_and_close() {
+    let events = [
+        ActiveOpen.into(),
+        RecvSynAck.into(),
+        Close.into(),
+        RecvAck.into(),
+        RecvFin.into(),
+        Timeout.into(),
+    ];
+    assert_eq!(
+        walk(events),
+        [
+            "Closed",
+            "SynSent",
+            "Established",
+            "FinWait1",
+            "FinWait2",
+            "TimeWait",
+            "Closed"
+        ]
+    );
+}
+
+#[test]
+fn passive_open_and_close() {
+    let events = [
+        PassiveOpen.into(),
+        RecvSyn.into(),
+        RecvAck.into(),
+        RecvFin.into(),
+        Close.into(),
+        RecvAck.into(),
+    ];
+    assert_eq!(
+        walk(events),
+        [
+            "Closed",
+            "Listen",
+            "SynReceived",
+            "Established",
+            "CloseWait",
+            "LastAck",
+            "Closed"
+        ]
+    );
+}
+
+#[test]
+fn simultaneous_close() {
+    let events = [
+        ActiveOpen.into(),
+        RecvSynAck.into(),
+        Close.into(),
+        RecvFin.into(),
+        RecvAck.into(),
+        Timeout.into(),
+    ];
+    assert_eq!(
+        walk(events),
+        [
+            "Closed",
+            "SynSent",
+            "Established",
+            "FinWait1",
+            "Closing",
+            "TimeWait",
+            "Closed"
+        ]
+    );
+}
+
+#[test]
+fn rejects_segments_out_of_the_handshake() {
+    let e = TcpConnection::new().next(RecvSyn.into()).unwrap_err();
+    assert_eq!(e.state.name(), "Closed");
+    assert_eq!(e.action.name(), "RecvSyn");
+    assert_eq!(e.expected, ["PassiveOpen", "ActiveOpen"]);
+
+    let established = TcpConnection::new()
+        .step_all([ActiveOpen.into(), RecvSynAck.into()])
+        .unwrap();
+    let (e, index) = established
+        .step_all([RecvFin.into(), Timeout.into()])
+        .unwrap_err();
+    assert_eq!(index, 1);
+    assert_eq!(e.state.name(), "CloseWait");
+    assert_eq!(e.action.name(), "Timeout");
+}
@@ -0,0 +1,94 @@
+use state_machine::{instantiate_machine, state_machine_template, Action, State};
+
+state_machine_template! {
+    Toggle<On, Off, Flip> {
+        On { Flip => Off },
+        Off { Flip => On },
+    }
+}
+
+mod light {
+    use super::*;
+
+    #[derive(Debug)]
+    pub struct Lit;
+    #[derive(Debug)]
+    pub struct Dark;
+    #[derive(Debug)]
+    pub struct Switch;
+
+    instantiate_machine!(
+        Toggle,
+        states = { On = Lit, Off = Dark, Flip = Switch },
+        pub Light,
+        pub LightAction
+    );
+
+    impl State<Light, Switch> for Lit {
+        fn next(self, _action: Switch) -> Light {
+            Dark.into()
+        }
+    }
+
+    impl State<Light, Switch> for Dark {
+        fn next(self, _action: Switch) -> Light {
+            Lit.into()
+        }
+    }
+}
+
+mod door {
+    use super::*;
+
+    #[derive(Debug)]
+    pub struct Open;
+    #[derive(Debug)]
+    pub struct Shut;
+    #[derive(Debug)]
+    pub struct Swing;
+
+    instantiate_machine!(
+        Toggle,
+        states = { On = Open, Off = Shut, Flip = Swing },
+        pub Door,
+        pub DoorAction
+    );
+
+    impl State<Door, Swing> for Open {
+        fn next(self, _action: Swing) -> Door {
+            Shut.into()
+        }
+    }
+
+    impl State<Door, Swing> for Shut {
+        fn next(self, _action: Swing) -> Door {
+            Open.into()
+        }
+    }
+}
+
+#[test]
+fn instances_get_the_template_transitions() {
+    use door::*;
+    use light::*;
+
+    assert_eq!(
+        Light::TRANSITIONS,
+        [
+            ("Lit", "Switch", &["Dark"][..]),
+            ("Dark", "Switch", &["Lit"][..])
+        ]
+    );
+    assert_eq!(
+        Door::TRANSITIONS,
+        [
+            ("Open", "Swing", &["Shut"][..]),
+            ("Shut", "Swing", &["Open"][..])
+        ]
+    );
+
+    let light = Light::from(Lit).next(Switch.into()).unwrap();
+    assert_eq!(light.name(), "Dark");
+    let door = Door::from(Shut).next(Swing.into()).unwrap();
+    assert_eq!(door.name(), "Open");
+}
@@ -0,0 +1,120 @@
+use state_machine::{state_machine, Action, Position, State, StepError};
+
+#[derive(Debug, Default)]
+pub struct Words(pub usize);
+
+#[derive(Debug)]
+pub struct Letter;
+#[derive(Debug)]
+pub struct Blank;
+#[derive(Debug)]
+pub struct Other;
+
+state_machine! {
+    Text,
+    TextAction,
+    initial Words,
+    Words {
+        reject Other,
+        Letter => Words,
+        Blank => Words,
+    },
+}
+
+impl From<char> for TextAction {
+    fn from(c: char) -> TextAction {
+        match c {
+            c if c.is_alphabetic() => Letter.into(),
+            c if c.is_whitespace() => Blank.into(),
+            _ => Other.into(),
+        }
+    }
+}
+
+impl From<u8> for TextAction {
+    fn from(b: u8) -> TextAction {
+        char::from(b).into()
+    }
+}
+
+impl State<Text, Letter> for Words {
+    fn next(self, _action: Letter) -> Text {
+        Words(self.0 + 1).into()
+    }
+}
+
+impl State<Text, Blank> for Words {
+    fn next(self, _action: Blank) -> Text {
+        self.into()
+    }
+}
+
+#[test]
+fn steps_through_text() {
+    let mut text = Text::new();
+    assert_eq!(text.step_str("été\nhiver"), Ok(9));
+    assert!(matches!(text, Text::Words(Words(8))));
+}
+
+#[test]
+fn tells_where_the_rejected_character_is() {
+    let mut text = Text::new();
+    let e = text.step_str("déjà vu\nen 2é").unwrap_err();
+    assert_eq!(
+        e,
+        StepError {
+            index: 11,
+            state: "Words",
+            action: "Other",
+            invalid: None,
+            position: Some(Position {
+                line: 2,
+                column: 4,
+                offset: 13,
+            }),
+        }
+    );
+    assert_eq!(
+        e.to_string(),
+        "Line 2, column 4: state Words rejected action Other"
+    );
+    assert!(matches!(text, Text::Words(Words(8))));
+}
+
+#[test]
+fn counts_columns_in_bytes() {
+    let mut text = Text::new();
+    let e = text.step_bytes(b"ab\n\ncd?").unwrap_err();
+    assert_eq!(e.index, 6);
+    assert_eq!(
+        e.position,
+        Some(Position {
+            line: 3,
+            column: 3,
+            offset: 6,
+        })
+    );
+}
+
+#[cfg(feature = "codespan")]
+#[test]
+fn renders_a_diagnostic() {
+    use codespan_reporting::files::SimpleFile;
+    use codespan_reporting::term::{self, termcolor::NoColor};
+
+    let source = "déjà vu\nen 2é";
+    let e = Text::new().step_str(source).unwrap_err();
+    let file = SimpleFile::new("input.txt", source);
+    let mut rendered = NoColor::new(Vec::new());
+    term::emit(
+        &mut rendered,
+        &term::Config::default(),
+        &file,
+        &e.to_diagnostic(()),
+    )
+    .unwrap();
+    let rendered = String::from_utf8(rendered.into_inner()).unwrap();
+    assert!(rendered.starts_with("error: state Words rejected action Other"));
+    assert!(rendered.contains("input.txt:2:4"));
+    assert!(rendered.contains("rejected by Words"));
+}
@@ -0,0 +1,16 @@
+use state_machine::state_machine;
+
+pub struct Idle;
+pub struct Busy;
+pub struct Start;
+
+state_machine! {
+    Worker,
+    WorkerAction,
+    Idle {
+        Start => Busy,
+        Start => Idle,
+    },
+}
+
+fn main() {}
@@ -0,0 +1,38 @@
+#![deny(deprecated)]
+
+use state_machine::{state_machine, Action, State};
+
+#[derive(Debug)]
+pub struct Idle;
+#[derive(Debug)]
+pub struct Busy;
+#[derive(Debug)]
+pub struct Start;
+#[derive(Debug)]
+pub struct Old;
+
+state_machine! {
+    Worker,
+    WorkerAction,
+    terminal Busy,
+    unit Old,
+    deprecated Old => Start,
+    Idle { Start => Busy },
+}
+
+impl State<Worker, Start> for Idle {
+    fn next(self, _action: Start) -> Worker {
+        Busy.into()
+    }
+}
+
+impl From<Old> for Start {
+    fn from(_: Old) -> Start {
+        Start
+    }
+}
+
+fn main() {
+    let _ = Worker::from(Idle).next(WorkerAction::Old(Old));
+    let _ = Worker::from(Idle).next(WorkerAction::OLD);
+}
@@ -0,0 +1,19 @@
+use state_machine::state_machine;
+
+pub struct Idle;
+pub struct Busy;
+pub struct Start;
+pub struct Stop;
+
+state_machine! {
+    Worker,
+    WorkerAction,
+    exhaustive,
+    Idle {
+        Start => Busy,
+        reject Stop,
+    },
+    Busy { Stop => Idle },
+}
+
+fn main() {}
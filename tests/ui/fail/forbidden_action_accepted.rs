@@ -0,0 +1,15 @@
+use state_machine::state_machine;
+
+pub struct Idle;
+pub struct Busy;
+pub struct Start;
+
+state_machine! {
+    Worker,
+    WorkerAction,
+    forbid Busy { Start },
+    Idle { Start => Busy },
+    Busy { Start => Busy },
+}
+
+fn main() {}
@@ -0,0 +1,14 @@
+use state_machine::state_machine;
+
+pub struct Idle;
+pub struct Busy;
+pub struct Start;
+
+state_machine! {
+    Worker,
+    WorkerAction,
+    * { _ => Idle },
+    Idle { Start => Busy },
+}
+
+fn main() {}
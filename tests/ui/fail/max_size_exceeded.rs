@@ -0,0 +1,24 @@
+use state_machine::{state_machine, Action, State};
+
+#[derive(Debug)]
+pub struct Empty;
+#[derive(Debug)]
+pub struct Full(pub [u8; 1024]);
+#[derive(Debug)]
+pub struct Fill;
+
+state_machine! {
+    Buffer,
+    BufferAction,
+    max_size 16,
+    terminal Full,
+    Empty { Fill => Full },
+}
+
+impl State<Buffer, Fill> for Empty {
+    fn next(self, _action: Fill) -> Buffer {
+        Full([0; 1024]).into()
+    }
+}
+
+fn main() {}
@@ -0,0 +1,15 @@
+use state_machine::state_machine;
+
+state_machine! {
+    mermaid "
+        stateDiagram-v2
+        [*] --> Idle
+        state Busy {
+            Working --> Done : Finish
+        }
+    ",
+    Worker,
+    WorkerAction,
+}
+
+fn main() {}
@@ -0,0 +1,19 @@
+use state_machine::state_machine;
+
+pub struct Idle;
+pub struct Busy;
+pub struct Orphan;
+pub struct Start;
+pub struct Stop;
+
+state_machine! {
+    Worker,
+    WorkerAction,
+    strict,
+    initial Idle,
+    Idle { Start => Busy },
+    Busy { Stop => Idle },
+    Orphan { Stop => Idle },
+}
+
+fn main() {}
@@ -0,0 +1,35 @@
+// Deriving on the wrappers replaces the built-in `Debug` derive instead of conflicting with it.
+use state_machine::{state_machine, Action, State};
+
+#[derive(Debug, Clone)]
+pub struct Off;
+#[derive(Debug, Clone)]
+pub struct On;
+#[derive(Debug, Clone)]
+pub struct Toggle;
+
+state_machine! {
+    #[derive(Debug, Clone)] Switch,
+    #[derive(Debug, Clone)] SwitchAction,
+    Off { Toggle => On },
+    On { Toggle => Off },
+}
+
+impl State<Switch, Toggle> for Off {
+    fn next(self, _action: Toggle) -> Switch {
+        On.into()
+    }
+}
+
+impl State<Switch, Toggle> for On {
+    fn next(self, _action: Toggle) -> Switch {
+        Off.into()
+    }
+}
+
+fn main() {
+    let switch = Switch::from(Off);
+    let action = SwitchAction::from(Toggle);
+    let _ = format!("{:?} {:?}", switch.clone(), action.clone());
+    let _ = switch.next(action);
+}
@@ -0,0 +1,33 @@
+// Payloads without `Debug` can be used once the derives are chosen by hand.
+use state_machine::{state_machine, Action, State};
+
+#[derive(Clone)]
+pub struct Off;
+#[derive(Clone)]
+pub struct On;
+#[derive(Clone)]
+pub struct Toggle;
+
+state_machine! {
+    #[derive(Clone)] Switch,
+    #[derive(Clone)] SwitchAction,
+    Off { Toggle => On },
+    On { Toggle => Off },
+}
+
+impl State<Switch, Toggle> for Off {
+    fn next(self, _action: Toggle) -> Switch {
+        On.into()
+    }
+}
+
+impl State<Switch, Toggle> for On {
+    fn next(self, _action: Toggle) -> Switch {
+        Off.into()
+    }
+}
+
+fn main() {
+    let switch = Switch::from(Off).clone();
+    let _ = switch.next(Toggle.into());
+}
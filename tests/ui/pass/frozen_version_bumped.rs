@@ -0,0 +1,39 @@
+// The machine gained `Reset` since version 1, and both the version and the pin were updated.
+use state_machine::{state_machine, Action, State};
+
+#[derive(Debug)]
+pub struct Off;
+#[derive(Debug)]
+pub struct On;
+#[derive(Debug)]
+pub struct Toggle;
+#[derive(Debug)]
+pub struct Reset;
+
+state_machine! {
+    Switch,
+    SwitchAction,
+    frozen 2 = 0x86b07fae764255c4,
+    Off { Toggle => On },
+    On { Toggle | Reset => Off },
+}
+
+impl State<Switch, Toggle> for Off {
+    fn next(self, _action: Toggle) -> Switch {
+        On.into()
+    }
+}
+
+impl State<Switch, Toggle> for On {
+    fn next(self, _action: Toggle) -> Switch {
+        Off.into()
+    }
+}
+
+impl State<Switch, Reset> for On {
+    fn next(self, _action: Reset) -> Switch {
+        Off.into()
+    }
+}
+
+fn main() {}
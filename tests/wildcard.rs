@@ -0,0 +1,66 @@
+use state_machine::{state_machine, Action, State};
+
+#[derive(Debug)]
+pub struct Listening(pub Vec<&'static str>);
+#[derive(Debug)]
+pub struct Stopped;
+
+#[derive(Debug)]
+pub struct Ping;
+#[derive(Debug)]
+pub struct Pong;
+#[derive(Debug)]
+pub struct Stop;
+
+state_machine! {
+    Listener,
+    ListenerAction,
+    terminal Stopped,
+    Listening {
+        Stop => Stopped,
+        _ => Listening,
+    },
+    Stopped {
+        reject Ping,
+        reject Pong,
+    },
+}
+
+impl State<Listener, Stop> for Listening {
+    fn next(self, _action: Stop) -> Listener {
+        Stopped.into()
+    }
+}
+
+impl State<Listener, ListenerAction> for Listening {
+    fn next(mut self, action: ListenerAction) -> Listener {
+        self.0.push(action.name());
+        self.into()
+    }
+}
+
+#[test]
+fn wildcard_catches_what_no_other_transition_takes() {
+    let listener = Listener::from(Listening(Vec::new()))
+        .next(Ping.into())
+        .unwrap()
+        .next(Pong.into())
+        .unwrap()
+        .next(Ping.into())
+        .unwrap();
+    assert!(matches!(
+        &listener,
+        Listener::Listening(Listening(heard)) if heard == &["Ping", "Pong", "Ping"]
+    ));
+
+    let stopped = listener.next(Stop.into()).unwrap();
+    assert_eq!(stopped.name(), "Stopped");
+}
+
+#[test]
+fn wildcard_does_not_reach_other_states() {
+    let stopped = Listener::from(Stopped);
+    let e = stopped.next(Ping.into()).unwrap_err();
+    assert_eq!(e.state.name(), "Stopped");
+    assert_eq!(e.action.name(), "Ping");
+}